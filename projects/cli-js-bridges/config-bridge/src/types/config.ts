@@ -1,4 +1,5 @@
+import type { _NovelSagaConfig } from './_novelsaga_config'
 import type { OverridableConfig } from './_overridable_config'
 import type { RootConfig } from './_root_config'
 
-export type NovelSagaConfig = OverridableConfig & RootConfig
+export type NovelSagaConfig = OverridableConfig & RootConfig & Pick<_NovelSagaConfig, 'extends'>
@@ -1,9 +1,9 @@
-use std::path::PathBuf;
+use std::{net::SocketAddr, path::PathBuf};
 
 use clap::{Parser, Subcommand};
 use path_absolutize::Absolutize;
 
-use crate::commands::metadata::MetadataCommands;
+use crate::commands::{config::ConfigCommands, metadata::MetadataCommands};
 
 /// JavaScript 运行时选择
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -32,11 +32,61 @@ impl std::str::FromStr for RuntimeChoice {
   }
 }
 
+/// LSP 服务监听方式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LspListen {
+  /// 通过 stdin/stdout 与单个客户端通信（默认）
+  Stdio,
+  /// 监听 TCP 地址，例如 `tcp://127.0.0.1:9257`
+  Tcp(SocketAddr),
+  /// 监听 Unix Socket，例如 `unix:/tmp/novelsaga.sock`
+  Unix(PathBuf),
+}
+
+impl std::str::FromStr for LspListen {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if s == "stdio" {
+      return Ok(LspListen::Stdio);
+    }
+    if let Some(addr) = s.strip_prefix("tcp://") {
+      return addr
+        .parse()
+        .map(LspListen::Tcp)
+        .map_err(|e| format!("Invalid TCP address '{addr}': {e}"));
+    }
+    if let Some(path) = s.strip_prefix("unix:") {
+      // 同时接受 `unix:/path` 与 `unix:///path`
+      let path = path.strip_prefix("//").unwrap_or(path);
+      if path.is_empty() {
+        return Err("Unix socket path must not be empty".to_string());
+      }
+      return Ok(LspListen::Unix(PathBuf::from(path)));
+    }
+    Err(format!(
+      "Invalid listen address: '{s}'. Expected tcp://HOST:PORT, unix:/PATH or stdio"
+    ))
+  }
+}
+
 /// Subcommands for `NovelSaga` CLI
 #[derive(Subcommand, Clone, Debug)]
 pub enum Commands {
-  /// Start as LSP server (communicates via stdin/stdout)
-  Lsp {},
+  /// Start as LSP server (communicates via stdin/stdout by default)
+  Lsp {
+    /// Communicate via stdin/stdout (default)
+    #[arg(long, conflicts_with = "listen")]
+    stdio: bool,
+
+    /// Accept clients on `tcp://HOST:PORT` or `unix:/PATH`; each connection gets its own server
+    #[arg(long, value_name = "ADDR")]
+    listen: Option<LspListen>,
+
+    /// Allow `--listen` on a non-loopback TCP address; clients are not authenticated
+    #[arg(long, requires = "listen")]
+    allow_remote: bool,
+  },
 
   /// Initialize a new `NovelSaga` project
   Init {
@@ -56,13 +106,19 @@ pub enum Commands {
     check: bool,
   },
 
-  /// Check configuration files for errors
+  /// Check configuration files against the config schema
   Check {
-    /// Files to check (default: auto-detect)
+    /// Config files or workspace directories to check (default: current directory)
     #[arg()]
     files: Vec<PathBuf>,
   },
 
+  /// Inspect the effective configuration
+  Config {
+    #[command(subcommand)]
+    command: ConfigCommands,
+  },
+
   /// Manage document metadata
   #[command(flatten)]
   Metadata(MetadataCommands),
@@ -89,6 +145,11 @@ pub struct Cli {
   #[arg(long, global = true)]
   deno_path: Option<PathBuf>,
 
+  /// Override a config setting, e.g. `--config fmt.indent_spaces=0` (repeatable;
+  /// takes precedence over config files and `NOVELSAGA__*` environment variables)
+  #[arg(long = "config", value_name = "KEY=VALUE", global = true)]
+  config_overrides: Vec<String>,
+
   #[command(subcommand)]
   pub command: Option<Commands>,
 }
@@ -120,6 +181,11 @@ impl Cli {
     self.deno_path.as_ref().map(|p| p.absolutize().unwrap().to_path_buf())
   }
 
+  /// 获取 `--config` 覆盖项（按出现顺序，后者优先）
+  pub fn get_config_overrides(&self) -> &[String] {
+    &self.config_overrides
+  }
+
   /// 验证并处理命令行参数
   fn validate(&self) {
     // 验证 Node.js 路径
@@ -159,11 +225,79 @@ mod tests {
   fn test_parse_lsp_subcommand() {
     let cli = Cli::parse_from(["novelsaga", "lsp"]);
     match &cli.command {
-      Some(Commands::Lsp {}) => {}
+      Some(Commands::Lsp { .. }) => {}
       _ => panic!("Expected Lsp command, got {:?}", cli.command),
     }
   }
 
+  #[test]
+  fn test_parse_lsp_listen_addresses() {
+    let cli = Cli::parse_from(["novelsaga", "lsp", "--listen", "tcp://127.0.0.1:9257"]);
+    match &cli.command {
+      Some(Commands::Lsp {
+        stdio,
+        listen,
+        allow_remote,
+      }) => {
+        assert!(!stdio);
+        assert!(!allow_remote);
+        assert_eq!(*listen, Some(LspListen::Tcp("127.0.0.1:9257".parse().unwrap())));
+      }
+      _ => panic!("Expected Lsp command, got {:?}", cli.command),
+    }
+
+    assert_eq!(
+      "unix:/tmp/novelsaga.sock".parse::<LspListen>(),
+      Ok(LspListen::Unix(PathBuf::from("/tmp/novelsaga.sock")))
+    );
+    assert_eq!(
+      "unix:///tmp/novelsaga.sock".parse::<LspListen>(),
+      Ok(LspListen::Unix(PathBuf::from("/tmp/novelsaga.sock")))
+    );
+    assert!("tcp://localhost".parse::<LspListen>().is_err());
+
+    let cli = Cli::parse_from(["novelsaga", "lsp", "--listen", "tcp://0.0.0.0:9257", "--allow-remote"]);
+    assert!(matches!(cli.command, Some(Commands::Lsp { allow_remote: true, .. })));
+    assert!(Cli::try_parse_from(["novelsaga", "lsp", "--allow-remote"]).is_err());
+    assert!("http://127.0.0.1:1".parse::<LspListen>().is_err());
+  }
+
+  #[test]
+  fn test_parse_lsp_stdio_conflicts_with_listen() {
+    let cli = Cli::parse_from(["novelsaga", "lsp", "--stdio"]);
+    assert!(matches!(
+      cli.command,
+      Some(Commands::Lsp {
+        stdio: true,
+        listen: None,
+        ..
+      })
+    ));
+
+    assert!(Cli::try_parse_from(["novelsaga", "lsp", "--stdio", "--listen", "unix:/tmp/x.sock"]).is_err());
+  }
+
+  #[test]
+  fn test_parse_repeated_config_overrides() {
+    let cli = Cli::parse_from([
+      "novelsaga",
+      "--config",
+      "fmt.indent_spaces=0",
+      "--config=workspace.cache_dir=out",
+      "config",
+      "show",
+      "ch1.md",
+    ]);
+    assert_eq!(
+      cli.get_config_overrides(),
+      ["fmt.indent_spaces=0", "workspace.cache_dir=out"]
+    );
+
+    // Global flag: also accepted after the subcommand
+    let cli = Cli::parse_from(["novelsaga", "check", "--config", "fmt.indent_spaces=0"]);
+    assert_eq!(cli.get_config_overrides(), ["fmt.indent_spaces=0"]);
+  }
+
   #[test]
   fn test_parse_init_subcommand() {
     let cli = Cli::parse_from(["novelsaga", "init", "/path/to/project"]);
@@ -180,7 +314,7 @@ mod tests {
     let cli = Cli::parse_from(["novelsaga", "--runtime", "node", "lsp"]);
     assert_eq!(cli.get_runtime_choice(), RuntimeChoice::Node, "Expected Node runtime");
     match &cli.command {
-      Some(Commands::Lsp {}) => {}
+      Some(Commands::Lsp { .. }) => {}
       _ => panic!("Expected Lsp command, got {:?}", cli.command),
     }
   }
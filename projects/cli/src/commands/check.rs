@@ -0,0 +1,243 @@
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+};
+
+use novelsaga_core::{
+  config::{
+    extends::config_layers,
+    schema::{SchemaViolation, validate_config_source},
+  },
+  discovery::FileDiscovery,
+  metadata::{EntitySchemas, metadata_dir_of},
+  state::{ConfigManager, init::Initializer},
+};
+
+use crate::metadata::pipeline;
+
+/// Validate config files against the config JSON Schema, and the frontmatter
+/// of metadata documents against the schema of their type.
+///
+/// Explicit directories and an empty `files` list (the current directory) are
+/// searched for the config files and metadata documents of their workspace;
+/// explicit files are checked as config files. Problems are printed as
+/// `path:line: key: message`; returns how many were found.
+pub fn run(files: &[PathBuf]) -> anyhow::Result<usize> {
+  let targets = if files.is_empty() {
+    vec![std::env::current_dir()?]
+  } else {
+    files.to_vec()
+  };
+
+  let mut config_files = Vec::new();
+  let mut metadata_problems = Vec::new();
+  let mut metadata_files = 0;
+  for target in targets {
+    if target.is_dir() {
+      let dir = target.canonicalize()?;
+      let config_manager = workspace_config_manager(&dir);
+      let discovery = pipeline::workspace_discovery(&dir, config_manager.as_ref());
+      config_files.extend(discovery.config_files());
+      let (checked, problems) = check_metadata_files(&dir, &discovery, config_manager.as_ref());
+      metadata_files += checked;
+      metadata_problems.extend(problems);
+    } else {
+      config_files.push(target);
+    }
+  }
+
+  let mut problems = 0;
+  for problem in config_files
+    .iter()
+    .flat_map(|path| check_config_file(path))
+    .chain(metadata_problems)
+  {
+    println!("{problem}");
+    problems += 1;
+  }
+  eprintln!(
+    "Checked {} config file(s) and {metadata_files} metadata document(s): {problems} problem(s) found",
+    config_files.len()
+  );
+  Ok(problems)
+}
+
+/// Config of the workspace containing `dir`; a broken root config is reported by the check itself.
+fn workspace_config_manager(dir: &Path) -> Option<ConfigManager> {
+  Initializer::get()
+    .ok()
+    .and_then(|state| ConfigManager::new(state.feature().clone(), dir).ok())
+}
+
+/// Check the metadata documents under `dir` against the frontmatter schemas of
+/// their types, returning how many were checked and the problems found.
+///
+/// Documents inside a nested metadata directory also use its `_schema` files.
+fn check_metadata_files(
+  dir: &Path,
+  discovery: &FileDiscovery,
+  config_manager: Option<&ConfigManager>,
+) -> (usize, Vec<String>) {
+  let layout = pipeline::metadata_layout(config_manager);
+  // A metadata directory whose schemas fail to load is reported once and its documents skipped
+  let mut schemas_by_dir: HashMap<Option<PathBuf>, Option<EntitySchemas>> = HashMap::new();
+  let mut checked = 0;
+  let mut problems = Vec::new();
+  for path in pipeline::discover_metadata_files(discovery, dir, &layout) {
+    let metadata_dir = metadata_dir_of(&path, dir, &layout);
+    let schemas = schemas_by_dir.entry(metadata_dir).or_insert_with_key(|metadata_dir| {
+      EntitySchemas::load_for(dir, metadata_dir.as_deref(), &layout)
+        .map_err(|error| problems.push(error.to_string()))
+        .ok()
+    });
+    let Some(schemas) = schemas.as_ref().filter(|schemas| !schemas.is_empty()) else {
+      continue;
+    };
+
+    checked += 1;
+    match std::fs::read_to_string(&path) {
+      Ok(content) => problems.extend(
+        pipeline::frontmatter_violations(schemas, &path, &content, &layout)
+          .into_iter()
+          .map(|violation| format_violation(&path, &violation)),
+      ),
+      Err(error) => problems.push(format!("{}: {error}", path.display())),
+    }
+  }
+  (checked, problems)
+}
+
+/// `path:line: key: message`, leaving out what is unknown.
+fn format_violation(path: &Path, violation: &SchemaViolation) -> String {
+  let display = path.display();
+  let location = violation
+    .line
+    .map_or_else(|| display.to_string(), |line| format!("{display}:{line}"));
+  if violation.key.is_empty() {
+    format!("{location}: {}", violation.message)
+  } else {
+    format!("{location}: {}: {}", violation.key, violation.message)
+  }
+}
+
+/// Problems found in one config file, one line each.
+pub fn check_config_file(path: &Path) -> Vec<String> {
+  let display = path.display();
+  let content = match std::fs::read_to_string(path) {
+    Ok(content) => content,
+    Err(error) => return vec![format!("{display}: {error}")],
+  };
+  // Files along the `extends` chain are checked on their own; here only the chain itself must resolve
+  if let Err(error) = config_layers(path) {
+    return vec![format!("{display}: {error}")];
+  }
+  match validate_config_source(path, &content) {
+    Ok(violations) => violations
+      .into_iter()
+      .map(|violation| format_violation(path, &violation))
+      .collect(),
+    Err(error) => vec![format!("{display}: {error}")],
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use novelsaga_core::discovery::FileDiscovery;
+  use tempfile::TempDir;
+
+  use super::{check_config_file, check_metadata_files};
+
+  #[test]
+  fn reports_schema_violations_with_lines() {
+    let workspace = TempDir::new().expect("tempdir");
+    let path = workspace.path().join("novelsaga.config.yaml");
+    std::fs::write(&path, "fmt:\n  indent_spaces: 2\n  indent: 4\n").expect("write config");
+
+    assert_eq!(
+      check_config_file(&path),
+      vec![format!(
+        "{}:3: fmt.indent: Additional properties are not allowed ('indent' was unexpected)",
+        path.display()
+      )]
+    );
+  }
+
+  #[test]
+  fn valid_and_unparsable_configs() {
+    let workspace = TempDir::new().expect("tempdir");
+    let valid = workspace.path().join(".novelsaga.toml");
+    std::fs::write(&valid, "[fmt]\nindent_spaces = 2\n").expect("write config");
+    assert!(check_config_file(&valid).is_empty());
+
+    let broken = workspace.path().join(".novelsaga.json");
+    std::fs::write(&broken, "{ \"fmt\": ").expect("write config");
+    let problems = check_config_file(&broken);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with(&format!("{}: ", broken.display())));
+
+    let extends_missing = workspace.path().join(".novelsaga.yaml");
+    std::fs::write(&extends_missing, "extends: [./house.yaml]\n").expect("write config");
+    let problems = check_config_file(&extends_missing);
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].contains("Cannot find `./house.yaml`"), "{problems:?}");
+  }
+
+  #[test]
+  fn reports_frontmatter_violations_of_metadata_documents() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    std::fs::create_dir_all(root.join("metadata/_schema")).expect("create schema dir");
+    std::fs::create_dir_all(root.join("metadata/characters")).expect("create metadata dir");
+    std::fs::write(
+      root.join("metadata/_schema/character.yaml"),
+      "required: [name]\nproperties:\n  age:\n    type: integer\n",
+    )
+    .expect("write schema");
+    let hero = root.join("metadata/characters/hero.md");
+    std::fs::write(&hero, "---\nname: Hero\nage: old\n---\nBody").expect("write hero");
+    let villain = root.join("metadata/characters/villain.md");
+    std::fs::write(&villain, "---\nage: 40\n---\nBody").expect("write villain");
+    std::fs::create_dir_all(root.join("metadata/scenes")).expect("create metadata dir");
+    std::fs::write(root.join("metadata/scenes/opening.md"), "---\nage: old\n---\n").expect("write scene");
+
+    let (checked, problems) = check_metadata_files(root, &FileDiscovery::new(root), None);
+    assert_eq!(checked, 3);
+    assert_eq!(
+      problems,
+      vec![
+        format!("{}:3: age: \"old\" is not of type \"integer\"", hero.display()),
+        format!("{}: \"name\" is a required property", villain.display()),
+      ]
+    );
+  }
+
+  #[test]
+  fn a_metadata_dir_with_broken_schemas_does_not_hide_the_others() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    std::fs::create_dir_all(root.join("metadata/_schema")).expect("create schema dir");
+    std::fs::create_dir_all(root.join("metadata/characters")).expect("create metadata dir");
+    std::fs::write(root.join("metadata/_schema/character.yaml"), "required: [name]\n").expect("write schema");
+    let villain = root.join("metadata/characters/villain.md");
+    std::fs::write(&villain, "---\nage: 40\n---\nBody").expect("write villain");
+    std::fs::create_dir_all(root.join("book/metadata/_schema")).expect("create nested schema dir");
+    std::fs::create_dir_all(root.join("book/metadata/characters")).expect("create nested metadata dir");
+    std::fs::write(root.join("book/metadata/_schema/character.yaml"), "required: [name\n").expect("write schema");
+    for name in ["hero", "sidekick"] {
+      std::fs::write(
+        root.join(format!("book/metadata/characters/{name}.md")),
+        "---\nage: 1\n---\n",
+      )
+      .expect("write document");
+    }
+
+    let (checked, problems) = check_metadata_files(root, &FileDiscovery::new(root), None);
+    assert_eq!(checked, 1);
+    assert_eq!(problems.len(), 2, "{problems:?}");
+    assert!(problems[0].contains("character.yaml"), "{problems:?}");
+    assert_eq!(
+      problems[1],
+      format!("{}: \"name\" is a required property", villain.display())
+    );
+  }
+}
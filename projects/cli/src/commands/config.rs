@@ -0,0 +1,138 @@
+use std::{fmt::Write, path::PathBuf};
+
+use clap::{Parser, Subcommand};
+use novelsaga_core::{
+  config::schema,
+  state::{ConfigManager, ExplainedConfig, init::Initializer},
+};
+
+/// Subcommands for `config` operations
+#[derive(Subcommand, Clone, Debug)]
+pub enum ConfigCommands {
+  /// Show the effective configuration for a file
+  Show(ConfigShowCommand),
+  /// Print the JSON Schema of config files
+  Schema(ConfigSchemaCommand),
+}
+
+/// Show the effective configuration for a file
+#[derive(Parser, Clone, Debug)]
+pub struct ConfigShowCommand {
+  /// Chapter, metadata or config file whose effective configuration to show
+  #[arg()]
+  pub path: PathBuf,
+
+  /// Show which file and line set each setting
+  #[arg(long)]
+  pub explain: bool,
+}
+
+/// Print the JSON Schema of config files
+#[derive(Parser, Clone, Debug)]
+pub struct ConfigSchemaCommand {
+  /// Print the schema of the settings that markdown frontmatter can override instead
+  #[arg(long)]
+  pub overridable: bool,
+}
+
+/// Handle config commands
+pub fn handle_config_command(command: &ConfigCommands) -> anyhow::Result<()> {
+  match command {
+    ConfigCommands::Show(cmd) => handle_show(cmd),
+    ConfigCommands::Schema(cmd) => handle_schema(cmd),
+  }
+}
+
+fn handle_schema(cmd: &ConfigSchemaCommand) -> anyhow::Result<()> {
+  let schema = if cmd.overridable {
+    schema::overridable_config_schema()
+  } else {
+    schema::novelsaga_config_schema()
+  };
+  println!("{}", serde_json::to_string_pretty(&schema)?);
+  Ok(())
+}
+
+fn handle_show(cmd: &ConfigShowCommand) -> anyhow::Result<()> {
+  let path = cmd.path.canonicalize()?;
+  let start_dir = path.parent().unwrap_or(&path);
+  let state = Initializer::get()?;
+  let config_manager = ConfigManager::new(state.feature().clone(), start_dir)?;
+  let explained = config_manager.explain_override_config(&path)?;
+
+  if cmd.explain {
+    print!("{}", render_explanation(&explained));
+  } else {
+    println!("{}", serde_json::to_string_pretty(&explained.config)?);
+  }
+  Ok(())
+}
+
+/// One `key = value  # source` line per setting, then the merged files.
+fn render_explanation(explained: &ExplainedConfig) -> String {
+  let assignments: Vec<String> = explained
+    .entries
+    .iter()
+    .map(|entry| format!("{} = {}", entry.key, entry.value))
+    .collect();
+  let width = assignments.iter().map(String::len).max().unwrap_or(0);
+
+  let mut out = String::new();
+  for (assignment, entry) in assignments.iter().zip(&explained.entries) {
+    let _ = writeln!(out, "{assignment:<width$}  # {}", entry.source);
+  }
+  out.push_str("\nMerged from (lowest precedence first):\n");
+  for file in &explained.files {
+    let _ = writeln!(out, "  {}", file.display());
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use novelsaga_core::{
+    config::OverridableConfig,
+    state::{ConfigEntry, ConfigSource, ExplainedConfig},
+  };
+  use serde_json::json;
+
+  use super::render_explanation;
+
+  #[test]
+  fn explanation_lists_value_and_source_per_key() {
+    let explained = ExplainedConfig {
+      config: OverridableConfig::default(),
+      entries: vec![
+        ConfigEntry {
+          key: "fmt.blank_lines_between_paragraphs".to_string(),
+          value: json!(2),
+          source: ConfigSource::File {
+            path: PathBuf::from("/novel/novelsaga.config.yaml"),
+            line: Some(3),
+          },
+        },
+        ConfigEntry {
+          key: "fmt.indent_spaces".to_string(),
+          value: json!(4),
+          source: ConfigSource::Default,
+        },
+      ],
+      files: vec![
+        PathBuf::from("/novel/novelsaga.config.yaml"),
+        PathBuf::from("/novel/chapter-01.md"),
+      ],
+    };
+
+    assert_eq!(
+      render_explanation(&explained),
+      "fmt.blank_lines_between_paragraphs = 2  # /novel/novelsaga.config.yaml:3\n\
+       fmt.indent_spaces = 4                   # default\n\
+       \n\
+       Merged from (lowest precedence first):\n  \
+       /novel/novelsaga.config.yaml\n  \
+       /novel/chapter-01.md\n"
+    );
+  }
+}
@@ -1,20 +1,23 @@
 use std::{
   io::Write,
+  ops::ControlFlow,
   path::{Path, PathBuf},
+  sync::Arc,
 };
 
 use clap::{Parser, Subcommand};
-use novelsaga_core::metadata::{
-  MetadataEntity,
-  parser::{generate_namespace, resolve_type},
+use novelsaga_core::{
+  metadata::MetadataEntity,
+  state::{ConfigManager, init::Initializer},
 };
 use serde_json::json;
-use walkdir::WalkDir;
 
 use crate::{
   bridge::{rpc::client::RpcClient, transport::unix_socket::UnixSocketTransport},
+  lsp::find_live_control_socket,
   metadata::{
     index::IndexManager,
+    pipeline::{self, IndexOptions},
     resolver::{MetadataResolver, ResolutionContext},
   },
 };
@@ -57,12 +60,26 @@ pub struct ShowCommand {
 
 /// Handle metadata commands
 pub async fn handle_metadata_command(command: MetadataCommands) -> anyhow::Result<()> {
-  // Check if LSP is running
-  if is_lsp_running() {
+  // Paths are sent absolute: the LSP resolves them against its own cwd
+  let target = match &command {
+    MetadataCommands::Index(cmd) => absolute_path(&cmd.path)?,
+    MetadataCommands::List(_) => std::env::current_dir()?,
+    MetadataCommands::Show(cmd) => absolute_path(&cmd.path)?,
+  };
+
+  // Route through a running LSP serving the target's workspace
+  if let Some(socket_path) = find_live_control_socket(&target) {
     match command {
-      MetadataCommands::Index(cmd) => route_to_lsp("novelsaga/index", json!({"path": cmd.path})).await,
-      MetadataCommands::List(cmd) => route_to_lsp("novelsaga/list", json!({"detailed": cmd.detailed})).await,
-      MetadataCommands::Show(cmd) => route_to_lsp("novelsaga/show", json!({"path": cmd.path})).await,
+      MetadataCommands::Index(_) => route_to_lsp(socket_path, "novelsaga/index", json!({"path": target})).await,
+      MetadataCommands::List(cmd) => {
+        route_to_lsp(
+          socket_path,
+          "novelsaga/list",
+          json!({"detailed": cmd.detailed, "path": target}),
+        )
+        .await
+      }
+      MetadataCommands::Show(_) => route_to_lsp(socket_path, "novelsaga/show", json!({"path": target})).await,
     }
   } else {
     // LSP not running, execute directly
@@ -73,20 +90,30 @@ pub async fn handle_metadata_command(command: MetadataCommands) -> anyhow::Resul
     }
   }
 }
-/// Check if LSP server is running by checking socket file existence
-fn is_lsp_running() -> bool {
-  let socket_path = std::path::PathBuf::from("/tmp/novelsaga-lsp.sock");
-  // Check if socket file exists and try to connect
-  if !socket_path.exists() {
-    return false;
+
+/// The config found from `dir` upwards; a broken config is reported and the
+/// command continues with default workspace settings.
+fn workspace_config_manager(dir: &Path) -> Option<ConfigManager> {
+  let state = Initializer::get().ok()?;
+  match ConfigManager::new(state.feature().clone(), dir) {
+    Ok(config_manager) => Some(config_manager),
+    Err(error) => {
+      eprintln!("⚠️  {error}; using default workspace settings");
+      None
+    }
   }
-  // Verify we can actually connect
-  UnixSocketTransport::connect(&socket_path).is_ok()
 }
 
-/// Route command to LSP server via JSON-RPC
-async fn route_to_lsp(command: &str, params: serde_json::Value) -> anyhow::Result<()> {
-  let socket_path = std::path::PathBuf::from("/tmp/novelsaga-lsp.sock");
+fn absolute_path(path: &Path) -> anyhow::Result<PathBuf> {
+  Ok(
+    path
+      .canonicalize()
+      .or_else(|_| std::env::current_dir().map(|cwd| cwd.join(path)))?,
+  )
+}
+
+/// Route command to the LSP control socket via JSON-RPC
+async fn route_to_lsp(socket_path: PathBuf, command: &str, params: serde_json::Value) -> anyhow::Result<()> {
   let command = command.to_string();
 
   let result = tokio::task::spawn_blocking(move || {
@@ -119,6 +146,9 @@ async fn handle_index(cmd: IndexCommand) -> anyhow::Result<()> {
     anyhow::bail!("Path is not a directory: {}", path.display());
   }
 
+  // Honor the workspace ignore files, include/exclude globs, metadata layout and cache dir of the indexed directory
+  let config_manager = workspace_config_manager(path);
+
   // Determine sled database path using MetadataResolver
   let context = ResolutionContext {
     workspace_root: None,
@@ -126,115 +156,86 @@ async fn handle_index(cmd: IndexCommand) -> anyhow::Result<()> {
     cli_cwd: None,
     show_target_parent: None,
     lsp_startup_dir: None,
+    cache_dir: pipeline::cache_dir(config_manager.as_ref()),
+    config_root: pipeline::config_root(config_manager.as_ref()),
   };
-  let db_path = MetadataResolver::resolve(&context)?;
+  let db_path = MetadataResolver::resolve_for_write(&context)?;
   println!("📦 Opening index database at: {}", db_path.display());
 
   // Open IndexManager
-  let index_manager = IndexManager::open(&db_path)?;
-
-  // Collect all .md files
-  let md_files: Vec<_> = WalkDir::new(path)
-    .into_iter()
-    .filter_map(std::result::Result::ok)
-    .filter(|e| e.file_type().is_file() && e.path().extension().is_some_and(|ext| ext.eq_ignore_ascii_case("md")))
-    .map(|e| e.path().to_path_buf())
-    .collect();
-
-  let total_files = md_files.len();
-  println!("🔍 Found {total_files} markdown files to index");
+  let index_manager = Arc::new(IndexManager::open(&db_path)?);
+
+  let discovery = pipeline::workspace_discovery(path, config_manager.as_ref());
+  let layout = pipeline::metadata_layout(config_manager.as_ref());
+
+  // Index incrementally with progress tracking; per-file failures are reported by the pipeline
+  let mut total_files = 0;
+  let outcome = pipeline::index_directory(
+    &index_manager,
+    &discovery,
+    &layout,
+    path,
+    &IndexOptions::default(),
+    |progress| {
+      total_files = progress.total;
+      print!(
+        "\r📄 [{}/{}] Checked: {}",
+        progress.done,
+        progress.total,
+        progress.file.display()
+      );
+      let _ = std::io::stdout().flush();
+      ControlFlow::Continue(())
+    },
+  )
+  .map_err(|e| anyhow::anyhow!(e))?;
 
   if total_files == 0 {
-    println!("⚠️  No markdown files found in directory");
-    return Ok(());
+    println!("⚠️  No metadata files found in directory");
+  } else {
+    println!(); // New line after progress
+  }
+  for error in &outcome.errors {
+    eprintln!("⚠️  {error}");
   }
 
-  // Process files with progress tracking
-  let mut processed = 0;
-  let mut success = 0;
-  let mut failed = 0;
-
-  for file_path in md_files {
-    processed += 1;
-    print!(
-      "\r📄 [{}/{}] Processing: {}",
-      processed,
-      total_files,
-      file_path.display()
-    );
-    std::io::Write::flush(&mut std::io::stdout())?;
-
-    match process_file(&file_path, path, &index_manager) {
-      Ok(()) => success += 1,
-      Err(e) => {
-        eprintln!("\n⚠️  Failed to index {}: {}", file_path.display(), e);
-        failed += 1;
-      }
+  let duplicates = index_manager.duplicate_ids();
+  for (id, paths) in &duplicates {
+    eprintln!("⚠️  Duplicate entity id '{id}' defined by:");
+    for path in paths {
+      eprintln!("     {}", path.display());
     }
   }
 
-  println!(); // New line after progress
-
-  // Flush database
-  print!("💾 Flushing database... ");
-  std::io::Write::flush(&mut std::io::stdout())?;
-  index_manager.flush()?;
-  println!("✓");
-  // Explicitly close database to release locks
-  index_manager.close()?;
+  // Explicitly close database to release locks; the write-back worker is done with it
+  Arc::into_inner(index_manager)
+    .ok_or_else(|| anyhow::anyhow!("Metadata index is still in use"))?
+    .close()?;
   // Print summary
   println!("\n📊 Indexing Complete:");
   println!("   Total files: {total_files}");
-  println!("   Successful: {success}");
-  println!("   Failed: {failed}");
+  println!("   Added: {}", outcome.added);
+  println!("   Changed: {}", outcome.changed);
+  println!("   Unchanged: {}", outcome.unchanged);
+  println!("   Removed: {}", outcome.removed);
+  println!("   Failed: {}", outcome.failed);
+  println!("   Duplicate ids: {}", duplicates.len());
   println!("   Database: {}", db_path.display());
 
   Ok(())
 }
 
-/// Process a single markdown file
-/// Process a single markdown file
-fn process_file(file_path: &Path, workspace_root: &Path, index_manager: &IndexManager) -> anyhow::Result<()> {
-  // Read file content
-  let content = std::fs::read_to_string(file_path)?;
-
-  // Parse frontmatter with simple YAML frontmatter delimiter handling
-  let (frontmatter, body) = if let Some(rest) = content.strip_prefix("---") {
-    if let Some(end) = rest.find("---") {
-      let body_str = &rest[end + 3..].trim_start();
-      (serde_json::json!({}), body_str.to_string())
-    } else {
-      (serde_json::json!({}), content)
-    }
-  } else {
-    (serde_json::json!({}), content)
-  };
-
-  // Generate ID from file path
-  let id = IndexManager::generate_id(&file_path.to_string_lossy());
-
-  // Determine type (frontmatter takes priority, then path inference)
-  let type_ = resolve_type(file_path, &frontmatter);
-
-  // Generate namespace from file location
-  let namespace = generate_namespace(file_path, workspace_root);
-
-  // Create metadata entity
-  let entity = MetadataEntity::new(&id, &type_, &namespace, frontmatter, &body);
-
-  // Index the entity
-  index_manager.index_entity(&entity)?;
-
-  Ok(())
-}
-
 #[allow(clippy::unused_async)]
 async fn handle_list(cmd: ListCommand) -> anyhow::Result<()> {
   // Determine database path using MetadataResolver
+  let cwd = std::env::current_dir()?;
+  let config_manager = workspace_config_manager(&cwd);
   let context = ResolutionContext {
     workspace_root: None,
     cli_target_path: None,
-    cli_cwd: Some(std::env::current_dir()?),
+    cache_dir: pipeline::cache_dir(config_manager.as_ref()),
+    config_root: pipeline::config_root(config_manager.as_ref()),
+    cli_cwd: Some(cwd),
     show_target_parent: None,
     lsp_startup_dir: None,
   };
@@ -413,24 +414,22 @@ fn truncate(s: &str, max_width: usize) -> String {
 async fn handle_show(cmd: ShowCommand) -> anyhow::Result<()> {
   use anyhow::Context;
 
-  // Get canonical path for consistent ID generation
+  // Entities are looked up by canonical path
   let canonical_path = cmd
     .path
     .canonicalize()
     .with_context(|| format!("File not found: {}", cmd.path.display()))?;
-  let path_str = canonical_path.to_string_lossy();
-
-  // Generate entity ID from path using blake3 hash
-  let entity_id = IndexManager::generate_id(&path_str);
-
   // Determine index database path using MetadataResolver
   let show_target_parent = canonical_path.parent().map(std::path::Path::to_path_buf);
+  let config_manager = show_target_parent.as_deref().and_then(workspace_config_manager);
   let context = ResolutionContext {
     workspace_root: None,
     cli_target_path: None,
     cli_cwd: None,
     show_target_parent,
     lsp_startup_dir: None,
+    cache_dir: pipeline::cache_dir(config_manager.as_ref()),
+    config_root: pipeline::config_root(config_manager.as_ref()),
   };
   let cache_dir = MetadataResolver::resolve(&context)?;
 
@@ -438,14 +437,16 @@ async fn handle_show(cmd: ShowCommand) -> anyhow::Result<()> {
   let index_manager =
     IndexManager::open(&cache_dir).map_err(|e| anyhow::anyhow!("Failed to open index database: {e}"))?;
 
-  // Query entity by ID
-  if let Some(entity) = index_manager.get_by_id(&entity_id)? {
+  // Query entity by path
+  if let Some(entity) = index_manager.get_by_path(&canonical_path)? {
     // Display entity as pretty JSON
     let json_output = serde_json::to_string_pretty(&entity).context("Failed to serialize metadata entity")?;
     println!("{json_output}");
   } else {
     eprintln!("No metadata found for: {}", cmd.path.display());
-    eprintln!("  Entity ID: {entity_id}");
+    if let Some(entity_id) = index_manager.get_id_by_path(&canonical_path) {
+      eprintln!("  Entity ID: {entity_id} (defined by another file as well)");
+    }
     eprintln!("  Run 'novelsaga index' to index this directory first.");
     std::process::exit(1);
   }
@@ -1,4 +1,6 @@
 /// Command handlers for `NovelSaga` CLI
+pub mod check;
+pub mod config;
 pub mod init;
 pub mod metadata;
 
@@ -1,11 +1,12 @@
 use std::{
+  cell::RefCell,
   collections::HashMap,
-  error::Error,
   path::{Path, PathBuf},
-  sync::Arc,
+  sync::{Arc, PoisonError, RwLock},
 };
 
 use anyhow::Result;
+use novelsaga_core::{config::error::ConfigError, state::feat::Feature};
 use serde_json::json;
 
 use crate::{
@@ -14,8 +15,42 @@ use crate::{
 };
 
 /// Type alias for the config loader closure returned to Core
-pub type ConfigLoaderFn =
-  Arc<dyn Fn(&str) -> Result<HashMap<String, serde_json::Value>, Box<dyn Error + Send + Sync>> + Send + Sync>;
+pub type ConfigLoaderFn = Arc<dyn Fn(&str) -> Result<HashMap<String, serde_json::Value>, ConfigError> + Send + Sync>;
+
+/// 编辑器设置中选择的 JS 运行时，优先于命令行的 `--runtime` 与 `--*-path`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeOverride {
+  pub choice: RuntimeChoice,
+  /// 该运行时的可执行文件路径，未设置时沿用命令行参数
+  pub path: Option<PathBuf>,
+}
+
+/// 一个 LSP 连接根据客户端设置选择的运行时覆盖，由该连接打开的所有 `ConfigManager` 共享
+pub type SharedRuntimeOverride = Arc<RwLock<Option<RuntimeOverride>>>;
+
+thread_local! {
+  /// 当前线程上正在求值的配置所用的运行时覆盖（见 [`with_runtime_override`]）
+  static RUNTIME_OVERRIDE: RefCell<Option<RuntimeOverride>> = const { RefCell::new(None) };
+}
+
+/// 使 `feature` 的 JS/TS 加载器在求值时优先使用 `runtime` 中当前的运行时覆盖
+#[must_use]
+pub fn with_runtime_override(feature: Feature, runtime: &SharedRuntimeOverride) -> Feature {
+  feature.map_loaders(|loader| {
+    let runtime = Arc::clone(runtime);
+    Arc::new(move |config_path| {
+      let current = runtime.read().unwrap_or_else(PoisonError::into_inner).clone();
+      let previous = RUNTIME_OVERRIDE.replace(current);
+      let result = loader(config_path);
+      RUNTIME_OVERRIDE.set(previous);
+      result
+    })
+  })
+}
+
+fn runtime_override() -> Option<RuntimeOverride> {
+  RUNTIME_OVERRIDE.with_borrow(Clone::clone)
+}
 
 /// Internal context for loading script configs (reduces function parameter count)
 struct LoaderContext<'a> {
@@ -73,7 +108,7 @@ impl ConfigLoader {
         bun_path: bun_path.as_ref(),
         deno_path: deno_path.as_ref(),
       };
-      Self::load_script_config_impl(&ctx, config_path, false).map_err(std::convert::Into::into)
+      Self::load_script_config_impl(&ctx, config_path, false).map_err(ConfigError::script)
     })
   }
 
@@ -95,7 +130,7 @@ impl ConfigLoader {
         bun_path: bun_path.as_ref(),
         deno_path: deno_path.as_ref(),
       };
-      Self::load_script_config_impl(&ctx, config_path, true).map_err(std::convert::Into::into)
+      Self::load_script_config_impl(&ctx, config_path, true).map_err(ConfigError::script)
     })
   }
 
@@ -107,13 +142,18 @@ impl ConfigLoader {
   ) -> Result<HashMap<String, serde_json::Value>> {
     let bridge_script = Self::get_bridge_script_path()?;
 
-    // 根据用户选择确定运行时类型和路径
-    let (preferred_runtime, user_path) = match ctx.runtime_choice {
+    // 根据用户选择确定运行时类型和路径，编辑器设置优先于命令行
+    let runtime_override = runtime_override();
+    let runtime_choice = runtime_override
+      .as_ref()
+      .map_or(ctx.runtime_choice, |runtime| runtime.choice);
+    let (preferred_runtime, user_path) = match runtime_choice {
       RuntimeChoice::Auto => (None, None),
       RuntimeChoice::Node => (Some(RuntimeType::NodeJs), ctx.node_path.cloned()),
       RuntimeChoice::Bun => (Some(RuntimeType::Bun), ctx.bun_path.cloned()),
       RuntimeChoice::Deno => (Some(RuntimeType::Deno), ctx.deno_path.cloned()),
     };
+    let user_path = runtime_override.and_then(|runtime| runtime.path).or(user_path);
 
     let runtime_info = ctx
       .runtime_discovery
@@ -152,8 +192,13 @@ impl ConfigLoader {
     let bridge_script = bridge_script.clone();
     let env_clone = env.clone();
 
-    // Register the "config" bridge with unique name per config path
-    let bridge_name = format!("config_{}", config_path_abs.to_string_lossy().replace(['/', '\\'], "_"));
+    // Register the "config" bridge with unique name per runtime and config path
+    let bridge_name = format!(
+      "config_{}_{}",
+      runtime_info.path.to_string_lossy(),
+      config_path_abs.to_string_lossy()
+    )
+    .replace(['/', '\\'], "_");
 
     ctx.manager.register(&bridge_name, move || {
       let bridge = ConfigBridge::new(runtime_info.clone(), &bridge_script, env_clone.clone())?;
@@ -227,4 +272,35 @@ mod tests {
 
     Ok(())
   }
+
+  #[test]
+  fn runtime_override_follows_the_handle_of_each_feature() {
+    // 记录求值时生效的运行时覆盖
+    let probe: ConfigLoaderFn = Arc::new(|_| {
+      let choice = runtime_override().map(|runtime| format!("{:?}", runtime.choice));
+      Ok(HashMap::from([("runtime".to_string(), json!(choice))]))
+    });
+    let node = SharedRuntimeOverride::default();
+    let bun = SharedRuntimeOverride::default();
+    let node_feature = with_runtime_override(Feature::new(Some(Arc::clone(&probe)), None), &node);
+    let bun_feature = with_runtime_override(Feature::new(Some(probe), None), &bun);
+    let evaluate =
+      |feature: &Feature| feature.js_loader().expect("js loader")("config.mjs").expect("evaluated")["runtime"].clone();
+
+    *node.write().unwrap() = Some(RuntimeOverride {
+      choice: RuntimeChoice::Node,
+      path: None,
+    });
+    *bun.write().unwrap() = Some(RuntimeOverride {
+      choice: RuntimeChoice::Bun,
+      path: None,
+    });
+    assert_eq!(evaluate(&node_feature), json!("Node"));
+    assert_eq!(evaluate(&bun_feature), json!("Bun"));
+
+    *bun.write().unwrap() = None;
+    assert_eq!(evaluate(&node_feature), json!("Node"));
+    assert_eq!(evaluate(&bun_feature), json!(null));
+    assert_eq!(runtime_override(), None);
+  }
 }
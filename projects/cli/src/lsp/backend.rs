@@ -1,19 +1,24 @@
 use std::{
-  collections::HashMap,
+  collections::{HashMap, HashSet},
+  ops::{ControlFlow, Deref},
   path::{Path, PathBuf},
-  sync::Arc,
+  sync::{Arc, PoisonError, Weak},
+  time::Duration,
 };
 
 use novelsaga_core::{
-  article::{Article, ArticleDocument},
-  config::OverridableConfig,
-  document::{DocumentKind, MarkdownParts, ParseSeverity, WorkspaceDocument},
-  library,
-  metadata::{
-    MetadataEntity,
-    parser::{generate_namespace, resolve_type},
+  article::Article,
+  config::{
+    extends::{config_layers, extended_files},
+    file_def,
+    metadata::MetadataConfig,
+    schema::config_key_doc,
   },
-  state::init::Initializer,
+  discovery::FileDiscovery,
+  document::{DocumentError, DocumentKind, MarkdownParts, WorkspaceDocument},
+  library,
+  metadata::{EntitySchemas, MetadataError, is_schema_file},
+  state::{ConfigManager, feat::Feature, init::Initializer},
 };
 use tokio::sync::RwLock;
 use tower_lsp::{
@@ -21,59 +26,151 @@ use tower_lsp::{
   jsonrpc::Result as LspResult,
   lsp_types::{
     CompletionContext, CompletionOptions, CompletionParams, CompletionResponse, CompletionTriggerKind,
-    DeleteFilesParams, Diagnostic, DiagnosticSeverity, DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
-    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentFormattingParams, ExecuteCommandParams,
+    ConfigurationItem, DeleteFilesParams, Diagnostic, DiagnosticOptions, DiagnosticServerCapabilities,
+    DidChangeConfigurationParams, DidChangeTextDocumentParams, DidChangeWatchedFilesParams,
+    DidChangeWorkspaceFoldersParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentDiagnosticParams,
+    DocumentDiagnosticReport, DocumentDiagnosticReportResult, DocumentFormattingParams, ExecuteCommandParams,
     FileChangeType, FileEvent, FileOperationFilter, FileOperationPattern, FileOperationPatternKind,
-    FileOperationRegistrationOptions, FileSystemWatcher, Hover, HoverContents, HoverParams, HoverProviderCapability,
-    InitializeParams, InitializeResult, InitializedParams, MarkupContent, MarkupKind, MessageType, OneOf, Position,
-    Range, Registration, RenameFilesParams, ServerCapabilities, ServerInfo, TextDocumentSyncCapability,
-    TextDocumentSyncKind, TextEdit, Url, WatchKind, WorkDoneProgressOptions, WorkspaceFileOperationsServerCapabilities,
-    WorkspaceServerCapabilities,
+    FileOperationRegistrationOptions, FileSystemWatcher, FullDocumentDiagnosticReport, Hover, HoverContents,
+    HoverParams, HoverProviderCapability, InitializeParams, InitializeResult, InitializedParams, MarkupContent,
+    MarkupKind, MessageType, NumberOrString, OneOf, Position, ProgressToken, Range, Registration,
+    RelatedFullDocumentDiagnosticReport, RelatedUnchangedDocumentDiagnosticReport, RenameFilesParams,
+    ServerCapabilities, ServerInfo, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit,
+    UnchangedDocumentDiagnosticReport, Unregistration, Url, WatchKind, WorkDoneProgressCancelParams,
+    WorkDoneProgressCreateParams, WorkDoneProgressOptions, WorkspaceDiagnosticParams, WorkspaceDiagnosticReport,
+    WorkspaceDiagnosticReportResult, WorkspaceDocumentDiagnosticReport, WorkspaceFileOperationsServerCapabilities,
+    WorkspaceFoldersServerCapabilities, WorkspaceFullDocumentDiagnosticReport, WorkspaceServerCapabilities,
+    WorkspaceUnchangedDocumentDiagnosticReport, request::WorkDoneProgressCreate,
   },
 };
 use uuid::Uuid;
-use walkdir::WalkDir;
 
 use crate::{
-  lsp::{build_completion_candidates, extract_active_prefix, offset_to_position, position_to_offset},
+  config::loader::{SharedRuntimeOverride, with_runtime_override},
+  lsp::{
+    DocumentState, build_completion_candidates, config_completion, config_hover,
+    control::ControlSocket,
+    diagnostics::{
+      DiagnosticsCache, DiagnosticsReport, LintRule, content_result_id, discover_workspace_documents,
+      duplicate_id_diagnostic, entity_schema_error_diagnostic, is_diagnosable_path, lint_report,
+      schema_violation_diagnostics,
+    },
+    extract_active_prefix, offset_to_position,
+    progress::{CancelFlag, WorkDoneProgressReporter},
+    settings::{ClientSettings, SETTINGS_SECTION, section_of},
+    workspace::{WorkspaceFolderState, WorkspaceFolders},
+  },
   metadata::{
     IndexManager,
-    resolver::{MetadataResolver, ResolutionContext},
+    pipeline::{self, IndexOptions, IndexOutcome},
   },
 };
 
 type DocumentStore = Arc<RwLock<HashMap<Url, DocumentState>>>;
-type SharedIndexManager = Arc<RwLock<Option<Arc<IndexManager>>>>;
+type SharedWorkspaceFolders = Arc<RwLock<WorkspaceFolders>>;
+type SharedDiagnosticsCache = Arc<RwLock<DiagnosticsCache>>;
+type ProgressCancellations = Arc<RwLock<HashMap<ProgressToken, CancelFlag>>>;
+type WatchedFilesRegistration = Arc<RwLock<Option<(String, Vec<String>)>>>;
 
-const WATCHED_METADATA_GLOB: &str = "**/metadata/**/*.md";
 const WATCHED_CONFIG_GLOBS: [&str; 2] = ["**/novelsaga.config.*", "**/.novelsaga.*"];
+/// Quiet period after the last `didChange` before a document is reparsed
+const REPARSE_DEBOUNCE: Duration = Duration::from_millis(150);
+const DIAGNOSTIC_IDENTIFIER: &str = "novelsaga";
+const INDEX_PROGRESS_TITLE: &str = "Indexing metadata";
+
+/// The language server; clones share one [`BackendState`].
+#[derive(Debug, Clone)]
+pub struct Backend(Arc<BackendState>);
 
+/// A [`Backend`] reference that does not keep the server alive, for tasks
+/// that outlive a client connection (e.g. the control socket).
 #[derive(Debug, Clone)]
-pub struct DocumentState {
-  pub version: i32,
-  pub text: Arc<str>,
-  pub kind: DocumentKind,
-  pub parsed: Result<WorkspaceDocument, String>,
-  pub disk_changed: bool,
+pub struct WeakBackend(Weak<BackendState>);
+
+impl WeakBackend {
+  pub fn upgrade(&self) -> Option<Backend> {
+    self.0.upgrade().map(Backend)
+  }
+}
+
+impl Deref for Backend {
+  type Target = BackendState;
+
+  fn deref(&self) -> &BackendState {
+    &self.0
+  }
 }
 
 #[derive(Debug)]
-pub struct Backend {
+pub struct BackendState {
   client: Client,
-  workspace_root: Arc<RwLock<Option<PathBuf>>>,
+  workspace_folders: SharedWorkspaceFolders,
+  /// Control sockets for CLI commands, one per workspace folder root
+  control_sockets: Arc<RwLock<HashMap<PathBuf, ControlSocket>>>,
   document_store: DocumentStore,
-  index_manager: SharedIndexManager,
+  diagnostics_cache: SharedDiagnosticsCache,
   watched_files_dynamic_registration: Arc<RwLock<bool>>,
+  /// Id and globs of the current `workspace/didChangeWatchedFiles` registration
+  watched_files: WatchedFilesRegistration,
+  /// Client pulls diagnostics (LSP 3.17), so they are not pushed as well
+  pull_diagnostics: Arc<RwLock<bool>>,
+  client_settings: Arc<RwLock<ClientSettings>>,
+  /// JS runtime chosen in this client's settings, used by the config loaders
+  /// of this connection only
+  runtime_override: SharedRuntimeOverride,
+  /// Client answers `workspace/configuration` requests
+  workspace_configuration: Arc<RwLock<bool>>,
+  did_change_configuration_dynamic_registration: Arc<RwLock<bool>>,
+  /// Client accepts server-initiated `window/workDoneProgress/create`
+  work_done_progress: Arc<RwLock<bool>>,
+  /// Cancellation flags of running work-done progress, by token
+  progress_cancellations: ProgressCancellations,
 }
 
 impl Backend {
   pub fn new(client: Client) -> Self {
-    Self {
+    Self(Arc::new(BackendState {
       client,
-      workspace_root: Arc::new(RwLock::new(None)),
+      workspace_folders: Arc::new(RwLock::new(WorkspaceFolders::default())),
+      control_sockets: Arc::new(RwLock::new(HashMap::new())),
       document_store: Arc::new(RwLock::new(HashMap::new())),
-      index_manager: Arc::new(RwLock::new(None)),
+      diagnostics_cache: Arc::new(RwLock::new(DiagnosticsCache::default())),
       watched_files_dynamic_registration: Arc::new(RwLock::new(false)),
+      watched_files: Arc::new(RwLock::new(None)),
+      pull_diagnostics: Arc::new(RwLock::new(false)),
+      client_settings: Arc::new(RwLock::new(ClientSettings::default())),
+      runtime_override: SharedRuntimeOverride::default(),
+      workspace_configuration: Arc::new(RwLock::new(false)),
+      did_change_configuration_dynamic_registration: Arc::new(RwLock::new(false)),
+      work_done_progress: Arc::new(RwLock::new(false)),
+      progress_cancellations: Arc::new(RwLock::new(HashMap::new())),
+    }))
+  }
+
+  /// Core features with this connection's runtime override, for evaluating
+  /// script configs outside a [`ConfigManager`]; `None` before initialization.
+  fn feature(&self) -> Option<Feature> {
+    let state = Initializer::get().ok()?;
+    Some(with_runtime_override(state.feature().clone(), &self.runtime_override))
+  }
+
+  pub fn downgrade(&self) -> WeakBackend {
+    WeakBackend(Arc::downgrade(&self.0))
+  }
+
+  /// Release what the server holds for its workspaces after the client
+  /// connection closed: control sockets, open indexes (and their sled locks),
+  /// documents and cached diagnostics.
+  ///
+  /// Clients may disconnect without `shutdown`, and socket transports keep the
+  /// process running for the next connection.
+  pub async fn close(&self) {
+    self.control_sockets.write().await.clear();
+    *self.workspace_folders.write().await = WorkspaceFolders::default();
+    self.document_store.write().await.clear();
+    self.diagnostics_cache.write().await.clear();
+    for flag in self.progress_cancellations.write().await.drain().map(|(_, flag)| flag) {
+      flag.cancel();
     }
   }
 
@@ -88,24 +185,50 @@ impl Backend {
       .map_err(|()| format!("Failed to convert URI to file path: {uri}"))
   }
 
-  fn classify_document(uri: &Url) -> DocumentKind {
+  fn classify_document(uri: &Url, layout: &MetadataConfig) -> DocumentKind {
     Self::document_path_from_url(uri).map_or_else(
-      |_| DocumentKind::classify_path(Path::new(uri.path())),
-      |path| DocumentKind::classify_path(&path),
+      |_| DocumentKind::classify_path(Path::new(uri.path()), layout),
+      |path| DocumentKind::classify_path(&path, layout),
     )
   }
 
-  fn workspace_root_from_params(params: &InitializeParams) -> Option<PathBuf> {
+  /// Classify `uri` with the metadata layout of its workspace folder.
+  async fn classify_uri(&self, uri: &Url) -> DocumentKind {
+    let layout = self
+      .folder_for_uri(uri)
+      .await
+      .map(|folder| folder.metadata_layout())
+      .unwrap_or_default();
+    Self::classify_document(uri, &layout)
+  }
+
+  /// Workspace folders from `initialize`: `workspaceFolders` when present, otherwise `rootUri`.
+  fn workspace_folders_from_params(params: &InitializeParams) -> Vec<(String, PathBuf)> {
+    if let Some(folders) = params.workspace_folders.as_ref().filter(|folders| !folders.is_empty()) {
+      return folders
+        .iter()
+        .filter_map(|folder| {
+          Self::document_path_from_url(&folder.uri)
+            .ok()
+            .map(|path| (folder.name.clone(), path))
+        })
+        .collect();
+    }
+
     params
       .root_uri
       .as_ref()
-      .or_else(|| {
-        params
-          .workspace_folders
-          .as_ref()
-          .and_then(|folders| folders.first().map(|folder| &folder.uri))
-      })
       .and_then(|uri| Self::document_path_from_url(uri).ok())
+      .map(|path| (Self::folder_name(&path), path))
+      .into_iter()
+      .collect()
+  }
+
+  fn folder_name(root: &Path) -> String {
+    root.file_name().map_or_else(
+      || root.to_string_lossy().into_owned(),
+      |name| name.to_string_lossy().into_owned(),
+    )
   }
 
   fn workspace_root_from_file_uri(uri: &Url) -> Option<PathBuf> {
@@ -124,35 +247,169 @@ impl Backend {
       .unwrap_or(false)
   }
 
-  fn open_index_manager(workspace_root: Option<&Path>, lsp_startup_dir: Option<&Path>) -> Option<Arc<IndexManager>> {
-    let context = ResolutionContext {
-      workspace_root: workspace_root.map(std::path::Path::to_path_buf),
-      cli_target_path: None,
-      cli_cwd: None,
-      show_target_parent: None,
-      lsp_startup_dir: lsp_startup_dir.map(std::path::Path::to_path_buf),
+  fn workspace_configuration_support(params: &InitializeParams) -> bool {
+    params
+      .capabilities
+      .workspace
+      .as_ref()
+      .and_then(|workspace| workspace.configuration)
+      .unwrap_or(false)
+  }
+
+  fn window_work_done_progress_support(params: &InitializeParams) -> bool {
+    params
+      .capabilities
+      .window
+      .as_ref()
+      .and_then(|window| window.work_done_progress)
+      .unwrap_or(false)
+  }
+
+  fn workspace_did_change_configuration_dynamic_registration(params: &InitializeParams) -> bool {
+    params
+      .capabilities
+      .workspace
+      .as_ref()
+      .and_then(|workspace| workspace.did_change_configuration.as_ref())
+      .and_then(|capabilities| capabilities.dynamic_registration)
+      .unwrap_or(false)
+  }
+
+  fn text_document_pull_diagnostics(params: &InitializeParams) -> bool {
+    params
+      .capabilities
+      .text_document
+      .as_ref()
+      .is_some_and(|text_document| text_document.diagnostic.is_some())
+  }
+
+  /// Metadata and schema file globs of every workspace folder's layout (the
+  /// default `metadata/` layout without folders), followed by the config file
+  /// globs and the files config files pull in through `extends`.
+  async fn watched_globs(&self) -> Vec<String> {
+    let (mut globs, discoveries, extended) = {
+      let folders = self.workspace_folders.read().await;
+      let layouts: Vec<MetadataConfig> = if folders.is_empty() {
+        vec![MetadataConfig::default()]
+      } else {
+        folders.all().map(|folder| folder.metadata_layout()).collect()
+      };
+      let globs: Vec<String> = layouts
+        .iter()
+        .flat_map(|layout| [layout.watch_globs(), layout.schema_watch_globs()].concat())
+        .collect();
+      let discoveries: Vec<FileDiscovery> = folders.all().map(|folder| folder.file_discovery()).collect();
+      // The root config may live above the folder and is not walked
+      let extended: Vec<PathBuf> = folders
+        .all()
+        .filter_map(|folder| folder.config_manager.as_ref())
+        .flat_map(ConfigManager::extended_config_files)
+        .collect();
+      (globs, discoveries, extended)
     };
+    globs.sort();
+    globs.dedup();
+    globs.extend(WATCHED_CONFIG_GLOBS.map(str::to_string));
+
+    // Extended files may sit outside the workspace under any name
+    let mut extended = tokio::task::spawn_blocking(move || {
+      let mut extended = extended;
+      for discovery in &discoveries {
+        for config_file in discovery.config_files() {
+          if let Ok(layers) = config_layers(&config_file) {
+            extended.extend(extended_files(&layers));
+          }
+        }
+      }
+      extended
+    })
+    .await
+    .unwrap_or_default();
+    extended.sort();
+    extended.dedup();
+    globs.extend(extended.iter().map(|path| path.to_string_lossy().into_owned()));
+    globs
+  }
 
-    let index_path = MetadataResolver::resolve(&context).ok()?;
-    match IndexManager::open(&index_path) {
-      Ok(manager) => Some(Arc::new(manager)),
+  /// Register the watched file globs with the client, replacing the previous
+  /// registration when they changed (e.g. a config file extends another file).
+  async fn refresh_watched_files_registration(&self) {
+    if !*self.watched_files_dynamic_registration.read().await {
+      return;
+    }
+    let globs = self.watched_globs().await;
+    let mut watched_files = self.watched_files.write().await;
+    if watched_files
+      .as_ref()
+      .is_some_and(|(_, registered)| *registered == globs)
+    {
+      return;
+    }
+
+    let registration = match Self::watched_files_registration(&globs) {
+      Ok(registration) => registration,
       Err(error) => {
-        eprintln!("Failed to open metadata index at {}: {error}", index_path.display());
-        None
+        self
+          .client
+          .log_message(
+            MessageType::WARNING,
+            format!("Failed to serialize watched files registration: {error}"),
+          )
+          .await;
+        return;
+      }
+    };
+    if let Some((id, _)) = watched_files.take()
+      && let Err(error) = self
+        .client
+        .unregister_capability(vec![Unregistration {
+          id,
+          method: registration.method.clone(),
+        }])
+        .await
+    {
+      self
+        .client
+        .log_message(
+          MessageType::WARNING,
+          format!("Failed to unregister watched files: {error}"),
+        )
+        .await;
+    }
+    let id = registration.id.clone();
+    match self.client.register_capability(vec![registration]).await {
+      Ok(()) => *watched_files = Some((id, globs)),
+      Err(error) => {
+        self
+          .client
+          .log_message(
+            MessageType::WARNING,
+            format!("Failed to register watched files: {error}"),
+          )
+          .await;
       }
     }
   }
 
-  fn watched_globs() -> impl Iterator<Item = &'static str> {
-    std::iter::once(WATCHED_METADATA_GLOB).chain(WATCHED_CONFIG_GLOBS)
+  /// Whether a workspace folder's loaded config pulls in `path` through `extends`.
+  async fn is_extended_config_file(&self, path: &Path) -> bool {
+    let path = Self::normalize_path(path);
+    self
+      .workspace_folders
+      .read()
+      .await
+      .all()
+      .filter_map(|folder| folder.config_manager.as_ref())
+      .any(|config_manager| config_manager.extended_config_files().contains(&path))
   }
 
-  fn watched_file_filters() -> Vec<FileOperationFilter> {
-    Self::watched_globs()
+  fn watched_file_filters(globs: &[String]) -> Vec<FileOperationFilter> {
+    globs
+      .iter()
       .map(|glob| FileOperationFilter {
         scheme: Some("file".to_string()),
         pattern: FileOperationPattern {
-          glob: glob.to_string(),
+          glob: glob.clone(),
           matches: Some(FileOperationPatternKind::File),
           options: None,
         },
@@ -160,9 +417,9 @@ impl Backend {
       .collect()
   }
 
-  fn watched_file_operations_capabilities() -> WorkspaceFileOperationsServerCapabilities {
+  fn watched_file_operations_capabilities(globs: &[String]) -> WorkspaceFileOperationsServerCapabilities {
     let options = FileOperationRegistrationOptions {
-      filters: Self::watched_file_filters(),
+      filters: Self::watched_file_filters(globs),
     };
 
     WorkspaceFileOperationsServerCapabilities {
@@ -175,11 +432,12 @@ impl Backend {
     }
   }
 
-  fn watched_files_registration() -> Result<Registration, serde_json::Error> {
+  fn watched_files_registration(globs: &[String]) -> Result<Registration, serde_json::Error> {
     let register_options = serde_json::to_value(tower_lsp::lsp_types::DidChangeWatchedFilesRegistrationOptions {
-      watchers: Self::watched_globs()
+      watchers: globs
+        .iter()
         .map(|glob| FileSystemWatcher {
-          glob_pattern: glob.to_string().into(),
+          glob_pattern: glob.clone().into(),
           kind: Some(WatchKind::Create | WatchKind::Change | WatchKind::Delete),
         })
         .collect(),
@@ -192,27 +450,351 @@ impl Backend {
     })
   }
 
-  fn is_metadata_document(path: &Path) -> bool {
-    DocumentKind::classify_path(path) == DocumentKind::Metadata
+  fn did_change_configuration_registration() -> Registration {
+    Registration {
+      id: format!("novelsaga-did-change-configuration-{}", Uuid::new_v4()),
+      method: "workspace/didChangeConfiguration".to_string(),
+      register_options: None,
+    }
   }
 
-  fn attach_entity_path(entity: &mut MetadataEntity, path: &Path) {
-    let normalized_path = Self::normalize_path(path);
+  /// Switch the JS runtime to the client's choice and report invalid runtime
+  /// or lint settings; lint severities are read when diagnostics are built.
+  async fn apply_client_settings(&self) {
+    let settings = self.client_settings.read().await.clone();
+    let mut problems = Vec::new();
+    match settings.runtime() {
+      Ok(runtime) => *self.runtime_override.write().unwrap_or_else(PoisonError::into_inner) = runtime,
+      Err(error) => problems.push(error),
+    }
+    if let Err(error) = settings.lint_severities() {
+      problems.push(error);
+    }
+    for problem in problems {
+      self
+        .client
+        .log_message(
+          MessageType::WARNING,
+          format!("Ignoring invalid `{SETTINGS_SECTION}` client setting: {problem}"),
+        )
+        .await;
+    }
+  }
 
-    if let Some(frontmatter) = entity.frontmatter.as_object_mut() {
-      frontmatter.insert(
-        "canonical_path".to_string(),
-        serde_json::Value::String(normalized_path.to_string_lossy().into_owned()),
-      );
-      frontmatter.insert(
-        "source_path".to_string(),
-        serde_json::Value::String(path.to_string_lossy().into_owned()),
-      );
+  /// Fetch the `novelsaga` section via `workspace/configuration`.
+  async fn pull_client_settings(&self) {
+    let items = vec![ConfigurationItem {
+      scope_uri: None,
+      section: Some(SETTINGS_SECTION.to_string()),
+    }];
+
+    match self.client.configuration(items).await {
+      Ok(mut values) => {
+        let section = (!values.is_empty()).then(|| values.swap_remove(0));
+        self.client_settings.write().await.set_configuration(section);
+        self.apply_client_settings().await;
+        self.refresh_open_document_diagnostics().await;
+      }
+      Err(error) => {
+        self
+          .client
+          .log_message(
+            MessageType::WARNING,
+            format!("Failed to fetch `{SETTINGS_SECTION}` settings: {error}"),
+          )
+          .await;
+      }
+    }
+  }
+
+  /// Whether `path` is a metadata document under the layout of its workspace folder.
+  async fn is_metadata_document(&self, path: &Path) -> bool {
+    let layout = self
+      .folder_for_path(path)
+      .await
+      .map(|folder| folder.metadata_layout())
+      .unwrap_or_default();
+    DocumentKind::classify_path(path, &layout) == DocumentKind::Metadata
+  }
+
+  /// Whether `path` is a frontmatter schema file under the layout of its workspace folder.
+  async fn is_schema_file(&self, path: &Path) -> bool {
+    let layout = self
+      .folder_for_path(path)
+      .await
+      .map(|folder| folder.metadata_layout())
+      .unwrap_or_default();
+    is_schema_file(path, &layout)
+  }
+
+  /// Recompile the schemas of the folder owning a changed schema file and
+  /// revalidate metadata documents against them.
+  async fn handle_schema_change(&self, path: &Path) {
+    eprintln!("Frontmatter schema changed: {}", path.display());
+    if let Some(folder) = self.folder_for_path(path).await {
+      folder.invalidate_entity_schemas();
     }
+    self.refresh_open_document_diagnostics().await;
   }
 
-  async fn index_manager(&self) -> Option<Arc<IndexManager>> {
-    self.index_manager.read().await.clone()
+  fn is_config_file(path: &Path) -> bool {
+    file_def::is_config_file(path)
+  }
+
+  /// Whether the document at `uri` is left out of its workspace folder by the
+  /// ignore files or include/exclude globs. Config files are never ignored.
+  async fn is_ignored_uri(&self, uri: &Url) -> bool {
+    let Ok(path) = Self::document_path_from_url(uri) else {
+      return false;
+    };
+    if Self::is_config_file(&path) {
+      return false;
+    }
+    let Some(folder) = self.folder_for_path(&path).await else {
+      return false;
+    };
+    tokio::task::spawn_blocking(move || folder.file_discovery().is_ignored(&path))
+      .await
+      .unwrap_or(false)
+  }
+
+  /// Effective value and source of the config key `key`, preceded in config
+  /// files by the setting's documentation.
+  ///
+  /// The document's own settings come from its open `text`; config files are
+  /// loaded off the async runtime.
+  async fn config_key_hover(&self, uri: &Url, path: PathBuf, key: String, text: String) -> Option<Hover> {
+    let docs = Self::is_config_file(&path)
+      .then(|| config_key_doc(&key).map(config_hover::key_doc_markdown))
+      .flatten();
+
+    let config_manager = self
+      .folder_for_uri(uri)
+      .await
+      .and_then(|folder| folder.config_manager.clone());
+    let runtime = Arc::clone(&self.runtime_override);
+    let effective = tokio::task::spawn_blocking(move || {
+      let config_manager = match config_manager {
+        Some(manager) => manager,
+        None => WorkspaceFolderState::open_config_manager(path.parent().unwrap_or(&path), &runtime).ok()?,
+      };
+      let explained = config_manager.explain_document_config(&path, &text).ok()?;
+      config_hover::config_key_hover(&explained, &key)
+    })
+    .await
+    .ok()
+    .flatten();
+
+    let value = match (docs, effective) {
+      (Some(docs), Some(effective)) => format!("{docs}\n\n---\n\n{effective}"),
+      (Some(value), None) | (None, Some(value)) => value,
+      (None, None) => return None,
+    };
+    Some(Hover {
+      contents: HoverContents::Markup(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value,
+      }),
+      range: None,
+    })
+  }
+
+  /// Workspace folder owning `path`; `None` outside every folder.
+  async fn folder_for_path(&self, path: &Path) -> Option<Arc<WorkspaceFolderState>> {
+    self.workspace_folders.read().await.owner_of(path)
+  }
+
+  /// Workspace folder owning the file at `uri`; `None` for other URIs.
+  async fn folder_for_uri(&self, uri: &Url) -> Option<Arc<WorkspaceFolderState>> {
+    let path = Self::document_path_from_url(uri).ok()?;
+    self.folder_for_path(&path).await
+  }
+
+  /// Folder owning `path`, or outside the workspace a folder opened for `dir`
+  /// with its own config and index, as the CLI would use.
+  async fn folder_or_standalone(&self, path: &Path, dir: PathBuf) -> Option<Arc<WorkspaceFolderState>> {
+    if let Some(folder) = self.folder_for_path(path).await {
+      return Some(folder);
+    }
+    let name = dir
+      .file_name()
+      .map(|name| name.to_string_lossy().into_owned())
+      .unwrap_or_default();
+    let runtime = Arc::clone(&self.runtime_override);
+    tokio::task::spawn_blocking(move || WorkspaceFolderState::open(name, dir, &runtime))
+      .await
+      .ok()
+      .map(Arc::new)
+  }
+
+  async fn add_workspace_folder(&self, name: String, root: PathBuf) {
+    // Opening the index touches disk (and may wait for its lock); keep it off the runtime and outside the lock
+    let runtime = Arc::clone(&self.runtime_override);
+    let Ok(folder) = tokio::task::spawn_blocking(move || WorkspaceFolderState::open(name, root, &runtime)).await else {
+      return;
+    };
+    eprintln!("Workspace folder added: {} ({})", folder.name, folder.root.display());
+    let root = folder.root.clone();
+    self.workspace_folders.write().await.insert(folder);
+
+    match ControlSocket::bind(&root, self.downgrade()) {
+      Ok(socket) => {
+        eprintln!("Control socket listening at {}", socket.path().display());
+        self.control_sockets.write().await.insert(root, socket);
+      }
+      Err(error) => {
+        self
+          .client
+          .log_message(
+            MessageType::WARNING,
+            format!("Control socket unavailable for {}: {error}", root.display()),
+          )
+          .await;
+      }
+    }
+  }
+
+  fn index_manager_of(
+    folder: Option<&Arc<WorkspaceFolderState>>,
+    to_internal_error: impl Fn(String) -> tower_lsp::jsonrpc::Error,
+  ) -> LspResult<Arc<IndexManager>> {
+    folder
+      .and_then(|folder| folder.index_manager.clone())
+      .ok_or_else(|| to_internal_error("Metadata index manager is not initialized".to_string()))
+  }
+
+  /// Handle `window/workDoneProgress/cancel` (not part of `LanguageServer`).
+  pub async fn work_done_progress_cancel(&self, params: WorkDoneProgressCancelParams) {
+    if let Some(cancel) = self.progress_cancellations.read().await.get(&params.token) {
+      eprintln!("Work done progress cancelled: {:?}", params.token);
+      cancel.cancel();
+    }
+  }
+
+  /// Token for reporting progress of a request: the client-provided one, else a
+  /// server-created one when the client supports `window/workDoneProgress/create`.
+  async fn work_done_token(&self, client_token: Option<ProgressToken>, prefix: &str) -> Option<ProgressToken> {
+    if client_token.is_some() {
+      return client_token;
+    }
+    if !*self.work_done_progress.read().await {
+      return None;
+    }
+
+    let token = NumberOrString::String(format!("{prefix}-{}", Uuid::new_v4()));
+    match self
+      .client
+      .send_request::<WorkDoneProgressCreate>(WorkDoneProgressCreateParams { token: token.clone() })
+      .await
+    {
+      Ok(()) => Some(token),
+      Err(error) => {
+        eprintln!("Failed to create work done progress: {error}");
+        None
+      }
+    }
+  }
+
+  /// Run `novelsaga/index` off the request handler.
+  ///
+  /// Indexing runs on a blocking thread inside a spawned task, so other requests
+  /// keep being served and the work still finishes cleanly (flush, progress end)
+  /// when the handler is dropped by `$/cancelRequest`.
+  async fn run_index(
+    &self,
+    scan_root: PathBuf,
+    discovery: FileDiscovery,
+    layout: MetadataConfig,
+    index_manager: Arc<IndexManager>,
+    token: Option<ProgressToken>,
+  ) -> LspResult<IndexOutcome> {
+    let cancel = CancelFlag::default();
+    let _cancel_on_drop = cancel.cancel_on_drop();
+    if let Some(token) = &token {
+      self
+        .progress_cancellations
+        .write()
+        .await
+        .insert(token.clone(), cancel.clone());
+    }
+
+    let client = self.client.clone();
+    let cancellations = self.progress_cancellations.clone();
+    let task = tokio::spawn(async move {
+      let mut reporter = WorkDoneProgressReporter::new(client.clone(), token.clone());
+      reporter
+        .begin(INDEX_PROGRESS_TITLE, Some(format!("Scanning {}", scan_root.display())))
+        .await;
+
+      let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+      let worker_cancel = cancel.clone();
+      let worker = tokio::task::spawn_blocking(move || {
+        pipeline::index_directory(
+          &index_manager,
+          &discovery,
+          &layout,
+          &scan_root,
+          &IndexOptions::default(),
+          |progress| {
+            let _ = progress_tx.send(progress.clone());
+            if worker_cancel.is_cancelled() {
+              ControlFlow::Break(())
+            } else {
+              ControlFlow::Continue(())
+            }
+          },
+        )
+      });
+
+      while let Some(progress) = progress_rx.recv().await {
+        reporter
+          .report(progress.done, progress.total, progress.file.display().to_string())
+          .await;
+      }
+
+      let result = worker
+        .await
+        .map_err(|error| format!("Metadata indexing task failed: {error}"))
+        .and_then(|result| result);
+      if let Ok(outcome) = &result {
+        for error in &outcome.errors {
+          client.log_message(MessageType::WARNING, error).await;
+        }
+      }
+      let end_message = match &result {
+        Ok(outcome) if outcome.cancelled => format!("Cancelled after {} files", outcome.indexed()),
+        Ok(outcome) => format!(
+          "{} added, {} changed, {} unchanged, {} removed, {} failed",
+          outcome.added, outcome.changed, outcome.unchanged, outcome.removed, outcome.failed
+        ),
+        Err(error) => error.clone(),
+      };
+      reporter.end(end_message).await;
+
+      if let Some(token) = &token {
+        cancellations.write().await.remove(token);
+      }
+      result
+    });
+
+    let outcome = task
+      .await
+      .map_err(|error| format!("Metadata indexing task failed: {error}"))
+      .and_then(|result| result)
+      .map_err(|message| tower_lsp::jsonrpc::Error {
+        code: tower_lsp::jsonrpc::ErrorCode::InternalError,
+        message: message.into(),
+        data: None,
+      })?;
+
+    if outcome.cancelled {
+      return Err(tower_lsp::jsonrpc::Error {
+        code: tower_lsp::jsonrpc::ErrorCode::RequestCancelled,
+        message: format!("Metadata indexing cancelled after {} files", outcome.indexed()).into(),
+        data: None,
+      });
+    }
+    Ok(outcome)
   }
 
   fn should_return_empty_completion(context: Option<&CompletionContext>) -> bool {
@@ -256,65 +838,84 @@ impl Backend {
   async fn upsert_metadata_from_disk(&self, path: PathBuf) {
     let _ = self.mark_document_disk_changed(&path).await;
 
-    let Some(index_manager) = self.index_manager().await else {
-      return;
-    };
-    let workspace_root = { self.workspace_root.read().await.clone() };
-    let Some(workspace_root) = workspace_root else {
+    let Some(folder) = self.folder_for_path(&path).await else {
       eprintln!("Skipping metadata refresh without workspace root: {}", path.display());
       return;
     };
-
-    let content = match std::fs::read_to_string(&path) {
-      Ok(content) => content,
-      Err(error) => {
-        eprintln!("Failed to read metadata file {}: {error}", path.display());
-        return;
-      }
+    let Some(index_manager) = folder.index_manager.clone() else {
+      return;
     };
 
-    let report = MarkdownParts::parse_with_issues(&content);
-    let mut entity = match MetadataEntity::try_from((report.parts, path.as_path(), workspace_root.as_path())) {
-      Ok(entity) => entity,
-      Err(error) => {
-        eprintln!("Failed to parse metadata file {}: {error}", path.display());
-        return;
+    // Reading the file and writing the index block; `None` for an indexed file that is now ignored
+    let indexed_path = path.clone();
+    let indexed = tokio::task::spawn_blocking(move || {
+      if folder.file_discovery().is_ignored(&indexed_path) {
+        return index_manager
+          .get_id_by_path(&indexed_path)
+          .is_none()
+          .then_some(Ok(false));
       }
-    };
-
-    Self::attach_entity_path(&mut entity, &path);
-    if let Err(error) = index_manager.index_entity(&entity) {
-      eprintln!("Failed to update metadata index for {}: {error}", path.display());
+      let was_duplicated = Self::path_has_duplicate_id(&index_manager, &indexed_path);
+      Some(
+        pipeline::index_file(&index_manager, &indexed_path, &folder.root, &folder.metadata_layout()).map(|entity| {
+          was_duplicated || !pipeline::duplicate_definitions(&index_manager, &entity.id, &indexed_path).is_empty()
+        }),
+      )
+    })
+    .await;
+
+    match indexed {
+      // Newly ignored files leave the index like deleted ones
+      Ok(None) => self.remove_metadata_by_path(&path).await,
+      Ok(Some(Ok(true))) => self.refresh_open_document_diagnostics().await,
+      Ok(Some(Ok(false))) => {}
+      Ok(Some(Err(error))) => eprintln!("Failed to update metadata index: {error}"),
+      Err(error) => eprintln!("Failed to update metadata index: {error}"),
     }
   }
 
+  /// Whether the file at `path` is indexed under an id other files define too.
+  fn path_has_duplicate_id(index_manager: &IndexManager, path: &Path) -> bool {
+    index_manager
+      .get_id_by_path(path)
+      .is_some_and(|id| index_manager.claimants(&id).is_ok_and(|paths| paths.len() > 1))
+  }
+
   async fn remove_metadata_by_path(&self, path: &Path) {
     let _ = self.mark_document_disk_changed(path).await;
 
-    let Some(index_manager) = self.index_manager().await else {
+    let Some(folder) = self.folder_for_path(path).await else {
       return;
     };
-
-    let Some(entity_id) = index_manager.get_id_by_path(path) else {
+    let Some(index_manager) = folder.index_manager.clone() else {
       return;
     };
 
-    if let Err(error) = index_manager.remove_entity(&entity_id) {
-      eprintln!(
-        "Failed to remove metadata entity {entity_id} for {}: {error}",
-        path.display()
-      );
+    // Removing writes the index, which blocks
+    let removed_path = path.to_path_buf();
+    let removed = tokio::task::spawn_blocking(move || {
+      let was_duplicated = Self::path_has_duplicate_id(&index_manager, &removed_path);
+      pipeline::remove_file(&index_manager, &removed_path, &folder.root, &folder.metadata_layout())
+        .map(|()| was_duplicated)
+    })
+    .await;
+
+    match removed {
+      Ok(Ok(true)) => self.refresh_open_document_diagnostics().await,
+      Ok(Ok(false)) => {}
+      Ok(Err(error)) => eprintln!("Failed to remove metadata entity: {error}"),
+      Err(error) => eprintln!("Failed to remove metadata entity: {error}"),
     }
   }
 
   async fn handle_watched_path_create_or_change(&self, path: PathBuf) {
-    if Self::is_metadata_document(&path) {
+    if self.is_metadata_document(&path).await {
       self.upsert_metadata_from_disk(path).await;
     }
   }
 
   async fn handle_watched_path_delete(&self, path: PathBuf) {
-    if Self::is_metadata_document(&path) {
+    if self.is_metadata_document(&path).await {
       self.remove_metadata_by_path(&path).await;
     }
   }
@@ -327,7 +928,172 @@ impl Backend {
     self.upsert_metadata_from_disk(new_path).await;
   }
 
+  /// Make a created, changed or deleted config file take effect: drop cached
+  /// override configs below its directory, reload root configs and refresh
+  /// diagnostics. Parse failures are shown to the user; the previous root
+  /// config stays active until the file is fixed.
+  async fn handle_config_change(&self, path: &Path) {
+    eprintln!("Config file changed: {}", path.display());
+    let dir = path.parent().unwrap_or(path);
+
+    let (config_managers, unconfigured, layouts): (
+      Vec<ConfigManager>,
+      Vec<Arc<WorkspaceFolderState>>,
+      HashMap<PathBuf, MetadataConfig>,
+    ) = {
+      let folders = self.workspace_folders.read().await;
+      (
+        folders
+          .all()
+          .filter_map(|folder| folder.config_manager.clone())
+          .collect(),
+        folders
+          .all()
+          .filter(|folder| folder.config_manager.is_none())
+          .cloned()
+          .collect(),
+        folders
+          .all()
+          .map(|folder| (folder.root.clone(), folder.metadata_layout()))
+          .collect(),
+      )
+    };
+
+    for config_manager in &config_managers {
+      config_manager.invalidate_override_config_cache_under(dir);
+    }
+    // Schemas may be declared in the config
+    for folder in self.workspace_folders.read().await.all() {
+      folder.invalidate_entity_schemas();
+    }
+
+    // Reading and evaluating (possibly JS/TS) config files blocks
+    let changed = path.to_path_buf();
+    let runtime = Arc::clone(&self.runtime_override);
+    let (error, reconfigured) = tokio::task::spawn_blocking(move || {
+      let mut error = config_managers
+        .first()
+        .filter(|_| changed.exists())
+        .and_then(|config_manager| config_manager.validate_config_file(&changed).err())
+        .map(|error| error.to_string());
+      for config_manager in &config_managers {
+        let reload_error = config_manager
+          .invalidate_extended_config_file(&changed)
+          .err()
+          .or_else(|| config_manager.reload_root_config().err());
+        if let Some(reload_error) = reload_error {
+          error.get_or_insert_with(|| reload_error.to_string());
+        }
+      }
+      // Folders whose root config was broken when opened pick it up once it parses
+      let mut reconfigured = Vec::new();
+      for folder in unconfigured {
+        match WorkspaceFolderState::open_config_manager(&folder.root, &runtime) {
+          Ok(config_manager) => reconfigured.push(folder.with_config_manager(config_manager)),
+          Err(open_error) => {
+            error.get_or_insert(open_error);
+          }
+        }
+      }
+      (error, reconfigured)
+    })
+    .await
+    .unwrap_or_else(|join_error| (Some(join_error.to_string()), Vec::new()));
+    for folder in reconfigured {
+      self.workspace_folders.write().await.insert(folder);
+    }
+
+    if let Some(error) = error {
+      self
+        .client
+        .show_message(
+          MessageType::ERROR,
+          format!("Failed to load NovelSaga config {}: {error}", path.display()),
+        )
+        .await;
+    }
+
+    // Documents may classify (and derive ids) differently under a new layout
+    let relaid: Vec<Arc<WorkspaceFolderState>> = self
+      .workspace_folders
+      .read()
+      .await
+      .all()
+      .filter(|folder| layouts.get(&folder.root) != Some(&folder.metadata_layout()))
+      .cloned()
+      .collect();
+    for folder in relaid {
+      self.schedule_reindex(folder);
+    }
+
+    self.refresh_open_document_diagnostics().await;
+    // The layout's metadata directories and `extends` may have changed
+    self.refresh_watched_files_registration().await;
+  }
+
+  /// Reindex a folder in the background, e.g. after its metadata layout changed.
+  fn schedule_reindex(&self, folder: Arc<WorkspaceFolderState>) {
+    let Some(index_manager) = folder.index_manager.clone() else {
+      return;
+    };
+    let backend = self.clone();
+    tokio::spawn(async move {
+      eprintln!("Metadata layout changed, reindexing {}", folder.root.display());
+      let token = backend.work_done_token(None, "novelsaga-index").await;
+      let result = backend
+        .run_index(
+          folder.root.clone(),
+          folder.file_discovery(),
+          folder.metadata_layout(),
+          index_manager,
+          token,
+        )
+        .await;
+      if let Err(error) = result {
+        backend
+          .client
+          .log_message(
+            MessageType::WARNING,
+            format!("Failed to reindex {}: {}", folder.root.display(), error.message),
+          )
+          .await;
+      }
+    });
+  }
+
+  async fn refresh_open_document_diagnostics(&self) {
+    self.diagnostics_cache.write().await.clear();
+
+    if *self.pull_diagnostics.read().await {
+      if let Err(error) = self.client.workspace_diagnostic_refresh().await {
+        self
+          .client
+          .log_message(
+            MessageType::WARNING,
+            format!("Failed to request diagnostic refresh: {error}"),
+          )
+          .await;
+      }
+      return;
+    }
+
+    let open_documents: Vec<(Url, i32, String)> = self
+      .document_store
+      .read()
+      .await
+      .iter()
+      .map(|(uri, state)| (uri.clone(), state.version, state.text()))
+      .collect();
+    for (uri, version, text) in open_documents {
+      self.publish_document_diagnostics(&uri, version, &text).await;
+    }
+  }
+
   async fn handle_file_change_event(&self, change: FileEvent) {
+    if change.typ == FileChangeType::DELETED {
+      self.diagnostics_cache.write().await.remove(&change.uri);
+    }
+
     let path = match Self::document_path_from_url(&change.uri) {
       Ok(path) => path,
       Err(error) => {
@@ -342,7 +1108,17 @@ impl Backend {
       }
     };
 
-    if !Self::is_metadata_document(&path) {
+    if Self::is_config_file(&path) || self.is_extended_config_file(&path).await {
+      self.handle_config_change(&path).await;
+      return;
+    }
+
+    if self.is_schema_file(&path).await {
+      self.handle_schema_change(&path).await;
+      return;
+    }
+
+    if !self.is_metadata_document(&path).await {
       return;
     }
 
@@ -363,98 +1139,183 @@ impl Backend {
     uri: &Url,
     text: &Arc<str>,
     workspace_root: Option<&Path>,
-  ) -> (DocumentKind, Result<WorkspaceDocument, String>) {
-    let kind = Self::classify_document(uri);
+    layout: &MetadataConfig,
+  ) -> (DocumentKind, Result<WorkspaceDocument, DocumentError>) {
+    let kind = Self::classify_document(uri, layout);
 
-    let parsed = match Self::document_path_from_url(uri) {
-      Ok(path) => {
+    let parsed = Self::document_path_from_url(uri)
+      .map_err(|_| DocumentError::NotAFile { uri: uri.to_string() })
+      .and_then(|path| {
         let report = MarkdownParts::parse_with_issues(text.as_ref());
-        match kind {
-          DocumentKind::Metadata => match workspace_root {
-            Some(root) => {
-              MetadataEntity::try_from((report.parts, path.as_path(), root)).map(WorkspaceDocument::Metadata)
-            }
-            None => Err(format!("Workspace root is required to parse metadata document: {uri}")),
-          },
-          DocumentKind::Article => Ok(WorkspaceDocument::Article(ArticleDocument::from_parts(report.parts))),
-        }
-      }
-      Err(err) => Err(err),
-    };
+        WorkspaceDocument::from_parts(kind, report.parts, path.as_path(), workspace_root, layout)
+      });
 
     (kind, parsed)
   }
 
-  async fn refresh_document_parse(&self, uri: &Url) {
-    let workspace_root = { self.workspace_root.read().await.clone() };
-    let Some((version, text)) = ({
-      let document_store = self.document_store.read().await;
-      document_store
-        .get(uri)
-        .map(|state| (state.version, Arc::clone(&state.text)))
-    }) else {
-      return;
+  /// Reparse a document if it is still at `version`, republishing diagnostics
+  /// only when the edits since the last parse may have touched the frontmatter.
+  ///
+  /// Body-only edits just refresh the body of the parsed document.
+  async fn reparse_document(&self, uri: &Url, version: i32) {
+    let text = {
+      let mut document_store = self.document_store.write().await;
+      let Some(state) = document_store.get_mut(uri).filter(|state| state.version == version) else {
+        return;
+      };
+      if state.reparse_body() {
+        return;
+      }
+      Arc::<str>::from(state.text())
     };
 
-    let (kind, parsed) = Self::parse_document(uri, &text, workspace_root.as_deref());
+    let folder = self.folder_for_uri(uri).await;
+    let workspace_root = folder.as_ref().map(|folder| folder.root.clone());
+    let layout = folder.map(|folder| folder.metadata_layout()).unwrap_or_default();
 
-    let mut document_store = self.document_store.write().await;
-    let Some(state) = document_store.get_mut(uri) else {
-      return;
-    };
+    let (kind, parsed) = Self::parse_document(uri, &text, workspace_root.as_deref(), &layout);
 
-    if state.version == version && Arc::ptr_eq(&state.text, &text) {
+    {
+      let mut document_store = self.document_store.write().await;
+      let Some(state) = document_store.get_mut(uri) else {
+        return;
+      };
+      if state.version != version {
+        return;
+      }
       state.kind = kind;
-      state.parsed = parsed;
+      state.mark_parsed(parsed);
     }
+
+    self.publish_document_diagnostics(uri, version, text.as_ref()).await;
+  }
+
+  /// Reparse after `REPARSE_DEBOUNCE`; superseded versions are skipped.
+  fn schedule_reparse(&self, uri: Url, version: i32) {
+    let backend = self.clone();
+    tokio::spawn(async move {
+      tokio::time::sleep(REPARSE_DEBOUNCE).await;
+      backend.reparse_document(&uri, version).await;
+    });
   }
 
   async fn publish_document_diagnostics(&self, uri: &Url, version: i32, text: &str) {
-    let report = MarkdownParts::parse_with_issues(text);
-    let diagnostics = report
-      .issues
-      .into_iter()
-      .map(|issue| Diagnostic {
-        range: Self::diagnostic_range(text, issue.line),
-        severity: Some(match issue.severity {
-          ParseSeverity::Error => DiagnosticSeverity::ERROR,
-          ParseSeverity::Warning => DiagnosticSeverity::WARNING,
-        }),
-        message: issue.message,
-        ..Diagnostic::default()
-      })
-      .collect();
+    let report = self.document_diagnostics(uri, text).await;
+    if *self.pull_diagnostics.read().await {
+      return;
+    }
+
+    self
+      .client
+      .publish_diagnostics(uri.clone(), report.items, Some(version))
+      .await;
+  }
+
+  /// Current text and version of a document: the open buffer if any, otherwise the file on disk.
+  async fn diagnostics_source(&self, uri: &Url) -> Option<(String, Option<i32>)> {
+    if let Some(state) = self.document_store.read().await.get(uri) {
+      return Some((state.text(), Some(state.version)));
+    }
+
+    let path = Self::document_path_from_url(uri).ok()?;
+    tokio::task::spawn_blocking(move || std::fs::read_to_string(path))
+      .await
+      .ok()?
+      .ok()
+      .map(|text| (text, None))
+  }
+
+  async fn diagnostics_report(&self, uri: &Url) -> Option<(DiagnosticsReport, Option<i32>)> {
+    let (text, version) = self.diagnostics_source(uri).await?;
+    let report = self.document_diagnostics(uri, &text).await;
+    Some((report, version))
+  }
+
+  /// Lint diagnostics plus, for metadata documents, duplicate entity ids and
+  /// frontmatter that does not match the schema of the entity's type.
+  ///
+  /// Both depend on other files, so they are not part of the content cache;
+  /// they are folded into the result id instead. Documents the workspace
+  /// ignores get none.
+  async fn document_diagnostics(&self, uri: &Url, text: &str) -> DiagnosticsReport {
+    if self.is_ignored_uri(uri).await {
+      return DiagnosticsReport {
+        result_id: content_result_id(text),
+        items: Vec::new(),
+      };
+    }
+    let cached = self.diagnostics_cache.read().await.get(uri, text);
+    let report = if let Some(report) = cached {
+      report
+    } else {
+      // Config files may be evaluated by the JS/TS loader
+      let (lint_uri, lint_text) = (uri.clone(), text.to_string());
+      let feature = self.feature();
+      let report = tokio::task::spawn_blocking(move || scoped(feature.as_ref(), || lint_report(&lint_uri, &lint_text)))
+        .await
+        .unwrap_or_else(|_| DiagnosticsReport {
+          result_id: content_result_id(text),
+          items: Vec::new(),
+        });
+      self.diagnostics_cache.write().await.insert(uri.clone(), report.clone());
+      report
+    };
+    self.with_metadata_diagnostics(uri, text, report).await
+  }
+
+  /// Add the metadata diagnostics to a lint report and apply the client's lint severities.
+  async fn with_metadata_diagnostics(&self, uri: &Url, text: &str, mut report: DiagnosticsReport) -> DiagnosticsReport {
+    for diagnostic in self.metadata_diagnostics(uri, text).await {
+      report.result_id = content_result_id(&format!("{}\n{}", report.result_id, diagnostic.message));
+      report.items.push(diagnostic);
+    }
+    let severities = self.client_settings.read().await.lint_severities().unwrap_or_default();
+    severities.apply(report)
+  }
 
-    self
-      .client
-      .publish_diagnostics(uri.clone(), diagnostics, Some(version))
-      .await;
+  /// Frontmatter schemas for the metadata document at `path`, compiled on a
+  /// blocking thread when not cached yet.
+  async fn entity_schemas(
+    folder: &Arc<WorkspaceFolderState>,
+    path: &Path,
+  ) -> Result<Arc<EntitySchemas>, MetadataError> {
+    let folder = Arc::clone(folder);
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || folder.entity_schemas(&path))
+      .await
+      .unwrap_or_else(|_| Ok(Arc::default()))
   }
 
-  fn diagnostic_range(text: &str, line: Option<usize>) -> Range {
-    let Some(line_index) = line.and_then(|line| line.checked_sub(1)) else {
-      return Range {
-        start: Position { line: 0, character: 0 },
-        end: Position { line: 0, character: 0 },
-      };
+  async fn metadata_diagnostics(&self, uri: &Url, text: &str) -> Vec<Diagnostic> {
+    let Ok(path) = Self::document_path_from_url(uri) else {
+      return Vec::new();
     };
-
-    let start = Position {
-      line: u32::try_from(line_index).unwrap_or(0),
-      character: 0,
+    let Some(folder) = self.folder_for_path(&path).await else {
+      return Vec::new();
     };
+    let layout = folder.metadata_layout();
+    if DocumentKind::classify_path(&path, &layout) != DocumentKind::Metadata {
+      return Vec::new();
+    }
 
-    let Some(line_content) = text.lines().nth(line_index) else {
-      return Range { start, end: start };
+    let mut diagnostics = match Self::entity_schemas(&folder, &path).await {
+      Ok(schemas) => schema_violation_diagnostics(
+        text,
+        pipeline::frontmatter_violations(&schemas, &path, text, &layout),
+        LintRule::MetadataSchema,
+      ),
+      Err(error) => vec![entity_schema_error_diagnostic(text, &error)],
     };
 
-    Range {
-      start,
-      end: Position {
-        line: start.line,
-        character: u32::try_from(line_content.encode_utf16().count()).unwrap_or(0),
-      },
+    if let Some(index_manager) = folder.index_manager.as_ref()
+      && let Ok(entity) = pipeline::build_entity(&path, text, &folder.root, &layout)
+    {
+      let others = pipeline::duplicate_definitions(index_manager, &entity.id, &path);
+      if !others.is_empty() {
+        diagnostics.push(duplicate_id_diagnostic(text, &entity.id, &others));
+      }
     }
+    diagnostics
   }
 }
 
@@ -464,26 +1325,37 @@ impl LanguageServer for Backend {
   async fn initialize(&self, params: InitializeParams) -> LspResult<InitializeResult> {
     eprintln!("NovelSaga LSP Server initializing...");
 
-    let workspace_root = Self::workspace_root_from_params(&params);
-    if let Some(root_path) = workspace_root.as_ref() {
-      eprintln!("Workspace root: {}", root_path.display());
-    } else {
+    let folders = Self::workspace_folders_from_params(&params);
+    if folders.is_empty() {
       eprintln!("Workspace root: <none>");
     }
-
-    *self.workspace_root.write().await = workspace_root.clone();
-    let lsp_startup_dir = workspace_root.as_deref();
-    *self.index_manager.write().await =
-      Self::open_index_manager(self.workspace_root.read().await.as_deref(), lsp_startup_dir);
+    for (name, root) in folders {
+      self.add_workspace_folder(name, root).await;
+    }
+    let watched_globs = self.watched_globs().await;
 
     *self.watched_files_dynamic_registration.write().await =
       Self::workspace_watched_files_dynamic_registration(&params);
+    *self.pull_diagnostics.write().await = Self::text_document_pull_diagnostics(&params);
+    *self.workspace_configuration.write().await = Self::workspace_configuration_support(&params);
+    *self.work_done_progress.write().await = Self::window_work_done_progress_support(&params);
+    *self.did_change_configuration_dynamic_registration.write().await =
+      Self::workspace_did_change_configuration_dynamic_registration(&params);
+    *self.client_settings.write().await =
+      ClientSettings::from_initialization_options(params.initialization_options.as_ref());
 
     Ok(InitializeResult {
       capabilities: ServerCapabilities {
-        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::INCREMENTAL)),
         document_formatting_provider: Some(OneOf::Left(true)),
         hover_provider: Some(HoverProviderCapability::Simple(true)),
+        diagnostic_provider: Some(DiagnosticServerCapabilities::Options(DiagnosticOptions {
+          identifier: Some(DIAGNOSTIC_IDENTIFIER.to_string()),
+          // Duplicate ids and entity schemas depend on other documents
+          inter_file_dependencies: true,
+          workspace_diagnostics: true,
+          work_done_progress_options: WorkDoneProgressOptions::default(),
+        })),
         completion_provider: Some(CompletionOptions {
           resolve_provider: Some(false),
           trigger_characters: None,
@@ -497,11 +1369,16 @@ impl LanguageServer for Backend {
             "novelsaga/list".to_string(),
             "novelsaga/show".to_string(),
           ],
-          work_done_progress_options: WorkDoneProgressOptions::default(),
+          work_done_progress_options: WorkDoneProgressOptions {
+            work_done_progress: Some(true),
+          },
         }),
         workspace: Some(WorkspaceServerCapabilities {
-          workspace_folders: None,
-          file_operations: Some(Self::watched_file_operations_capabilities()),
+          workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+            supported: Some(true),
+            change_notifications: Some(OneOf::Left(true)),
+          }),
+          file_operations: Some(Self::watched_file_operations_capabilities(&watched_globs)),
         }),
         ..Default::default()
       },
@@ -515,29 +1392,26 @@ impl LanguageServer for Backend {
   async fn initialized(&self, _: InitializedParams) {
     eprintln!("NovelSaga LSP Server initialized!");
 
-    if *self.watched_files_dynamic_registration.read().await {
-      match Self::watched_files_registration() {
-        Ok(registration) => {
-          if let Err(error) = self.client.register_capability(vec![registration]).await {
-            self
-              .client
-              .log_message(
-                MessageType::WARNING,
-                format!("Failed to register watched files capability: {error}"),
-              )
-              .await;
-          }
-        }
-        Err(error) => {
-          self
-            .client
-            .log_message(
-              MessageType::WARNING,
-              format!("Failed to serialize watched files registration: {error}"),
-            )
-            .await;
-        }
-      }
+    self.refresh_watched_files_registration().await;
+    if *self.did_change_configuration_dynamic_registration.read().await
+      && let Err(error) = self
+        .client
+        .register_capability(vec![Self::did_change_configuration_registration()])
+        .await
+    {
+      self
+        .client
+        .log_message(
+          MessageType::WARNING,
+          format!("Failed to register dynamic capabilities: {error}"),
+        )
+        .await;
+    }
+
+    if *self.workspace_configuration.read().await {
+      self.pull_client_settings().await;
+    } else {
+      self.apply_client_settings().await;
     }
 
     self
@@ -548,50 +1422,41 @@ impl LanguageServer for Backend {
 
   async fn shutdown(&self) -> LspResult<()> {
     eprintln!("NovelSaga LSP Server shutting down...");
+    // The process exits right after `exit`, so remove socket files now
+    self.control_sockets.write().await.clear();
     Ok(())
   }
 
   async fn did_open(&self, params: DidOpenTextDocumentParams) {
     let uri = params.text_document.uri;
     let version = params.text_document.version;
-    let text: Arc<str> = Arc::from(params.text_document.text);
-    let kind = Self::classify_document(&uri);
 
     eprintln!("Document opened: {uri}");
 
-    // Fallback chain: root_uri → workspaceFolders[0] → first file derivation → None
-    let should_initialize_workspace = {
-      let current_root = self.workspace_root.read().await;
-      current_root.is_none()
-    };
+    // Fallback chain: workspaceFolders → root_uri → first file derivation → None
+    let should_initialize_workspace = self.workspace_folders.read().await.is_empty();
 
     if should_initialize_workspace && let Some(derived_root) = Self::workspace_root_from_file_uri(&uri) {
       eprintln!(
         "Workspace root derived from first opened file: {}",
         derived_root.display()
       );
-      *self.workspace_root.write().await = Some(derived_root.clone());
-      // Initialize index_manager with newly derived workspace_root
-      *self.index_manager.write().await =
-        Self::open_index_manager(Some(derived_root.as_path()), Some(derived_root.as_path()));
+      self
+        .add_workspace_folder(Self::folder_name(&derived_root), derived_root)
+        .await;
+      self.refresh_watched_files_registration().await;
     }
 
+    let kind = self.classify_uri(&uri).await;
     {
       let mut document_store = self.document_store.write().await;
       document_store.insert(
         uri.clone(),
-        DocumentState {
-          version,
-          text: Arc::clone(&text),
-          kind,
-          parsed: Err("Document parsing in progress".to_string()),
-          disk_changed: false,
-        },
+        DocumentState::new(version, &params.text_document.text, kind),
       );
     }
 
-    self.refresh_document_parse(&uri).await;
-    self.publish_document_diagnostics(&uri, version, text.as_ref()).await;
+    self.reparse_document(&uri, version).await;
 
     self
       .client
@@ -601,37 +1466,47 @@ impl LanguageServer for Backend {
 
   async fn did_change(&self, params: DidChangeTextDocumentParams) {
     let uri = params.text_document.uri;
+    let version = params.text_document.version;
 
     eprintln!("Document changed: {uri}");
 
-    if let Some(change) = params.content_changes.into_iter().next() {
-      let version = params.text_document.version;
-      let text: Arc<str> = Arc::from(change.text);
-      let kind = Self::classify_document(&uri);
-
-      {
-        let mut document_store = self.document_store.write().await;
-        document_store
-          .entry(uri.clone())
-          .and_modify(|state| {
-            state.version = version;
-            state.text = Arc::clone(&text);
-            state.kind = kind;
-            state.parsed = Err("Document parsing in progress".to_string());
-            state.disk_changed = false;
-          })
-          .or_insert_with(|| DocumentState {
-            version,
-            text: Arc::clone(&text),
-            kind,
-            parsed: Err("Document parsing in progress".to_string()),
-            disk_changed: false,
-          });
+    let kind = self.classify_uri(&uri).await;
+    let applied = {
+      let mut document_store = self.document_store.write().await;
+      match document_store.get_mut(&uri) {
+        Some(state) => state.apply_changes(version, params.content_changes),
+        // Without the opened text only a full replacement can be applied
+        None
+          if params
+            .content_changes
+            .first()
+            .is_some_and(|change| change.range.is_none()) =>
+        {
+          let mut state = DocumentState::new(version, "", kind);
+          let applied = state.apply_changes(version, params.content_changes);
+          document_store.insert(uri.clone(), state);
+          applied
+        }
+        None => Err("the document is not open".to_string()),
       }
+    };
 
-      self.refresh_document_parse(&uri).await;
-      self.publish_document_diagnostics(&uri, version, text.as_ref()).await;
+    if let Err(error) = applied {
+      // The buffer no longer matches the client; fall back to the file on disk
+      // until the client reopens the document
+      self.document_store.write().await.remove(&uri);
+      self.diagnostics_cache.write().await.remove(&uri);
+      self
+        .client
+        .log_message(
+          MessageType::WARNING,
+          format!("Failed to apply changes to {uri}: {error}; reopen the document to resync"),
+        )
+        .await;
+      return;
     }
+
+    self.schedule_reparse(uri, version);
   }
 
   async fn did_close(&self, params: DidCloseTextDocumentParams) {
@@ -642,10 +1517,55 @@ impl LanguageServer for Backend {
       .publish_diagnostics(params.text_document.uri.clone(), Vec::new(), None)
       .await;
 
+    self.diagnostics_cache.write().await.remove(&params.text_document.uri);
     let mut document_store = self.document_store.write().await;
     document_store.remove(&params.text_document.uri);
   }
 
+  async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+    // Pull-capable clients may send `null` settings; ask for the section instead
+    if *self.workspace_configuration.read().await {
+      self.pull_client_settings().await;
+      return;
+    }
+
+    let section = section_of(&params.settings).cloned();
+    self.client_settings.write().await.set_configuration(section);
+    self.apply_client_settings().await;
+    self.refresh_open_document_diagnostics().await;
+  }
+
+  async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+    for removed in params.event.removed {
+      let Ok(root) = Self::document_path_from_url(&removed.uri) else {
+        continue;
+      };
+      if self.workspace_folders.write().await.remove(&root).is_some() {
+        eprintln!("Workspace folder removed: {}", root.display());
+      }
+      self.control_sockets.write().await.remove(&root);
+    }
+
+    for added in params.event.added {
+      match Self::document_path_from_url(&added.uri) {
+        Ok(root) => {
+          if !self.workspace_folders.read().await.contains(&root) {
+            self.add_workspace_folder(added.name, root).await;
+          }
+        }
+        Err(error) => {
+          self
+            .client
+            .log_message(MessageType::WARNING, format!("Ignoring workspace folder: {error}"))
+            .await;
+        }
+      }
+    }
+
+    // Each folder's layout contributes its own metadata globs
+    self.refresh_watched_files_registration().await;
+  }
+
   async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
     for change in params.changes {
       self.handle_file_change_event(change).await;
@@ -654,9 +1574,16 @@ impl LanguageServer for Backend {
 
   async fn did_delete_files(&self, params: DeleteFilesParams) {
     for file in params.files {
+      if let Ok(uri) = Url::parse(&file.uri) {
+        self.diagnostics_cache.write().await.remove(&uri);
+      }
       match Self::path_from_uri_str(&file.uri) {
         Ok(path) => {
-          if Self::is_metadata_document(&path) {
+          if Self::is_config_file(&path) || self.is_extended_config_file(&path).await {
+            self.handle_config_change(&path).await;
+          } else if self.is_schema_file(&path).await {
+            self.handle_schema_change(&path).await;
+          } else if self.is_metadata_document(&path).await {
             self.handle_watched_path_delete(path).await;
           }
         }
@@ -672,12 +1599,26 @@ impl LanguageServer for Backend {
 
   async fn did_rename_files(&self, params: RenameFilesParams) {
     for file in params.files {
+      if let Ok(uri) = Url::parse(&file.old_uri) {
+        self.diagnostics_cache.write().await.remove(&uri);
+      }
       match (
         Self::path_from_uri_str(&file.old_uri),
         Self::path_from_uri_str(&file.new_uri),
       ) {
         (Ok(old_path), Ok(new_path)) => {
-          if Self::is_metadata_document(&old_path) || Self::is_metadata_document(&new_path) {
+          if Self::is_config_file(&old_path) || self.is_extended_config_file(&old_path).await {
+            self.handle_config_change(&old_path).await;
+          }
+          if Self::is_config_file(&new_path) {
+            self.handle_config_change(&new_path).await;
+          }
+          if self.is_schema_file(&old_path).await {
+            self.handle_schema_change(&old_path).await;
+          } else if self.is_schema_file(&new_path).await {
+            self.handle_schema_change(&new_path).await;
+          }
+          if self.is_metadata_document(&old_path).await || self.is_metadata_document(&new_path).await {
             self.handle_metadata_rename(old_path, new_path).await;
           }
         }
@@ -693,23 +1634,22 @@ impl LanguageServer for Backend {
 
   async fn formatting(&self, params: DocumentFormattingParams) -> LspResult<Option<Vec<TextEdit>>> {
     eprintln!("Formatting requested for {:?}", params.text_document.uri);
+    if self.is_ignored_uri(&params.text_document.uri).await {
+      return Ok(None);
+    }
 
-    // 使用全局配置加载器获取配置
-    let (config, maybe_err) = {
-      let state = match Initializer::get() {
-        Ok(s) => s,
-        Err(e) => {
-          dbg!("Failed to get global state for formatting:", e);
-          return Ok(None);
-        }
+    // 优先使用文档所属 workspace folder 的配置，否则从文档所在目录向上查找配置
+    let folder = self.folder_for_uri(&params.text_document.uri).await;
+    let res = Self::document_path_from_url(&params.text_document.uri).and_then(|path| {
+      let config_manager = match folder.as_ref().and_then(|folder| folder.config_manager.clone()) {
+        Some(manager) => manager,
+        None => WorkspaceFolderState::open_config_manager(path.parent().unwrap_or(&path), &self.runtime_override)?,
       };
-      let res = state
-        .config_manager()
-        .get_override_config(Path::new(params.text_document.uri.as_str()));
-      match res {
-        Ok(cfg) => (Some(cfg), None),
-        Err(err) => (None, Some(err.to_string())),
-      }
+      config_manager.get_override_config(&path).map_err(|err| err.to_string())
+    });
+    let (config, maybe_err) = match res {
+      Ok(cfg) => (Some(cfg), None),
+      Err(err) => (None, Some(err)),
     };
     if let Some(err) = maybe_err {
       self
@@ -723,25 +1663,32 @@ impl LanguageServer for Backend {
         )
         .await;
     }
-    dbg!("Loaded config for formatting:", &config);
+    let file_config = config.unwrap_or_default();
+    let config = match self.client_settings.read().await.apply(&file_config) {
+      Ok(config) => config,
+      Err(error) => {
+        self
+          .client
+          .log_message(
+            MessageType::WARNING,
+            format!("Ignoring invalid `{SETTINGS_SECTION}` client settings: {error}"),
+          )
+          .await;
+        file_config
+      }
+    };
 
     // 获取文档内容
     let document_store = self.document_store.read().await;
-    let Some(content) = document_store
-      .get(&params.text_document.uri)
-      .map(|state| Arc::clone(&state.text))
-    else {
+    let Some(content) = document_store.get(&params.text_document.uri).map(DocumentState::text) else {
       return Ok(None);
     };
     drop(document_store);
 
-    let content = content.as_ref();
+    let content = content.as_str();
 
     // 使用 pangu 格式化文本(在中英文之间添加空格)
-    let formatted = library::formatter::format_text(
-      &Article::new(content),
-      &config.as_ref().unwrap_or(&OverridableConfig::default()).fmt,
-    );
+    let formatted = library::formatter::format_text(&Article::new(content), &config.fmt);
 
     // 计算文档的结束位置 (使用 UTF-16 编码)
     let end_position = offset_to_position(content, content.len()).unwrap_or(Position { line: 0, character: 0 });
@@ -765,10 +1712,29 @@ impl LanguageServer for Backend {
       return Ok(None);
     };
 
-    if position_to_offset(state.text.as_ref(), position).is_none() {
+    if state.position_to_char(position).is_none() {
       return Ok(None);
     }
 
+    let line = position.line as usize;
+    let config_key = Self::document_path_from_url(&uri)
+      .ok()
+      .filter(|path| Self::is_config_file(path) || state.in_frontmatter(line))
+      .zip(
+        state
+          .line_prefix(position)
+          .and_then(|prefix| config_hover::key_at(&state.rope.line(line).to_string(), prefix.chars().count())),
+      )
+      .map(|(path, word)| {
+        // In config files the key is qualified by its enclosing sections
+        if Self::is_config_file(&path) {
+          let mut section = config_completion::enclosing_key_path(&state.text(), line);
+          section.push(word);
+          (path, section.join("."))
+        } else {
+          (path, word)
+        }
+      });
     let hover = match &state.parsed {
       Ok(WorkspaceDocument::Metadata(entity)) => Some(Hover {
         contents: HoverContents::Markup(MarkupContent {
@@ -779,10 +1745,134 @@ impl LanguageServer for Backend {
       }),
       Ok(WorkspaceDocument::Article(_)) | Err(_) => None,
     };
+    let text = config_key.is_some().then(|| state.text());
+    drop(document_store);
 
+    // Config keys (in config files or frontmatter) explain their effective value
+    if let Some(((path, key), text)) = config_key.zip(text)
+      && let Some(config_hover) = self.config_key_hover(&uri, path, key, text).await
+    {
+      return Ok(Some(config_hover));
+    }
     Ok(hover)
   }
 
+  async fn diagnostic(&self, params: DocumentDiagnosticParams) -> LspResult<DocumentDiagnosticReportResult> {
+    let uri = params.text_document.uri;
+
+    let Some((report, _)) = self.diagnostics_report(&uri).await else {
+      return Err(tower_lsp::jsonrpc::Error::invalid_params(format!(
+        "Cannot read document for diagnostics: {uri}"
+      )));
+    };
+
+    let report = if params.previous_result_id.as_deref() == Some(report.result_id.as_str()) {
+      DocumentDiagnosticReport::Unchanged(RelatedUnchangedDocumentDiagnosticReport {
+        related_documents: None,
+        unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+          result_id: report.result_id,
+        },
+      })
+    } else {
+      DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+        related_documents: None,
+        full_document_diagnostic_report: FullDocumentDiagnosticReport {
+          result_id: Some(report.result_id),
+          items: report.items,
+        },
+      })
+    };
+
+    Ok(DocumentDiagnosticReportResult::Report(report))
+  }
+
+  async fn workspace_diagnostic(
+    &self,
+    params: WorkspaceDiagnosticParams,
+  ) -> LspResult<WorkspaceDiagnosticReportResult> {
+    let discoveries: Vec<FileDiscovery> = self
+      .workspace_folders
+      .read()
+      .await
+      .all()
+      .map(|folder| folder.file_discovery())
+      .collect();
+
+    let previous_result_ids: HashMap<Url, String> = params
+      .previous_result_ids
+      .into_iter()
+      .map(|previous| (previous.uri, previous.value))
+      .collect();
+
+    let open_documents: HashMap<Url, (String, i32)> = self
+      .document_store
+      .read()
+      .await
+      .iter()
+      .map(|(uri, state)| (uri.clone(), (state.text(), state.version)))
+      .collect();
+
+    // Walking the tree and reading files blocks; linting happens outside the cache lock
+    let cache = Arc::clone(&self.diagnostics_cache);
+    let feature = self.feature();
+    let linted = tokio::task::spawn_blocking(move || {
+      scoped(feature.as_ref(), || {
+        lint_workspace(&discoveries, open_documents, &cache)
+      })
+    })
+    .await
+    .unwrap_or_default();
+
+    {
+      let mut cache = self.diagnostics_cache.write().await;
+      let current: HashSet<&Url> = linted.iter().map(|document| &document.uri).collect();
+      cache.retain(|uri| current.contains(uri));
+      for document in linted.iter().filter(|document| document.fresh) {
+        cache.insert(document.uri.clone(), document.report.clone());
+      }
+    }
+
+    let mut items = Vec::with_capacity(linted.len());
+    for LintedDocument {
+      uri,
+      text,
+      version,
+      report,
+      ..
+    } in linted
+    {
+      let report = self.with_metadata_diagnostics(&uri, &text, report).await;
+      let version = version.map(i64::from);
+
+      if previous_result_ids.get(&uri) == Some(&report.result_id) {
+        items.push(WorkspaceDocumentDiagnosticReport::Unchanged(
+          WorkspaceUnchangedDocumentDiagnosticReport {
+            uri,
+            version,
+            unchanged_document_diagnostic_report: UnchangedDocumentDiagnosticReport {
+              result_id: report.result_id,
+            },
+          },
+        ));
+      } else {
+        items.push(WorkspaceDocumentDiagnosticReport::Full(
+          WorkspaceFullDocumentDiagnosticReport {
+            uri,
+            version,
+            full_document_diagnostic_report: FullDocumentDiagnosticReport {
+              result_id: Some(report.result_id),
+              items: report.items,
+            },
+          },
+        ));
+      }
+    }
+
+    Ok(WorkspaceDiagnosticReportResult::Report(WorkspaceDiagnosticReport {
+      items,
+    }))
+  }
+
   async fn completion(&self, params: CompletionParams) -> LspResult<Option<CompletionResponse>> {
     if Self::should_return_empty_completion(params.context.as_ref()) {
       return Ok(Some(CompletionResponse::Array(Vec::new())));
@@ -796,23 +1886,62 @@ impl LanguageServer for Backend {
       return Ok(Some(CompletionResponse::Array(Vec::new())));
     };
 
+    // Config files complete settings from the schema, even while they do not parse
+    if Self::document_path_from_url(&uri).is_ok_and(|path| Self::is_config_file(&path)) {
+      let items = state.line_prefix(position).map_or_else(Vec::new, |line_prefix| {
+        config_completion::config_completions(&state.text(), position.line as usize, &line_prefix)
+      });
+      return Ok(Some(CompletionResponse::Array(items)));
+    }
+
     if state.parsed.is_err() {
       return Ok(Some(CompletionResponse::Array(Vec::new())));
     }
 
-    let Some(cursor_offset) = position_to_offset(state.text.as_ref(), position) else {
+    let Some(line_prefix) = state.line_prefix(position) else {
       return Ok(Some(CompletionResponse::Array(Vec::new())));
     };
-    let prefix = extract_active_prefix(state.text.as_ref(), cursor_offset);
+    let prefix = extract_active_prefix(&line_prefix, line_prefix.len());
+    let frontmatter = match &state.parsed {
+      Ok(WorkspaceDocument::Metadata(entity)) if state.in_frontmatter(position.line as usize) => {
+        Some((entity.type_.clone(), state.text()))
+      }
+      _ => None,
+    };
     drop(document_store);
 
-    let Some(index_manager) = self.index_manager().await else {
-      return Ok(Some(CompletionResponse::Array(Vec::new())));
+    // Metadata frontmatter completes keys and values from the schema of the entity's type
+    if let Some((type_, text)) = frontmatter
+      && let Some(folder) = self.folder_for_uri(&uri).await
+      && let Ok(path) = Self::document_path_from_url(&uri)
+      && let Ok(schemas) = Self::entity_schemas(&folder, &path).await
+      && let Some(schema) = schemas.get(&type_)
+    {
+      let items =
+        config_completion::frontmatter_completions(&schema.key_docs(), &text, position.line as usize, &line_prefix);
+      if !items.is_empty() {
+        return Ok(Some(CompletionResponse::Array(items)));
+      }
+    }
+
+    let index_managers = {
+      let workspace_folders = self.workspace_folders.read().await;
+      let Some(folder) = Self::document_path_from_url(&uri)
+        .ok()
+        .and_then(|path| workspace_folders.owner_of(&path))
+      else {
+        return Ok(Some(CompletionResponse::Array(Vec::new())));
+      };
+      workspace_folders.visible_index_managers(&folder)
     };
 
-    let (entities, maybe_warning) = Self::completion_entities_or_empty(index_manager.list_all());
-    if let Some(warning) = maybe_warning {
-      self.client.log_message(MessageType::WARNING, warning).await;
+    let mut entities = Vec::new();
+    for index_manager in index_managers {
+      let (folder_entities, maybe_warning) = Self::completion_entities_or_empty(index_manager.list_all());
+      if let Some(warning) = maybe_warning {
+        self.client.log_message(MessageType::WARNING, warning).await;
+      }
+      entities.extend(folder_entities);
     }
     Ok(Some(CompletionResponse::Array(build_completion_candidates(
       &entities, &prefix,
@@ -859,70 +1988,36 @@ impl LanguageServer for Backend {
           )));
         }
 
-        let index_manager = self
-          .index_manager()
-          .await
-          .ok_or_else(|| to_internal_error("Metadata index manager is not initialized".to_string()))?;
+        let folder = self.folder_or_standalone(&path, path.clone()).await;
+        let index_manager = Self::index_manager_of(folder.as_ref(), to_internal_error)?;
+        let layout = folder
+          .as_ref()
+          .map(|folder| folder.metadata_layout())
+          .unwrap_or_default();
+        let discovery = folder.map_or_else(
+          || pipeline::workspace_discovery(&path, None),
+          |folder| folder.file_discovery(),
+        );
         let db_path = index_manager.db_path().to_path_buf();
 
-        let md_files: Vec<PathBuf> = WalkDir::new(&path)
-          .into_iter()
-          .filter_map(std::result::Result::ok)
-          .filter(|e| e.file_type().is_file() && e.path().extension().is_some_and(|ext| ext.eq_ignore_ascii_case("md")))
-          .map(|e| e.path().to_path_buf())
-          .collect();
-
-        let mut indexed = 0usize;
-        let mut failed = 0usize;
-
-        for file_path in md_files {
-          let file_content = match std::fs::read_to_string(&file_path) {
-            Ok(file_content) => file_content,
-            Err(error) => {
-              eprintln!("Failed to read markdown file {}: {error}", file_path.display());
-              failed += 1;
-              continue;
-            }
-          };
-
-          let (frontmatter, body) = if let Some(rest) = file_content.strip_prefix("---") {
-            if let Some(end) = rest.find("---") {
-              let body_str = &rest[end + 3..].trim_start();
-              (serde_json::json!({}), body_str.to_string())
-            } else {
-              (serde_json::json!({}), file_content)
-            }
-          } else {
-            (serde_json::json!({}), file_content)
-          };
-
-          let id = IndexManager::generate_id(&file_path.to_string_lossy());
-          let type_ = resolve_type(&file_path, &frontmatter);
-          let namespace = generate_namespace(&file_path, &path);
-          let entity = MetadataEntity::new(&id, &type_, &namespace, frontmatter, &body);
-
-          if let Err(error) = index_manager.index_entity(&entity) {
-            eprintln!("Failed to index {}: {error}", file_path.display());
-            failed += 1;
-            continue;
-          }
-
-          indexed += 1;
-        }
-
-        index_manager.flush().map_err(|error| {
-          to_internal_error(format!(
-            "Failed to flush metadata index at {}: {error}",
-            db_path.display()
-          ))
-        })?;
+        let token = self
+          .work_done_token(
+            params.work_done_progress_params.work_done_token.clone(),
+            "novelsaga-index",
+          )
+          .await;
+        let outcome = self.run_index(path, discovery, layout, index_manager, token).await?;
 
         Ok(Some(serde_json::json!({
           "status": "ok",
           "command": "novelsaga/index",
           "db_path": db_path,
-          "indexed": indexed,
-          "failed": failed
+          "indexed": outcome.indexed(),
+          "added": outcome.added,
+          "changed": outcome.changed,
+          "unchanged": outcome.unchanged,
+          "removed": outcome.removed,
+          "failed": outcome.failed
         })))
       }
       "novelsaga/list" => {
@@ -931,10 +2026,25 @@ impl LanguageServer for Backend {
           .log_message(MessageType::INFO, "Executing novelsaga/list command")
           .await;
 
-        let index_manager = self
-          .index_manager()
-          .await
-          .ok_or_else(|| to_internal_error("Metadata index manager is not initialized".to_string()))?;
+        // Optional arguments[0].path selects the folder; otherwise the first folder is listed
+        let folder = match params
+          .arguments
+          .first()
+          .and_then(|arg| arg.get("path"))
+          .and_then(serde_json::Value::as_str)
+        {
+          Some(path_str) => {
+            let path = PathBuf::from(path_str);
+            let dir = if path.is_dir() {
+              path.clone()
+            } else {
+              path.parent().map_or_else(|| path.clone(), Path::to_path_buf)
+            };
+            self.folder_or_standalone(&path, dir).await
+          }
+          None => self.workspace_folders.read().await.primary(),
+        };
+        let index_manager = Self::index_manager_of(folder.as_ref(), to_internal_error)?;
         let db_path = index_manager.db_path().to_path_buf();
         let entities = index_manager.list_all().map_err(|error| {
           to_internal_error(format!(
@@ -968,14 +2078,15 @@ impl LanguageServer for Backend {
         let canonical_path = PathBuf::from(path_str)
           .canonicalize()
           .map_err(|error| to_invalid_params(format!("Failed to canonicalize show path '{path_str}': {error}")))?;
-        let index_manager = self
-          .index_manager()
-          .await
-          .ok_or_else(|| to_internal_error("Metadata index manager is not initialized".to_string()))?;
+        let dir = canonical_path
+          .parent()
+          .map_or_else(|| canonical_path.clone(), Path::to_path_buf);
+        let folder = self.folder_or_standalone(&canonical_path, dir).await;
+        let index_manager = Self::index_manager_of(folder.as_ref(), to_internal_error)?;
         let db_path = index_manager.db_path().to_path_buf();
 
-        let entity_id = IndexManager::generate_id(&canonical_path.to_string_lossy());
-        let entity = index_manager.get_by_id(&entity_id).map_err(|error| {
+        let entity_id = index_manager.get_id_by_path(&canonical_path);
+        let entity = index_manager.get_by_path(&canonical_path).map_err(|error| {
           to_internal_error(format!(
             "Failed to query metadata entity for {}: {error}",
             canonical_path.display()
@@ -1002,26 +2113,131 @@ impl LanguageServer for Backend {
   }
 }
 
-#[cfg(test)]
-mod tests {
-  use std::{path::Path, sync::Arc};
+/// A workspace document linted for a workspace diagnostics pull
+struct LintedDocument {
+  uri: Url,
+  text: String,
+  /// Version of the open buffer; `None` for files read from disk
+  version: Option<i32>,
+  report: DiagnosticsReport,
+  /// Linted now rather than taken from the cache
+  fresh: bool,
+}
+
+/// Run `f` evaluating script configs with `feature`'s loaders when given.
+fn scoped<T>(feature: Option<&Feature>, f: impl FnOnce() -> T) -> T {
+  match feature {
+    Some(feature) => feature.scope(f),
+    None => f(),
+  }
+}
 
-  use novelsaga_core::document::{DocumentKind, WorkspaceDocument};
-  use tower_lsp::lsp_types::{CompletionContext, CompletionTriggerKind, Url};
+/// Lint every diagnosable file of `discoveries` plus the open buffers, reusing
+/// cached reports for unchanged content.
+///
+/// Blocks on the file system and the cache lock; run it on a blocking thread.
+fn lint_workspace(
+  discoveries: &[FileDiscovery],
+  mut open_documents: HashMap<Url, (String, i32)>,
+  cache: &RwLock<DiagnosticsCache>,
+) -> Vec<LintedDocument> {
+  let mut seen = HashSet::new();
+  let mut uris: Vec<Url> = Vec::new();
+  for discovery in discoveries {
+    // Nested folders are walked by their parent too
+    for uri in discover_workspace_documents(discovery)
+      .into_iter()
+      .filter_map(|path| Url::from_file_path(path).ok())
+    {
+      if seen.insert(uri.clone()) {
+        uris.push(uri);
+      }
+    }
+  }
+  // Open buffers outside the walked tree still get reported
+  let mut open_uris: Vec<Url> = open_documents
+    .keys()
+    .filter(|uri| {
+      !seen.contains(*uri) && Backend::document_path_from_url(uri).is_ok_and(|path| is_diagnosable_path(&path))
+    })
+    .cloned()
+    .collect();
+  open_uris.sort();
+  uris.extend(open_uris);
+
+  let mut linted = Vec::with_capacity(uris.len());
+  for uri in uris {
+    let (text, version) = if let Some((text, version)) = open_documents.remove(&uri) {
+      (text, Some(version))
+    } else {
+      let Some(text) = Backend::document_path_from_url(&uri)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+      else {
+        continue;
+      };
+      (text, None)
+    };
+    let cached = cache.blocking_read().get(&uri, &text);
+    let fresh = cached.is_none();
+    let report = cached.unwrap_or_else(|| lint_report(&uri, &text));
+    linted.push(LintedDocument {
+      uri,
+      text,
+      version,
+      report,
+      fresh,
+    });
+  }
+  linted
+}
 
-  use super::Backend;
+#[cfg(test)]
+mod tests {
+  use std::{collections::HashMap, path::Path, sync::Arc};
+
+  use novelsaga_core::{
+    config::metadata::MetadataConfig,
+    discovery::FileDiscovery,
+    document::{DocumentKind, WorkspaceDocument},
+    state::{feat::Feature, init::Initializer},
+  };
+  use tempfile::TempDir;
+  use tokio::sync::RwLock;
+  use tower_lsp::{
+    LanguageServer, LspService,
+    lsp_types::{
+      CompletionContext, CompletionTriggerKind, DiagnosticSeverity, DidChangeConfigurationParams,
+      DidOpenTextDocumentParams, DocumentFormattingParams, FileChangeType, FileEvent, FormattingOptions, HoverContents,
+      HoverParams, Position, TextDocumentIdentifier, TextDocumentItem, TextDocumentPositionParams, Url,
+      WorkDoneProgressParams,
+    },
+  };
+
+  use super::{Backend, lint_workspace};
+  use crate::{args::RuntimeChoice, lsp::diagnostics::DiagnosticsCache};
 
   #[test]
   fn parse_document_keeps_metadata_when_frontmatter_has_error_issue() {
     let uri = Url::parse("file:///workspace/metadata/characters/hero.md").expect("valid uri");
     let text: Arc<str> = Arc::from("---\ntype: character\nmalformed frontmatter line\n---\n# Hero\n");
 
-    let (kind, parsed) = Backend::parse_document(&uri, &text, Some(Path::new("/workspace")));
+    let (kind, parsed) =
+      Backend::parse_document(&uri, &text, Some(Path::new("/workspace")), &MetadataConfig::default());
 
     assert_eq!(kind, DocumentKind::Metadata);
     assert!(matches!(parsed, Ok(WorkspaceDocument::Metadata(_))));
   }
 
+  #[test]
+  fn is_config_file_matches_config_names_but_not_ignore_files() {
+    assert!(Backend::is_config_file(Path::new("/ws/novelsaga.config.json")));
+    assert!(Backend::is_config_file(Path::new("/ws/book/.novelsaga.toml")));
+    assert!(!Backend::is_config_file(Path::new("/ws/.novelsaga.ignore")));
+    assert!(!Backend::is_config_file(Path::new("/ws/novelsaga.md")));
+    assert!(!Backend::is_config_file(Path::new("/ws/metadata/hero.md")));
+  }
+
   #[test]
   fn completion_returns_empty_for_trigger_character_requests() {
     let context = CompletionContext {
@@ -1053,4 +2269,331 @@ mod tests {
     assert!(warning.contains("Failed to list metadata entities for completion"));
     assert!(warning.contains("index unavailable"));
   }
+
+  #[test]
+  fn lint_workspace_dedups_nested_folders_and_prefers_open_buffers() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let root = temp_dir.path();
+    std::fs::create_dir_all(root.join("book"))?;
+    std::fs::write(root.join("book/chapter-01.md"), "---\ntype: 1\n---\nBody")?;
+    std::fs::write(root.join("notes.md"), "Notes")?;
+    let chapter = Url::from_file_path(root.join("book/chapter-01.md")).expect("file uri");
+    let discoveries = [FileDiscovery::new(root), FileDiscovery::new(root.join("book"))];
+    let cache = RwLock::new(DiagnosticsCache::default());
+
+    let open = HashMap::from([(chapter.clone(), ("Fixed".to_string(), 3))]);
+    let linted = lint_workspace(&discoveries, open, &cache);
+    assert_eq!(linted.len(), 2);
+    let document = linted.iter().find(|document| document.uri == chapter).expect("chapter");
+    assert_eq!((document.text.as_str(), document.version), ("Fixed", Some(3)));
+    assert!(document.report.items.is_empty() && document.fresh);
+
+    let on_disk = lint_workspace(&discoveries, HashMap::new(), &cache);
+    let document = on_disk
+      .into_iter()
+      .find(|document| document.uri == chapter)
+      .expect("chapter");
+    assert_eq!((document.report.items.len(), document.version), (1, None));
+
+    cache.blocking_write().insert(chapter.clone(), document.report.clone());
+    let again = lint_workspace(&discoveries, HashMap::new(), &cache);
+    assert!(again.iter().any(|document| document.uri == chapter && !document.fresh));
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn editing_an_extended_file_updates_the_config_of_its_dependents() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let house = temp_dir.path().canonicalize()?.join("house.yaml");
+    let root = temp_dir.path().canonicalize()?.join("book");
+    std::fs::create_dir_all(&root)?;
+    std::fs::write(&house, "fmt:\n  indent_spaces: 2\n")?;
+    std::fs::write(
+      root.join("novelsaga.config.yaml"),
+      "extends: [../house.yaml]\nworkspace: {}\n",
+    )?;
+    let chapter = root.join("chapter-01.md");
+    std::fs::write(&chapter, "Body")?;
+
+    // Another test may have initialized it already
+    let _ = Initializer::init(Feature::new(None, None));
+    let (service, _socket) = LspService::new(Backend::new);
+    let backend = service.inner().clone();
+    backend.add_workspace_folder("book".to_string(), root.clone()).await;
+    let indent_spaces = || async {
+      let folder = backend.folder_for_path(&chapter).await.expect("routed to the folder");
+      let config_manager = folder.config_manager.clone().expect("config loaded");
+      config_manager
+        .get_override_config(&chapter)
+        .map(|config| config.fmt.indent_spaces)
+    };
+    assert_eq!(indent_spaces().await?, 2);
+    assert!(
+      backend
+        .watched_globs()
+        .await
+        .contains(&house.to_string_lossy().into_owned())
+    );
+
+    std::fs::write(&house, "fmt:\n  indent_spaces: 6\n")?;
+    backend
+      .handle_file_change_event(FileEvent {
+        uri: Url::from_file_path(&house).expect("file uri"),
+        typ: FileChangeType::CHANGED,
+      })
+      .await;
+    assert_eq!(indent_spaces().await?, 6);
+
+    backend.close().await;
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn layout_changes_reindex_the_folder_and_rewatch_its_metadata() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let root = temp_dir.path().canonicalize()?;
+    let config = root.join("novelsaga.config.yaml");
+    std::fs::write(&config, "workspace: {}\n")?;
+    std::fs::create_dir_all(root.join("设定/人物"))?;
+    std::fs::write(root.join("设定/人物/hero.md"), "---\nname: Hero\n---\n")?;
+
+    let _ = Initializer::init(Feature::new(None, None));
+    let (service, _socket) = LspService::new(Backend::new);
+    let backend = service.inner().clone();
+    backend.add_workspace_folder("book".to_string(), root.clone()).await;
+    let index_manager = backend
+      .folder_for_path(&root)
+      .await
+      .and_then(|folder| folder.index_manager.clone())
+      .expect("index opened");
+    assert!(index_manager.list_all()?.is_empty());
+
+    std::fs::write(
+      &config,
+      "workspace: {}\nmetadata:\n  dirs: [设定]\n  folder_types:\n    人物: character\n",
+    )?;
+    backend
+      .handle_file_change_event(FileEvent {
+        uri: Url::from_file_path(&config).expect("file uri"),
+        typ: FileChangeType::CHANGED,
+      })
+      .await;
+    assert!(backend.watched_globs().await.contains(&"**/设定/**/*.md".to_string()));
+
+    let mut types = Vec::new();
+    for _ in 0..100 {
+      types = index_manager
+        .list_all()?
+        .into_iter()
+        .map(|entity| entity.type_)
+        .collect();
+      if !types.is_empty() {
+        break;
+      }
+      tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    }
+    assert_eq!(types, vec!["character".to_string()]);
+
+    backend.close().await;
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn schema_files_are_cached_until_they_change() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let root = temp_dir.path().canonicalize()?;
+    std::fs::write(root.join("novelsaga.config.yaml"), "workspace: {}\n")?;
+    let schema = root.join("book-01/metadata/_schema/character.yaml");
+    std::fs::create_dir_all(schema.parent().expect("schema dir"))?;
+    std::fs::write(&schema, "required: [name]\n")?;
+    let hero = root.join("book-01/metadata/characters/hero.md");
+    let text = "---\nalias: Al\n---\n";
+
+    let _ = Initializer::init(Feature::new(None, None));
+    let (service, _socket) = LspService::new(Backend::new);
+    let backend = service.inner().clone();
+    backend.add_workspace_folder("book".to_string(), root.clone()).await;
+    let uri = Url::from_file_path(&hero).expect("file uri");
+    assert_eq!(backend.metadata_diagnostics(&uri, text).await.len(), 1);
+    assert!(
+      backend
+        .watched_globs()
+        .await
+        .contains(&"**/metadata/_schema/*".to_string())
+    );
+
+    std::fs::write(&schema, "required: []\n")?;
+    assert_eq!(backend.metadata_diagnostics(&uri, text).await.len(), 1);
+    backend
+      .handle_file_change_event(FileEvent {
+        uri: Url::from_file_path(&schema).expect("file uri"),
+        typ: FileChangeType::CHANGED,
+      })
+      .await;
+    assert!(backend.metadata_diagnostics(&uri, text).await.is_empty());
+
+    backend.close().await;
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn lint_severity_settings_relevel_and_silence_diagnostics() {
+    let uri = Url::parse("file:///novel/chapter-01.md").expect("valid uri");
+    let text = "---\ntitle: Hero\nnot a pair\n---\nBody";
+
+    let (service, _socket) = LspService::new(Backend::new);
+    let backend = service.inner().clone();
+    let default = backend.document_diagnostics(&uri, text).await;
+    assert_eq!(default.items[0].severity, Some(DiagnosticSeverity::ERROR));
+
+    let configure = |severity: &str| DidChangeConfigurationParams {
+      settings: serde_json::json!({ "novelsaga": { "lint": { "severity": { "frontmatter": severity } } } }),
+    };
+    backend.did_change_configuration(configure("warning")).await;
+    let releveled = backend.document_diagnostics(&uri, text).await;
+    assert_eq!(releveled.items[0].severity, Some(DiagnosticSeverity::WARNING));
+    assert_ne!(releveled.result_id, default.result_id);
+
+    backend.did_change_configuration(configure("off")).await;
+    assert!(backend.document_diagnostics(&uri, text).await.items.is_empty());
+  }
+
+  #[tokio::test]
+  async fn runtime_settings_apply_to_their_own_connection_only() {
+    let (service, _socket) = LspService::new(Backend::new);
+    let node = service.inner().clone();
+    let (service, _socket) = LspService::new(Backend::new);
+    let bun = service.inner().clone();
+    let configure = |settings: serde_json::Value| DidChangeConfigurationParams {
+      settings: serde_json::json!({ "novelsaga": settings }),
+    };
+    let runtime = |backend: &Backend| {
+      backend
+        .runtime_override
+        .read()
+        .unwrap()
+        .as_ref()
+        .map(|runtime| (runtime.choice, runtime.path.clone()))
+    };
+
+    node
+      .did_change_configuration(configure(
+        serde_json::json!({ "runtime": "node", "runtime_path": "/opt/node" }),
+      ))
+      .await;
+    bun
+      .did_change_configuration(configure(serde_json::json!({ "runtime": "bun" })))
+      .await;
+    assert_eq!(runtime(&node), Some((RuntimeChoice::Node, Some("/opt/node".into()))));
+    assert_eq!(runtime(&bun), Some((RuntimeChoice::Bun, None)));
+
+    // Dropping the setting on one connection keeps the other's choice
+    bun.did_change_configuration(configure(serde_json::json!({}))).await;
+    assert_eq!(runtime(&bun), None);
+    assert_eq!(runtime(&node), Some((RuntimeChoice::Node, Some("/opt/node".into()))));
+  }
+
+  #[tokio::test]
+  async fn files_outside_every_folder_are_not_routed_to_the_primary_folder() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let root = temp_dir.path().canonicalize()?;
+    let book = root.join("book");
+    let elsewhere = root.join("elsewhere/metadata/characters/hero.md");
+    std::fs::create_dir_all(&book)?;
+    std::fs::create_dir_all(elsewhere.parent().expect("metadata dir"))?;
+    std::fs::write(&elsewhere, "---\nname: Hero\n---\n")?;
+
+    let _ = Initializer::init(Feature::new(None, None));
+    let (service, _socket) = LspService::new(Backend::new);
+    let backend = service.inner().clone();
+    backend.add_workspace_folder("book".to_string(), book.clone()).await;
+
+    assert!(backend.folder_for_path(&elsewhere).await.is_none());
+    backend.upsert_metadata_from_disk(elsewhere.clone()).await;
+    let folder = backend.folder_for_path(&book).await.expect("book folder");
+    let index_manager = folder.index_manager.clone().expect("book index");
+    assert!(index_manager.list_all()?.is_empty());
+
+    backend.close().await;
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn ignored_documents_get_no_diagnostics_or_formatting() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let root = temp_dir.path().canonicalize()?;
+    std::fs::write(root.join(".novelsagaignore"), "drafts/\n")?;
+    std::fs::create_dir_all(root.join("drafts"))?;
+    let text = "---\ntitle: Hero\nnot a pair\n---\nBody";
+
+    let _ = Initializer::init(Feature::new(None, None));
+    let (service, _socket) = LspService::new(Backend::new);
+    let backend = service.inner().clone();
+    backend.add_workspace_folder("book".to_string(), root.clone()).await;
+
+    let draft = Url::from_file_path(root.join("drafts/idea.md")).expect("file uri");
+    let chapter = Url::from_file_path(root.join("chapter-01.md")).expect("file uri");
+    assert!(backend.document_diagnostics(&draft, text).await.items.is_empty());
+    assert_eq!(backend.document_diagnostics(&chapter, text).await.items.len(), 1);
+
+    backend
+      .did_open(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem::new(draft.clone(), "markdown".to_string(), 1, "中文English".to_string()),
+      })
+      .await;
+    let formatting = backend
+      .formatting(DocumentFormattingParams {
+        text_document: TextDocumentIdentifier::new(draft),
+        options: FormattingOptions::default(),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+      })
+      .await?;
+    assert_eq!(formatting, None);
+
+    backend.close().await;
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn config_key_hover_uses_the_open_document_text() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let root = temp_dir.path().canonicalize()?;
+    std::fs::write(root.join("novelsaga.config.yaml"), "fmt:\n  indent_spaces: 2\n")?;
+    let chapter = root.join("chapter-01.md");
+    std::fs::write(&chapter, "---\nfmt:\n  indent_spaces: 6\n---\nBody")?;
+
+    let _ = Initializer::init(Feature::new(None, None));
+    let (service, _socket) = LspService::new(Backend::new);
+    let backend = service.inner().clone();
+    backend.add_workspace_folder("book".to_string(), root.clone()).await;
+
+    let uri = Url::from_file_path(&chapter).expect("file uri");
+    backend
+      .did_open(DidOpenTextDocumentParams {
+        text_document: TextDocumentItem::new(
+          uri.clone(),
+          "markdown".to_string(),
+          1,
+          "---\nfmt:\n  indent_spaces: 8\n---\nBody".to_string(),
+        ),
+      })
+      .await;
+    let hover = backend
+      .hover(HoverParams {
+        text_document_position_params: TextDocumentPositionParams::new(
+          TextDocumentIdentifier::new(uri),
+          Position::new(2, 4),
+        ),
+        work_done_progress_params: WorkDoneProgressParams::default(),
+      })
+      .await?
+      .expect("config key hover");
+    let HoverContents::Markup(markup) = hover.contents else {
+      panic!("expected markdown hover");
+    };
+    assert!(markup.value.contains("= `8`"), "{}", markup.value);
+
+    backend.close().await;
+    Ok(())
+  }
 }
@@ -108,7 +108,11 @@ fn entity_label(entity: &MetadataEntity) -> String {
   ["name", "title"]
     .into_iter()
     .find_map(|field| entity.get_field(field).and_then(|value| value.as_str()))
-    .map_or_else(|| entity.id.clone(), ToString::to_string)
+    .map_or_else(
+      // Namespace-qualified ids (`book-01/character/hero`) read best by their last segment
+      || entity.id.rsplit('/').next().unwrap_or(&entity.id).to_string(),
+      ToString::to_string,
+    )
 }
 
 /// Match priority per plan specification:
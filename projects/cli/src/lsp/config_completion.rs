@@ -0,0 +1,262 @@
+//! Completion in config files: the keys of the enclosing section and the
+//! values of the key being set, from the config JSON Schema. Metadata
+//! frontmatter completes the same way from the schema of the entity's type.
+//!
+//! The enclosing section is found from the text alone — indentation for
+//! YAML, JSON and JS, `[section]` headers for TOML — so it works on files
+//! that do not parse yet.
+
+use novelsaga_core::config::schema::{ConfigKeyDoc, config_key_docs};
+use tower_lsp::lsp_types::{CompletionItem, CompletionItemKind, Documentation, MarkupContent, MarkupKind};
+
+use crate::lsp::config_hover::key_doc_markdown;
+
+/// Keys of the objects enclosing line `line` (0-based), outermost first.
+pub fn enclosing_key_path(text: &str, line: usize) -> Vec<String> {
+  // `None` indent: a TOML section, left only by the next header
+  let mut stack: Vec<(Option<usize>, String)> = Vec::new();
+  let pop_deeper = |stack: &mut Vec<(Option<usize>, String)>, indent: usize| {
+    while stack
+      .last()
+      .is_some_and(|(depth, _)| depth.is_some_and(|depth| depth >= indent))
+    {
+      stack.pop();
+    }
+  };
+
+  for raw in text.lines().take(line) {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with("//") {
+      continue;
+    }
+    if let Some(section) = toml_section(trimmed) {
+      stack = section.split('.').map(|key| (None, unquote(key).to_string())).collect();
+      continue;
+    }
+    let indent = indent_of(raw);
+    pop_deeper(&mut stack, indent);
+    if let Some(key) = opened_block_key(trimmed) {
+      stack.push((Some(indent), key.to_string()));
+    }
+  }
+  pop_deeper(&mut stack, text.lines().nth(line).map_or(0, indent_of));
+
+  stack.into_iter().map(|(_, key)| key).collect()
+}
+
+/// Completion items for the cursor at the end of `line_prefix` on line `line`.
+pub fn config_completions(text: &str, line: usize, line_prefix: &str) -> Vec<CompletionItem> {
+  key_completions(config_key_docs(), text, line, line_prefix)
+}
+
+/// Completion items in the frontmatter of a metadata document whose type
+/// declares the keys `docs`.
+pub fn frontmatter_completions(
+  docs: &[ConfigKeyDoc],
+  text: &str,
+  line: usize,
+  line_prefix: &str,
+) -> Vec<CompletionItem> {
+  key_completions(docs, text, line, line_prefix)
+}
+
+fn key_completions(docs: &[ConfigKeyDoc], text: &str, line: usize, line_prefix: &str) -> Vec<CompletionItem> {
+  let section = enclosing_key_path(text, line);
+
+  if let Some(key) = key_being_set(line_prefix) {
+    let key = section
+      .iter()
+      .map(String::as_str)
+      .chain([key])
+      .collect::<Vec<_>>()
+      .join(".");
+    return docs
+      .iter()
+      .find(|doc| doc.key == key)
+      .map(value_completions)
+      .unwrap_or_default();
+  }
+
+  let typed = line_prefix.rsplit(|c: char| !is_key_char(c)).next().unwrap_or_default();
+  let parent = section.join(".");
+  docs
+    .iter()
+    .filter(|doc| doc.key.rsplit_once('.').map_or("", |(parent, _)| parent) == parent)
+    .filter(|doc| doc.name().starts_with(typed))
+    .map(|doc| CompletionItem {
+      label: doc.name().to_string(),
+      kind: Some(if doc.value_type == "object" {
+        CompletionItemKind::MODULE
+      } else {
+        CompletionItemKind::PROPERTY
+      }),
+      detail: Some(doc.value_type.clone()),
+      documentation: Some(markdown(key_doc_markdown(doc))),
+      ..CompletionItem::default()
+    })
+    .collect()
+}
+
+/// `true`/`false` for booleans and the allowed values of enums, plus the default value.
+fn value_completions(doc: &ConfigKeyDoc) -> Vec<CompletionItem> {
+  let mut values: Vec<(String, Option<String>)> = Vec::new();
+  if doc.value_type == "boolean" {
+    values.extend([("true".to_string(), None), ("false".to_string(), None)]);
+  }
+  values.extend(doc.allowed_values.iter().map(|value| (value.to_string(), None)));
+  if let Some(default) = doc.default.as_ref().filter(|default| !default.is_object()) {
+    let default = default.to_string();
+    values.retain(|(value, _)| *value != default);
+    values.insert(0, (default, Some("default".to_string())));
+  }
+
+  values
+    .into_iter()
+    .map(|(label, detail)| CompletionItem {
+      label,
+      kind: Some(CompletionItemKind::VALUE),
+      detail,
+      documentation: Some(markdown(key_doc_markdown(doc))),
+      ..CompletionItem::default()
+    })
+    .collect()
+}
+
+/// The key of `key: ` / `key = ` when the cursor is in its value.
+fn key_being_set(line_prefix: &str) -> Option<&str> {
+  let (key, _) = line_prefix.trim_start().split_once([':', '='])?;
+  let key = unquote(key.trim());
+  (!key.is_empty() && key.chars().all(is_key_char)).then_some(key)
+}
+
+/// Key of a line opening a nested object: `key:` (YAML), `key: {` / `"key": {`
+/// (JSON, JS) or `key = {` spanning several lines.
+fn opened_block_key(trimmed: &str) -> Option<&str> {
+  let (key, rest) = trimmed.split_once([':', '='])?;
+  let key = unquote(key.trim());
+  let rest = rest.trim();
+  (!key.is_empty() && key.chars().all(is_key_char) && (rest.is_empty() || rest.starts_with('{')) && !rest.contains('}'))
+    .then_some(key)
+}
+
+/// Name of a TOML `[section]` header.
+fn toml_section(trimmed: &str) -> Option<&str> {
+  let inner = trimmed.strip_prefix('[')?.strip_suffix(']')?.trim();
+  (!inner.is_empty()
+    && !inner.starts_with('[')
+    && inner.chars().all(|c| is_key_char(c) || matches!(c, '.' | '"' | '\'')))
+  .then_some(inner)
+}
+
+fn unquote(key: &str) -> &str {
+  key.trim_matches(|c| c == '"' || c == '\'')
+}
+
+fn indent_of(line: &str) -> usize {
+  line.len() - line.trim_start().len()
+}
+
+fn is_key_char(c: char) -> bool {
+  c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+fn markdown(value: String) -> Documentation {
+  Documentation::MarkupContent(MarkupContent {
+    kind: MarkupKind::Markdown,
+    value,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use novelsaga_core::metadata::EntitySchema;
+  use serde_json::json;
+
+  use super::{config_completions, enclosing_key_path, frontmatter_completions};
+
+  fn labels(text: &str, line: usize, line_prefix: &str) -> Vec<String> {
+    config_completions(text, line, line_prefix)
+      .into_iter()
+      .map(|item| item.label)
+      .collect()
+  }
+
+  #[test]
+  fn enclosing_path_follows_indentation_and_toml_sections() {
+    let yaml = "workspace:\n  cache_dir: .cache\nfmt:\n  indent_spaces: 2\n  \n";
+    assert_eq!(enclosing_key_path(yaml, 1), vec!["workspace"]);
+    assert_eq!(enclosing_key_path(yaml, 4), vec!["fmt"]);
+    assert!(enclosing_key_path(yaml, 2).is_empty());
+
+    let json = "{\n  \"fmt\": {\n    \"indent_spaces\": 2,\n  },\n  \n}";
+    assert_eq!(enclosing_key_path(json, 2), vec!["fmt"]);
+    assert!(enclosing_key_path(json, 4).is_empty());
+
+    let toml = "[workspace]\ncache_dir = \".cache\"\n\n[fmt]\nindent_spaces = 2\n";
+    assert_eq!(enclosing_key_path(toml, 1), vec!["workspace"]);
+    assert_eq!(enclosing_key_path(toml, 4), vec!["fmt"]);
+
+    let js = "export default {\n  fmt: {\n    indent_spaces: 2,\n  },\n};";
+    assert_eq!(enclosing_key_path(js, 2), vec!["fmt"]);
+  }
+
+  #[test]
+  fn completes_keys_of_the_enclosing_section() {
+    assert_eq!(
+      labels("", 0, ""),
+      vec!["extends", "fmt", "metadata", "overrides", "workspace"]
+    );
+    assert_eq!(
+      labels("fmt:\n  ", 1, "  "),
+      vec!["blank_lines_between_paragraphs", "indent_spaces"]
+    );
+    assert_eq!(
+      labels("[workspace]\ncross", 1, "cross"),
+      vec!["cross_folder_resolution"]
+    );
+  }
+
+  #[test]
+  fn completes_values_of_the_key_being_set() {
+    assert_eq!(
+      labels(
+        "workspace:\n  cross_folder_resolution: ",
+        1,
+        "  cross_folder_resolution: "
+      ),
+      vec!["false", "true"]
+    );
+    assert_eq!(labels("fmt:\n  indent_spaces: ", 1, "  indent_spaces: "), vec!["4"]);
+    assert!(labels("fmt:\n  unknown: ", 1, "  unknown: ").is_empty());
+  }
+
+  #[test]
+  fn frontmatter_completes_keys_and_values_from_the_type_schema() {
+    let schema = EntitySchema::new(
+      "character",
+      json!({
+        "properties": {
+          "name": { "type": "string" },
+          "gender": { "enum": ["female", "male", "other"] },
+          "appearance": { "type": "object", "properties": { "height": { "type": "integer" } } }
+        }
+      }),
+    )
+    .expect("valid schema");
+    let docs = schema.key_docs();
+    let labels = |text: &str, line: usize, line_prefix: &str| -> Vec<String> {
+      frontmatter_completions(&docs, text, line, line_prefix)
+        .into_iter()
+        .map(|item| item.label)
+        .collect()
+    };
+
+    assert_eq!(labels("---\n\n---\n", 1, ""), vec!["appearance", "gender", "name"]);
+    assert_eq!(labels("---\nappearance:\n  \n---\n", 2, "  "), vec!["height"]);
+    assert_eq!(
+      labels("---\ngender: \n---\n", 1, "gender: "),
+      vec!["\"female\"", "\"male\"", "\"other\""]
+    );
+    assert!(labels("---\nname: \n---\n", 1, "name: ").is_empty());
+  }
+}
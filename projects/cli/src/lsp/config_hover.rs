@@ -0,0 +1,133 @@
+//! Hover on config keys — in config files and markdown frontmatter — showing
+//! the effective value and which file and line set it, and in config files
+//! the setting's documentation.
+
+use std::fmt::Write;
+
+use novelsaga_core::{
+  config::schema::ConfigKeyDoc,
+  state::{ConfigSource, ExplainedConfig},
+};
+
+/// Key-like word (`[A-Za-z0-9_-]`) around char index `character` of `line`.
+pub fn key_at(line: &str, character: usize) -> Option<String> {
+  let chars: Vec<char> = line.chars().collect();
+  let is_key_char = |c: &char| c.is_alphanumeric() || *c == '_' || *c == '-';
+
+  let start = chars[..character.min(chars.len())]
+    .iter()
+    .rposition(|c| !is_key_char(c))
+    .map_or(0, |index| index + 1);
+  let end = chars[start..]
+    .iter()
+    .position(|c| !is_key_char(c))
+    .map_or(chars.len(), |offset| start + offset);
+
+  (start < end).then(|| chars[start..end].iter().collect())
+}
+
+/// Markdown describing the settings `word` names: a full dotted key, its last
+/// segment, or a section (e.g. `fmt`) listing every key below it.
+pub fn config_key_hover(explained: &ExplainedConfig, word: &str) -> Option<String> {
+  let section = format!("{word}.");
+  let leaf = format!(".{word}");
+  let entries: Vec<_> = explained
+    .entries
+    .iter()
+    .filter(|entry| entry.key == word || entry.key.ends_with(&leaf) || entry.key.starts_with(&section))
+    .collect();
+  if entries.is_empty() {
+    return None;
+  }
+
+  let lines: Vec<String> = entries
+    .iter()
+    .map(|entry| {
+      let source = match &entry.source {
+        ConfigSource::Default => "default value".to_string(),
+        source => format!("set by `{source}`"),
+      };
+      format!("- **`{}`** = `{}` — {source}", entry.key, entry.value)
+    })
+    .collect();
+  Some(format!("**Effective configuration**\n\n{}", lines.join("\n")))
+}
+
+/// Markdown documenting a setting: its key, type, description and default.
+pub fn key_doc_markdown(doc: &ConfigKeyDoc) -> String {
+  let mut markdown = format!("**`{}`**: `{}`", doc.key, doc.value_type);
+  if let Some(description) = &doc.description {
+    markdown.push_str("\n\n");
+    markdown.push_str(description);
+  }
+  if let Some(default) = doc.default.as_ref().filter(|default| !default.is_object()) {
+    let _ = write!(markdown, "\n\nDefault: `{default}`");
+  }
+  markdown
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::PathBuf;
+
+  use novelsaga_core::{
+    config::{OverridableConfig, schema::config_key_doc},
+    state::{ConfigEntry, ConfigSource, ExplainedConfig},
+  };
+  use serde_json::json;
+
+  use super::{config_key_hover, key_at, key_doc_markdown};
+
+  fn explained() -> ExplainedConfig {
+    ExplainedConfig {
+      config: OverridableConfig::default(),
+      entries: vec![
+        ConfigEntry {
+          key: "fmt.blank_lines_between_paragraphs".to_string(),
+          value: json!(2),
+          source: ConfigSource::File {
+            path: PathBuf::from("/novel/novelsaga.config.yaml"),
+            line: Some(3),
+          },
+        },
+        ConfigEntry {
+          key: "fmt.indent_spaces".to_string(),
+          value: json!(4),
+          source: ConfigSource::Default,
+        },
+      ],
+      files: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn key_at_extracts_word_under_cursor() {
+    assert_eq!(key_at("  indent_spaces: 3", 5).as_deref(), Some("indent_spaces"));
+    assert_eq!(key_at("  \"fmt\": {", 3).as_deref(), Some("fmt"));
+    assert_eq!(key_at("  indent_spaces: 3", 16), None);
+  }
+
+  #[test]
+  fn hover_shows_value_and_source_for_leaf_and_section() {
+    let hover = config_key_hover(&explained(), "blank_lines_between_paragraphs").expect("hover");
+    assert!(hover.contains("**`fmt.blank_lines_between_paragraphs`** = `2` — set by `/novel/novelsaga.config.yaml:3`"));
+    assert!(!hover.contains("indent_spaces"));
+
+    let section = config_key_hover(&explained(), "fmt").expect("hover");
+    assert!(section.contains("**`fmt.indent_spaces`** = `4` — default value"));
+    assert!(section.contains("blank_lines_between_paragraphs"));
+
+    assert!(config_key_hover(&explained(), "title").is_none());
+  }
+
+  #[test]
+  fn key_docs_show_type_description_and_default() {
+    let doc = config_key_doc("workspace.cache_dir").expect("documented");
+    assert_eq!(
+      key_doc_markdown(doc),
+      "**`workspace.cache_dir`**: `string`\n\n索引缓存目录，相对于工作区根目录\n\n绝对路径、`~/…` 或 \
+       `$cache/…`（用户缓存目录）为多个工作区共享的目录，\n每个工作区使用其中一个子目录\n\nDefault: \
+       `\".cache/novelsaga\"`"
+    );
+  }
+}
@@ -0,0 +1,342 @@
+//! Per-workspace control socket of a running LSP server.
+//!
+//! CLI commands (e.g. `novelsaga metadata index`) connect here while an editor
+//! has the workspace open, so they reuse the server's live `IndexManager`
+//! instead of competing for the sled lock. The protocol is newline-delimited
+//! JSON-RPC 2.0 (see `UnixSocketTransport`); only `workspace/executeCommand`
+//! is accepted.
+
+use std::{
+  io,
+  os::unix::{
+    fs::{DirBuilderExt, PermissionsExt},
+    net::UnixStream,
+  },
+  path::{Path, PathBuf},
+};
+
+use serde_json::{Value, json};
+use tokio::{
+  io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+  net::{UnixListener, unix::OwnedWriteHalf},
+  task::JoinHandle,
+};
+use tower_lsp::{LanguageServer, lsp_types::ExecuteCommandParams};
+
+use crate::{
+  bridge::rpc::types::{RpcError, RpcErrorResponse, RpcRequest, RpcSuccessResponse, error_codes},
+  lsp::{Backend, backend::WeakBackend},
+};
+
+const EXECUTE_COMMAND_METHOD: &str = "workspace/executeCommand";
+
+/// Socket path for the LSP serving `workspace_root`.
+///
+/// Lives in `$XDG_RUNTIME_DIR` (or a per-user directory in the temp dir)
+/// rather than the workspace so it stays within the `sockaddr_un` length
+/// limit; the name is keyed by a hash of the canonical root.
+pub fn control_socket_path(workspace_root: &Path) -> PathBuf {
+  let root = workspace_root
+    .canonicalize()
+    .unwrap_or_else(|_| workspace_root.to_path_buf());
+  let hash = blake3::hash(root.to_string_lossy().as_bytes()).to_hex();
+
+  runtime_dir().join(format!("novelsaga-lsp-{}.sock", &hash[..16]))
+}
+
+fn runtime_dir() -> PathBuf {
+  std::env::var_os("XDG_RUNTIME_DIR").map_or_else(
+    || {
+      let user = std::env::var("USER")
+        .or_else(|_| std::env::var("LOGNAME"))
+        .unwrap_or_else(|_| "default".to_string());
+      std::env::temp_dir().join(format!("novelsaga-{user}"))
+    },
+    PathBuf::from,
+  )
+}
+
+/// Create `dir` accessible only to the current user, or check that an
+/// existing one is not accessible to anyone else.
+fn ensure_private_dir(dir: &Path) -> io::Result<()> {
+  match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+    Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+      let metadata = std::fs::symlink_metadata(dir)?;
+      if !metadata.is_dir() || metadata.permissions().mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+          io::ErrorKind::PermissionDenied,
+          format!("{} must be a directory accessible only to its owner", dir.display()),
+        ));
+      }
+      Ok(())
+    }
+    result => result,
+  }
+}
+
+/// Socket of a live LSP serving `path` or one of its ancestors, if any.
+pub fn find_live_control_socket(path: &Path) -> Option<PathBuf> {
+  let path = path.canonicalize().ok()?;
+  path
+    .ancestors()
+    .map(control_socket_path)
+    .find(|socket_path| socket_path.exists() && UnixStream::connect(socket_path).is_ok())
+}
+
+/// A bound control socket; the socket file is removed when dropped.
+#[derive(Debug)]
+pub struct ControlSocket {
+  path: PathBuf,
+  accept_task: JoinHandle<()>,
+}
+
+impl ControlSocket {
+  /// Bind the control socket for `workspace_root` and serve it with `backend`.
+  ///
+  /// A leftover socket file nobody listens on is removed first. The socket is
+  /// only accessible to the current user. `backend` is held weakly so the
+  /// socket does not keep a disconnected server alive.
+  ///
+  /// # Errors
+  ///
+  /// Returns `AddrInUse` when another server already serves the workspace, or
+  /// the underlying I/O error when binding fails.
+  pub fn bind(workspace_root: &Path, backend: WeakBackend) -> io::Result<Self> {
+    let path = control_socket_path(workspace_root);
+    if let Some(dir) = path.parent() {
+      ensure_private_dir(dir)?;
+    }
+    remove_stale_socket(&path)?;
+
+    let listener = UnixListener::bind(&path)?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    let accept_task = tokio::spawn(async move {
+      loop {
+        match listener.accept().await {
+          Ok((stream, _)) => {
+            tokio::spawn(serve_connection(stream, backend.clone()));
+          }
+          Err(error) => {
+            eprintln!("Control socket accept failed: {error}");
+            break;
+          }
+        }
+      }
+    });
+
+    Ok(Self { path, accept_task })
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+}
+
+impl Drop for ControlSocket {
+  fn drop(&mut self) {
+    self.accept_task.abort();
+    let _ = std::fs::remove_file(&self.path);
+  }
+}
+
+/// Remove a socket file left behind by a dead process.
+///
+/// # Errors
+///
+/// Returns `AddrInUse` when a process still listens on `path`.
+pub fn remove_stale_socket(path: &Path) -> io::Result<()> {
+  if !path.exists() {
+    return Ok(());
+  }
+  if UnixStream::connect(path).is_ok() {
+    return Err(io::Error::new(
+      io::ErrorKind::AddrInUse,
+      format!("Another NovelSaga LSP is serving {}", path.display()),
+    ));
+  }
+
+  std::fs::remove_file(path)
+}
+
+async fn serve_connection(stream: tokio::net::UnixStream, backend: WeakBackend) {
+  let (reader, mut writer) = stream.into_split();
+  let mut lines = BufReader::new(reader).lines();
+
+  loop {
+    let line = match lines.next_line().await {
+      Ok(Some(line)) => line,
+      Ok(None) => break,
+      Err(error) => {
+        eprintln!("Control socket read failed: {error}");
+        break;
+      }
+    };
+    if line.trim().is_empty() {
+      continue;
+    }
+
+    let Some(backend) = backend.upgrade() else {
+      break;
+    };
+    let response = handle_line(&line, &backend).await;
+    if let Err(error) = write_response(&mut writer, &response).await {
+      eprintln!("Control socket write failed: {error}");
+      break;
+    }
+  }
+}
+
+async fn write_response(writer: &mut OwnedWriteHalf, response: &Value) -> io::Result<()> {
+  let mut message = response.to_string();
+  message.push('\n');
+  writer.write_all(message.as_bytes()).await?;
+  writer.flush().await
+}
+
+async fn handle_line(line: &str, backend: &Backend) -> Value {
+  let request: RpcRequest = match serde_json::from_str(line) {
+    Ok(request) => request,
+    Err(error) => {
+      return error_response(
+        Value::Null,
+        error_codes::PARSE_ERROR,
+        format!("Invalid JSON-RPC request: {error}"),
+      );
+    }
+  };
+
+  if request.method != EXECUTE_COMMAND_METHOD {
+    return error_response(
+      request.id,
+      error_codes::METHOD_NOT_FOUND,
+      format!("Unsupported control socket method: {}", request.method),
+    );
+  }
+
+  let params: ExecuteCommandParams = match serde_json::from_value(request.params) {
+    Ok(params) => params,
+    Err(error) => {
+      return error_response(
+        request.id,
+        error_codes::INVALID_PARAMS,
+        format!("Invalid executeCommand params: {error}"),
+      );
+    }
+  };
+
+  match backend.execute_command(params).await {
+    Ok(result) => json!(RpcSuccessResponse {
+      jsonrpc: "2.0".to_string(),
+      id: request.id,
+      result: result.unwrap_or(Value::Null),
+    }),
+    Err(error) => json!(RpcErrorResponse {
+      jsonrpc: "2.0".to_string(),
+      id: request.id,
+      error: RpcError {
+        code: i32::try_from(error.code.code()).unwrap_or(error_codes::INTERNAL_ERROR),
+        message: error.message.into_owned(),
+        data: error.data,
+      },
+    }),
+  }
+}
+
+fn error_response(id: Value, code: i32, message: String) -> Value {
+  json!(RpcErrorResponse {
+    jsonrpc: "2.0".to_string(),
+    id,
+    error: RpcError {
+      code,
+      message,
+      data: None,
+    },
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use std::os::unix::{fs::PermissionsExt, net::UnixListener};
+
+  use serde_json::{Value, json};
+  use tempfile::TempDir;
+  use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+  use tower_lsp::LspService;
+
+  use super::{ControlSocket, control_socket_path, remove_stale_socket};
+  use crate::{bridge::rpc::types::error_codes, lsp::Backend};
+
+  #[test]
+  fn control_socket_path_is_stable_per_workspace() {
+    let first = TempDir::new().expect("tempdir");
+    let second = TempDir::new().expect("tempdir");
+
+    assert_eq!(control_socket_path(first.path()), control_socket_path(first.path()));
+    assert_ne!(control_socket_path(first.path()), control_socket_path(second.path()));
+    assert!(
+      control_socket_path(first.path())
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with("novelsaga-lsp-"))
+    );
+  }
+
+  #[test]
+  fn remove_stale_socket_keeps_live_and_removes_dead_sockets() {
+    let dir = TempDir::new().expect("tempdir");
+    let path = dir.path().join("control.sock");
+
+    let listener = UnixListener::bind(&path).expect("bind");
+    let error = remove_stale_socket(&path).expect_err("live socket must be kept");
+    assert_eq!(error.kind(), std::io::ErrorKind::AddrInUse);
+    assert!(path.exists());
+
+    drop(listener);
+    remove_stale_socket(&path).expect("stale socket should be removed");
+    assert!(!path.exists());
+  }
+
+  #[tokio::test]
+  async fn serves_execute_command_requests_to_the_owner_only() {
+    let workspace = TempDir::new().expect("tempdir");
+    let (service, _socket) = LspService::new(Backend::new);
+    let backend = service.inner().clone();
+    let control = ControlSocket::bind(workspace.path(), backend.downgrade()).expect("bind control socket");
+    let mode = std::fs::metadata(control.path())
+      .expect("socket metadata")
+      .permissions()
+      .mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    let stream = tokio::net::UnixStream::connect(control.path()).await.expect("connect");
+    let (reader, mut writer) = stream.into_split();
+    let missing = workspace.path().join("missing");
+    let request = json!({
+      "jsonrpc": "2.0",
+      "id": 7,
+      "method": "workspace/executeCommand",
+      "params": { "command": "novelsaga/index", "arguments": [{ "path": missing }] },
+    });
+    let unsupported = json!({ "jsonrpc": "2.0", "id": 8, "method": "shutdown", "params": {} });
+    writer
+      .write_all(format!("{request}\n{unsupported}\n").as_bytes())
+      .await
+      .expect("write requests");
+
+    let mut lines = BufReader::new(reader).lines();
+    let response: Value =
+      serde_json::from_str(&lines.next_line().await.expect("read").expect("response")).expect("json response");
+    assert_eq!(response["id"], 7);
+    assert_eq!(response["error"]["code"], error_codes::INVALID_PARAMS);
+    assert!(
+      response["error"]["message"]
+        .as_str()
+        .is_some_and(|message| message.starts_with("Directory does not exist"))
+    );
+    let response: Value =
+      serde_json::from_str(&lines.next_line().await.expect("read").expect("response")).expect("json response");
+    assert_eq!(response["error"]["code"], error_codes::METHOD_NOT_FOUND);
+
+    drop(control);
+    assert!(!control_socket_path(workspace.path()).exists());
+  }
+}
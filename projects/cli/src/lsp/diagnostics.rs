@@ -0,0 +1,510 @@
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+  sync::{LazyLock, Mutex},
+};
+
+use novelsaga_core::{
+  config::{
+    file_def,
+    fileformat::NovelSagaFileFormat,
+    schema::{SchemaViolation, validate_config_source},
+  },
+  discovery::FileDiscovery,
+  document::{MarkdownParts, ParseSeverity},
+  metadata::MetadataError,
+};
+use tower_lsp::lsp_types::{
+  Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, Position, Range, Url,
+};
+
+/// Lint rules, sent as diagnostic codes; clients can change their severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintRule {
+  /// Malformed manuscript or metadata frontmatter
+  Frontmatter,
+  /// Config files that do not match the config schema
+  ConfigSchema,
+  /// Metadata frontmatter that does not match the schema of its type
+  MetadataSchema,
+  /// Entity ids defined by more than one metadata document
+  DuplicateId,
+}
+
+impl LintRule {
+  pub const ALL: [LintRule; 4] = [
+    LintRule::Frontmatter,
+    LintRule::ConfigSchema,
+    LintRule::MetadataSchema,
+    LintRule::DuplicateId,
+  ];
+
+  pub fn code(self) -> &'static str {
+    match self {
+      LintRule::Frontmatter => "frontmatter",
+      LintRule::ConfigSchema => "config-schema",
+      LintRule::MetadataSchema => "metadata-schema",
+      LintRule::DuplicateId => "duplicate-id",
+    }
+  }
+
+  pub fn from_code(code: &str) -> Option<Self> {
+    Self::ALL.into_iter().find(|rule| rule.code() == code)
+  }
+}
+
+/// Client-chosen severities per lint rule; `None` turns a rule off.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LintSeverities {
+  overrides: HashMap<LintRule, Option<DiagnosticSeverity>>,
+}
+
+impl LintSeverities {
+  pub fn set(&mut self, rule: LintRule, severity: Option<DiagnosticSeverity>) {
+    self.overrides.insert(rule, severity);
+  }
+
+  /// Re-level or drop the diagnostics of overridden rules. The result id
+  /// changes with the overrides, so clients refetch reports after a change.
+  pub fn apply(&self, mut report: DiagnosticsReport) -> DiagnosticsReport {
+    if self.overrides.is_empty() {
+      return report;
+    }
+
+    report.items.retain_mut(|diagnostic| {
+      let rule = match &diagnostic.code {
+        Some(NumberOrString::String(code)) => LintRule::from_code(code),
+        _ => None,
+      };
+      match rule.and_then(|rule| self.overrides.get(&rule)) {
+        Some(Some(severity)) => {
+          diagnostic.severity = Some(*severity);
+          true
+        }
+        Some(None) => false,
+        None => true,
+      }
+    });
+
+    let mut overrides: Vec<String> = self
+      .overrides
+      .iter()
+      .map(|(rule, severity)| format!("{}={severity:?}", rule.code()))
+      .collect();
+    overrides.sort();
+    report.result_id = content_result_id(&format!("{}\n{}", report.result_id, overrides.join(",")));
+    report
+  }
+}
+
+/// Diagnostics for one document together with the result id handed to pull clients.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiagnosticsReport {
+  /// Hash of the linted content; unchanged content yields the same id
+  pub result_id: String,
+  pub items: Vec<Diagnostic>,
+}
+
+/// Per-document diagnostics cache keyed by content hash.
+///
+/// Pull requests for files whose content did not change since the last lint
+/// reuse the cached diagnostics instead of linting again. Linting happens
+/// outside the cache lock: look up with [`DiagnosticsCache::get`], lint with
+/// [`lint_report`] on a miss, then [`DiagnosticsCache::insert`] the result.
+#[derive(Debug, Default)]
+pub struct DiagnosticsCache {
+  entries: HashMap<Url, DiagnosticsReport>,
+}
+
+impl DiagnosticsCache {
+  /// Cached diagnostics for `text`, unless its content hash changed since the last lint.
+  pub fn get(&self, uri: &Url, text: &str) -> Option<DiagnosticsReport> {
+    let result_id = content_result_id(text);
+    self
+      .entries
+      .get(uri)
+      .filter(|report| report.result_id == result_id)
+      .cloned()
+  }
+
+  pub fn insert(&mut self, uri: Url, report: DiagnosticsReport) {
+    self.entries.insert(uri, report);
+  }
+
+  /// Forget documents that are no longer open or part of a workspace.
+  pub fn retain(&mut self, mut keep: impl FnMut(&Url) -> bool) {
+    self.entries.retain(|uri, _| keep(uri));
+  }
+
+  pub fn remove(&mut self, uri: &Url) {
+    self.entries.remove(uri);
+  }
+
+  pub fn clear(&mut self) {
+    self.entries.clear();
+  }
+}
+
+/// Diagnostics of saved JS/TS config files, tagged with the hash of the saved content.
+static SCRIPT_CONFIG_DIAGNOSTICS: LazyLock<Mutex<HashMap<PathBuf, DiagnosticsReport>>> = LazyLock::new(Mutex::default);
+
+/// Lint a document or config file and tag the result with its content hash.
+///
+/// JS/TS config files are reported as saved, so their result id also covers
+/// the saved content.
+pub fn lint_report(uri: &Url, text: &str) -> DiagnosticsReport {
+  let mut result_id = content_result_id(text);
+  let items = match uri.to_file_path() {
+    Ok(path) if is_script_config_file(&path) => {
+      let saved = lint_saved_script_config(&path);
+      result_id = content_result_id(&format!("{result_id}\n{}", saved.result_id));
+      saved.items
+    }
+    Ok(path) if file_def::is_config_file(&path) => lint_config_file(&path, text),
+    _ => lint_document(text),
+  };
+  DiagnosticsReport { result_id, items }
+}
+
+fn is_script_config_file(path: &Path) -> bool {
+  let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+  file_def::is_config_file(path)
+    && [NovelSagaFileFormat::JavaScript, NovelSagaFileFormat::TypeScript]
+      .iter()
+      .any(|format| format.get_extensions().contains(&ext))
+}
+
+/// Validate a JS/TS config file as saved on disk.
+///
+/// Evaluating it runs the config loader, so unsaved edits are not evaluated
+/// and each saved content is evaluated once.
+fn lint_saved_script_config(path: &Path) -> DiagnosticsReport {
+  let saved = match std::fs::read_to_string(path) {
+    Ok(saved) => saved,
+    Err(error) => {
+      return DiagnosticsReport {
+        result_id: String::new(),
+        items: vec![error_diagnostic(
+          "",
+          None,
+          LintRule::ConfigSchema,
+          format!("Cannot read config file: {error}"),
+        )],
+      };
+    }
+  };
+  let result_id = content_result_id(&saved);
+  let cached = SCRIPT_CONFIG_DIAGNOSTICS
+    .lock()
+    .unwrap_or_else(std::sync::PoisonError::into_inner)
+    .get(path)
+    .filter(|report| report.result_id == result_id)
+    .cloned();
+  if let Some(report) = cached {
+    return report;
+  }
+
+  // The loader may be slow; other documents must not wait for the cache lock meanwhile
+  let report = DiagnosticsReport {
+    result_id,
+    items: lint_config_file(path, &saved),
+  };
+  SCRIPT_CONFIG_DIAGNOSTICS
+    .lock()
+    .unwrap_or_else(std::sync::PoisonError::into_inner)
+    .insert(path.to_path_buf(), report.clone());
+  report
+}
+
+/// Result id for a document's content (16 hex chars of its blake3 hash).
+pub fn content_result_id(text: &str) -> String {
+  blake3::hash(text.as_bytes()).to_hex()[..16].to_string()
+}
+
+/// Lint a manuscript or metadata document.
+pub fn lint_document(text: &str) -> Vec<Diagnostic> {
+  MarkdownParts::parse_with_issues(text)
+    .issues
+    .into_iter()
+    .map(|issue| Diagnostic {
+      range: diagnostic_range(text, issue.line),
+      severity: Some(match issue.severity {
+        ParseSeverity::Error => DiagnosticSeverity::ERROR,
+        ParseSeverity::Warning => DiagnosticSeverity::WARNING,
+      }),
+      code: Some(NumberOrString::String(LintRule::Frontmatter.code().to_string())),
+      source: Some("novelsaga".to_string()),
+      message: issue.message,
+      ..Diagnostic::default()
+    })
+    .collect()
+}
+
+/// Validate a config file against the config JSON Schema.
+///
+/// Unparsable content yields a single diagnostic on the first line.
+pub fn lint_config_file(path: &Path, text: &str) -> Vec<Diagnostic> {
+  match validate_config_source(path, text) {
+    Ok(violations) => schema_violation_diagnostics(text, violations, LintRule::ConfigSchema),
+    Err(error) => vec![error_diagnostic(
+      text,
+      None,
+      LintRule::ConfigSchema,
+      format!("Invalid config file: {error}"),
+    )],
+  }
+}
+
+/// Diagnostics for schema violations of a config file or metadata frontmatter.
+///
+/// Violations that cannot be located (e.g. a missing required key) point at the first line.
+pub fn schema_violation_diagnostics(text: &str, violations: Vec<SchemaViolation>, rule: LintRule) -> Vec<Diagnostic> {
+  violations
+    .into_iter()
+    .map(|violation| {
+      let message = if violation.key.is_empty() {
+        violation.message
+      } else {
+        format!("{}: {}", violation.key, violation.message)
+      };
+      error_diagnostic(text, violation.line, rule, message)
+    })
+    .collect()
+}
+
+/// Diagnostic for a metadata document whose type schemas cannot be loaded.
+pub fn entity_schema_error_diagnostic(text: &str, error: &MetadataError) -> Diagnostic {
+  error_diagnostic(text, None, LintRule::MetadataSchema, error.to_string())
+}
+
+fn error_diagnostic(text: &str, line: Option<usize>, rule: LintRule, message: String) -> Diagnostic {
+  Diagnostic {
+    range: diagnostic_range(text, line),
+    severity: Some(DiagnosticSeverity::ERROR),
+    code: Some(NumberOrString::String(rule.code().to_string())),
+    source: Some("novelsaga".to_string()),
+    message,
+    ..Diagnostic::default()
+  }
+}
+
+/// Diagnostic for a metadata document whose entity id is also defined by `others`.
+///
+/// Points at the frontmatter `id` line when there is one, else the first line.
+pub fn duplicate_id_diagnostic(text: &str, id: &str, others: &[PathBuf]) -> Diagnostic {
+  let id_line = text
+    .lines()
+    .skip(1)
+    .take_while(|line| line.trim() != "---")
+    .position(|line| line.trim_start().starts_with("id:"))
+    .filter(|_| text.starts_with("---"))
+    .map(|index| index + 2);
+
+  Diagnostic {
+    range: diagnostic_range(text, id_line),
+    severity: Some(DiagnosticSeverity::ERROR),
+    code: Some(NumberOrString::String(LintRule::DuplicateId.code().to_string())),
+    source: Some("novelsaga".to_string()),
+    message: format!(
+      "Duplicate entity id `{id}`: also defined by {}",
+      others
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+    ),
+    related_information: Some(
+      others
+        .iter()
+        .filter_map(|path| Url::from_file_path(path).ok())
+        .map(|uri| DiagnosticRelatedInformation {
+          location: Location {
+            uri,
+            range: Range::default(),
+          },
+          message: format!("`{id}` is also defined here"),
+        })
+        .collect(),
+    ),
+    ..Diagnostic::default()
+  }
+}
+
+/// Whether a path is a manuscript, metadata document or config file that gets diagnostics.
+pub fn is_diagnosable_path(path: &Path) -> bool {
+  file_def::is_config_file(path)
+    || path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| {
+      NovelSagaFileFormat::Markdown
+        .get_extensions()
+        .iter()
+        .any(|candidate| candidate.eq_ignore_ascii_case(ext))
+    })
+}
+
+/// Collect every manuscript, metadata document and config file of the workspace, sorted by path.
+///
+/// Hidden directories (`.git`, `.cache`, …) and ignored files are skipped.
+pub fn discover_workspace_documents(discovery: &FileDiscovery) -> Vec<PathBuf> {
+  let mut documents = discovery.files(is_diagnosable_path);
+  documents.extend(discovery.config_files());
+  documents.sort();
+  documents.dedup();
+  documents
+}
+
+fn diagnostic_range(text: &str, line: Option<usize>) -> Range {
+  let Some(line_index) = line.and_then(|line| line.checked_sub(1)) else {
+    return Range {
+      start: Position { line: 0, character: 0 },
+      end: Position { line: 0, character: 0 },
+    };
+  };
+
+  let start = Position {
+    line: u32::try_from(line_index).unwrap_or(0),
+    character: 0,
+  };
+
+  let Some(line_content) = text.lines().nth(line_index) else {
+    return Range { start, end: start };
+  };
+
+  Range {
+    start,
+    end: Position {
+      line: start.line,
+      character: u32::try_from(line_content.encode_utf16().count()).unwrap_or(0),
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use tempfile::TempDir;
+  use tower_lsp::lsp_types::{DiagnosticSeverity, Url};
+
+  use super::*;
+
+  #[test]
+  fn lint_document_reports_malformed_frontmatter_line() {
+    let diagnostics = lint_document("---\ntitle: Hero\nnot a pair\n---\nBody");
+
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+    assert_eq!(diagnostics[0].range.start.line, 2);
+    assert_eq!(diagnostics[0].range.end.character, 10);
+  }
+
+  #[test]
+  fn duplicate_id_diagnostic_points_at_frontmatter_id() {
+    let others = [PathBuf::from("/ws/book-02/metadata/characters/hero.md")];
+
+    let diagnostic = duplicate_id_diagnostic("---\ntype: character\nid: hero\n---\nBody", "hero", &others);
+    assert_eq!(diagnostic.range.start.line, 2);
+    assert!(diagnostic.message.contains("`hero`"));
+    assert!(diagnostic.message.contains("book-02"));
+    assert_eq!(diagnostic.related_information.map(|related| related.len()), Some(1));
+
+    let without_id = duplicate_id_diagnostic("---\ntype: character\n---\nid: not frontmatter", "hero", &others);
+    assert_eq!(without_id.range.start.line, 0);
+  }
+
+  #[test]
+  fn script_configs_are_linted_as_saved() {
+    let workspace = TempDir::new().expect("tempdir");
+    let path = workspace.path().join("novelsaga.config.js");
+    std::fs::write(&path, "export default { fmt: { indent: 4 } };\n").expect("write config");
+    let uri = Url::from_file_path(&path).expect("file uri");
+
+    let saved = lint_report(&uri, "export default { fmt: { indent: 4 } };\n");
+    let unsaved = lint_report(&uri, "export default { fmt: { indent_spaces: 4 } };\n");
+    assert_eq!(saved.items, unsaved.items);
+    assert_eq!(saved.items.len(), 1);
+    assert_ne!(saved.result_id, unsaved.result_id);
+
+    std::fs::write(&path, "export default { fmt: { indent_spaces: 4 } };\n").expect("write config");
+    let resaved = lint_report(&uri, "export default { fmt: { indent_spaces: 4 } };\n");
+    assert_ne!(resaved.result_id, unsaved.result_id);
+  }
+
+  #[test]
+  fn cache_reuses_result_id_for_unchanged_content() {
+    let uri = Url::parse("file:///workspace/chapter-01.md").expect("valid uri");
+    let mut cache = DiagnosticsCache::default();
+
+    let first = lint_report(&uri, "---\ntype: 1\n---\nBody");
+    cache.insert(uri.clone(), first.clone());
+    let second = cache.get(&uri, "---\ntype: 1\n---\nBody");
+    assert!(cache.get(&uri, "---\ntype: hero\n---\nBody").is_none());
+    let changed = lint_report(&uri, "---\ntype: hero\n---\nBody");
+
+    assert_eq!(Some(first.clone()), second);
+    assert_eq!(first.items.len(), 1);
+    assert_ne!(first.result_id, changed.result_id);
+    assert!(changed.items.is_empty());
+  }
+
+  #[test]
+  fn discover_workspace_documents_skips_hidden_and_ignored_files() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let root = temp_dir.path();
+    std::fs::create_dir_all(root.join("metadata/characters"))?;
+    std::fs::create_dir_all(root.join(".cache/novelsaga"))?;
+    std::fs::write(root.join("chapter-01.md"), "Body")?;
+    std::fs::write(root.join("notes.markdown"), "Notes")?;
+    std::fs::write(root.join("metadata/characters/hero.md"), "Hero")?;
+    std::fs::write(root.join(".cache/novelsaga/stale.md"), "Stale")?;
+    std::fs::write(root.join("novelsaga.config.json"), "{}")?;
+    std::fs::create_dir_all(root.join("drafts"))?;
+    std::fs::write(root.join("drafts/idea.md"), "Idea")?;
+    std::fs::write(root.join(".nsignore"), "drafts/\n")?;
+
+    let documents = discover_workspace_documents(&FileDiscovery::new(root));
+
+    assert_eq!(
+      documents,
+      vec![
+        root.join("chapter-01.md"),
+        root.join("metadata/characters/hero.md"),
+        root.join("notes.markdown"),
+        root.join("novelsaga.config.json"),
+      ]
+    );
+
+    Ok(())
+  }
+
+  #[test]
+  fn config_files_are_validated_against_the_schema() {
+    let uri = Url::parse("file:///novel/.novelsaga.yaml").expect("valid uri");
+    let report = lint_report(&uri, "fmt:\n  indent_spaces: wide\n");
+    assert_eq!(report.items.len(), 1);
+    assert_eq!(report.items[0].range.start.line, 1);
+    assert!(report.items[0].message.starts_with("fmt.indent_spaces: "));
+    assert_eq!(report.items[0].severity, Some(DiagnosticSeverity::ERROR));
+
+    assert!(lint_report(&uri, "fmt:\n  indent_spaces: 2\n").items.is_empty());
+  }
+
+  #[test]
+  fn lint_severities_relevel_or_drop_rules_and_change_the_result_id() {
+    let uri = Url::parse("file:///novel/.novelsaga.yaml").expect("valid uri");
+    let report = lint_report(&uri, "fmt:\n  indent_spaces: wide\n");
+    assert_eq!(
+      report.items[0].code,
+      Some(NumberOrString::String("config-schema".to_string()))
+    );
+
+    let mut severities = LintSeverities::default();
+    assert_eq!(severities.apply(report.clone()), report);
+
+    severities.set(LintRule::ConfigSchema, Some(DiagnosticSeverity::HINT));
+    severities.set(LintRule::DuplicateId, None);
+    let releveled = severities.apply(report.clone());
+    assert_eq!(releveled.items[0].severity, Some(DiagnosticSeverity::HINT));
+    assert_ne!(releveled.result_id, report.result_id);
+
+    severities.set(LintRule::ConfigSchema, None);
+    assert!(severities.apply(report).items.is_empty());
+  }
+}
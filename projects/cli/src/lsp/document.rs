@@ -0,0 +1,358 @@
+use novelsaga_core::{
+  article::ArticleDocument,
+  document::{DocumentError, DocumentKind, WorkspaceDocument},
+};
+use ropey::Rope;
+use tower_lsp::lsp_types::{Position, TextDocumentContentChangeEvent};
+
+/// In-memory state of an open text document.
+///
+/// The text is kept in a rope so incremental `didChange` edits are applied in
+/// `O(log n)` instead of copying the whole chapter on every keystroke.
+#[derive(Debug, Clone)]
+pub struct DocumentState {
+  pub version: i32,
+  pub rope: Rope,
+  pub kind: DocumentKind,
+  pub parsed: Result<WorkspaceDocument, DocumentError>,
+  pub disk_changed: bool,
+  /// 0-based line of the closing frontmatter delimiter as of the last parse
+  frontmatter_end_line: Option<usize>,
+  /// Lowest 0-based line touched by edits since the last parse
+  dirty_from_line: Option<usize>,
+}
+
+impl DocumentState {
+  pub fn new(version: i32, text: &str, kind: DocumentKind) -> Self {
+    Self {
+      version,
+      rope: Rope::from_str(text),
+      kind,
+      parsed: Err(DocumentError::NotParsed),
+      disk_changed: false,
+      frontmatter_end_line: None,
+      dirty_from_line: Some(0),
+    }
+  }
+
+  /// Full document text.
+  pub fn text(&self) -> String {
+    self.rope.to_string()
+  }
+
+  /// Apply `didChange` content changes in order and bump the version.
+  ///
+  /// A change without a range replaces the whole document.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error when a change range lies outside the document. The
+  /// changes are applied to a copy first, so on error the document is unchanged.
+  pub fn apply_changes(&mut self, version: i32, changes: Vec<TextDocumentContentChangeEvent>) -> Result<(), String> {
+    // Cloning a rope shares its nodes, so the scratch copy is cheap
+    let mut rope = self.rope.clone();
+    let mut dirty_from_line = self.dirty_from_line;
+    let mut mark_dirty = |line: usize| dirty_from_line = Some(dirty_from_line.map_or(line, |dirty| dirty.min(line)));
+
+    for change in changes {
+      let Some(range) = change.range else {
+        rope = Rope::from_str(&change.text);
+        mark_dirty(0);
+        continue;
+      };
+
+      let start = Self::char_index(&rope, range.start)
+        .ok_or_else(|| format!("Change start {:?} is outside the document", range.start))?;
+      let end = Self::char_index(&rope, range.end)
+        .ok_or_else(|| format!("Change end {:?} is outside the document", range.end))?;
+      if end < start {
+        return Err(format!(
+          "Change range end {:?} precedes start {:?}",
+          range.end, range.start
+        ));
+      }
+
+      rope.remove(start..end);
+      rope.insert(start, &change.text);
+      mark_dirty(range.start.line as usize);
+    }
+
+    self.rope = rope;
+    self.dirty_from_line = dirty_from_line;
+    self.version = version;
+    self.disk_changed = false;
+    Ok(())
+  }
+
+  /// Update the parsed document after body-only edits without parsing the
+  /// frontmatter again.
+  ///
+  /// Returns `false` when a full parse is needed: the frontmatter may have
+  /// changed, or there is no successful parse to update.
+  pub fn reparse_body(&mut self) -> bool {
+    let Some(end) = self.frontmatter_end_line.filter(|_| !self.frontmatter_affected()) else {
+      return false;
+    };
+    if self.parsed.is_err() {
+      return false;
+    }
+
+    let body_start = self.rope.line_to_char((end + 1).min(self.rope.len_lines()));
+    let body = self
+      .rope
+      .slice(body_start..)
+      .to_string()
+      .lines()
+      .collect::<Vec<_>>()
+      .join("\n");
+    match &mut self.parsed {
+      Ok(WorkspaceDocument::Metadata(entity)) => entity.body = body,
+      Ok(WorkspaceDocument::Article(article)) => *article = ArticleDocument::new(article.frontmatter.clone(), body),
+      Err(_) => return false,
+    }
+    self.dirty_from_line = None;
+    true
+  }
+
+  /// Store a fresh parse result and reset the dirty region.
+  pub fn mark_parsed(&mut self, parsed: Result<WorkspaceDocument, DocumentError>) {
+    self.parsed = parsed;
+    self.frontmatter_end_line = self.find_frontmatter_end_line();
+    self.dirty_from_line = None;
+  }
+
+  /// Whether edits since the last parse may have changed the frontmatter.
+  ///
+  /// Frontmatter diagnostics only depend on the lines up to the closing
+  /// delimiter, so body-only edits can skip recomputing them.
+  pub fn frontmatter_affected(&self) -> bool {
+    match (self.dirty_from_line, self.frontmatter_end_line) {
+      (None, _) => false,
+      (Some(_), None) => true,
+      (Some(dirty), Some(end)) => dirty <= end,
+    }
+  }
+
+  /// Whether `line` lies between the frontmatter delimiters.
+  ///
+  /// Uses the delimiter found at the last parse; only pending edits that may
+  /// have moved it rescan the frontmatter.
+  pub fn in_frontmatter(&self, line: usize) -> bool {
+    let end = if self.frontmatter_affected() {
+      self.find_frontmatter_end_line()
+    } else {
+      self.frontmatter_end_line
+    };
+    line > 0 && end.is_some_and(|end| line < end)
+  }
+
+  /// Convert an LSP position (UTF-16 code units) to a char index in the rope.
+  pub fn position_to_char(&self, position: Position) -> Option<usize> {
+    Self::char_index(&self.rope, position)
+  }
+
+  fn char_index(rope: &Rope, position: Position) -> Option<usize> {
+    let line_index = position.line as usize;
+    if line_index >= rope.len_lines() {
+      return None;
+    }
+
+    let line = rope.line(line_index);
+    let line_len_utf16 = line.len_utf16_cu() - Self::line_ending_len(&line);
+    let character = position.character as usize;
+    if character > line_len_utf16 {
+      return None;
+    }
+
+    Some(rope.line_to_char(line_index) + line.utf16_cu_to_char(character))
+  }
+
+  /// Text of the given line up to (not including) the position.
+  pub fn line_prefix(&self, position: Position) -> Option<String> {
+    let char_index = self.position_to_char(position)?;
+    let line_start = self.rope.line_to_char(position.line as usize);
+    Some(self.rope.slice(line_start..char_index).to_string())
+  }
+
+  fn line_ending_len(line: &ropey::RopeSlice<'_>) -> usize {
+    let len = line.len_chars();
+    let last = (len > 0).then(|| line.char(len - 1));
+    let before_last = (len > 1).then(|| line.char(len - 2));
+    match (last, before_last) {
+      (Some('\n'), Some('\r')) => 2,
+      (Some('\n' | '\r'), _) => 1,
+      _ => 0,
+    }
+  }
+
+  fn find_frontmatter_end_line(&self) -> Option<usize> {
+    let mut lines = self.rope.lines();
+    if !lines.next()?.to_string().trim().starts_with("---") {
+      return None;
+    }
+
+    lines
+      .position(|line| line.to_string().trim().starts_with("---"))
+      .map(|index| index + 1)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use novelsaga_core::{
+    article::ArticleDocument,
+    document::{DocumentError, DocumentKind, MarkdownParts, WorkspaceDocument},
+  };
+  use tower_lsp::lsp_types::{Position, Range, TextDocumentContentChangeEvent};
+
+  use super::DocumentState;
+
+  fn edit(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+    TextDocumentContentChangeEvent {
+      range: Some(Range {
+        start: Position {
+          line: start.0,
+          character: start.1,
+        },
+        end: Position {
+          line: end.0,
+          character: end.1,
+        },
+      }),
+      range_length: None,
+      text: text.to_string(),
+    }
+  }
+
+  #[test]
+  fn apply_changes_applies_ranged_edits_in_order() {
+    let mut state = DocumentState::new(1, "Hello\nWorld", DocumentKind::Article);
+
+    state
+      .apply_changes(2, vec![edit((0, 5), (0, 5), ", there"), edit((1, 0), (1, 5), "世界")])
+      .expect("edits should apply");
+
+    assert_eq!(state.version, 2);
+    assert_eq!(state.text(), "Hello, there\n世界");
+  }
+
+  #[test]
+  fn apply_changes_uses_utf16_columns() {
+    let mut state = DocumentState::new(1, "😀a\r\nb", DocumentKind::Article);
+
+    state
+      .apply_changes(2, vec![edit((0, 2), (0, 3), "z"), edit((1, 1), (1, 1), "c")])
+      .expect("edits should apply");
+
+    assert_eq!(state.text(), "😀z\r\nbc");
+  }
+
+  #[test]
+  fn apply_changes_replaces_whole_document_without_range() {
+    let mut state = DocumentState::new(1, "old", DocumentKind::Article);
+
+    state
+      .apply_changes(
+        2,
+        vec![TextDocumentContentChangeEvent {
+          range: None,
+          range_length: None,
+          text: "new text".to_string(),
+        }],
+      )
+      .expect("full replacement should apply");
+
+    assert_eq!(state.text(), "new text");
+  }
+
+  #[test]
+  fn apply_changes_rejects_out_of_range_positions() {
+    let mut state = DocumentState::new(1, "Hello", DocumentKind::Article);
+
+    let result = state.apply_changes(2, vec![edit((0, 6), (0, 6), "!")]);
+
+    assert!(result.is_err());
+    assert_eq!(state.text(), "Hello");
+
+    // Earlier changes of a failing batch are not applied either
+    let result = state.apply_changes(3, vec![edit((0, 5), (0, 5), "!"), edit((2, 0), (2, 0), "?")]);
+    assert!(result.is_err());
+    assert_eq!(state.text(), "Hello");
+    assert_eq!(state.version, 1);
+  }
+
+  #[test]
+  fn reparse_body_updates_the_body_after_body_only_edits() {
+    let text = "---\ntitle: Hero\n---\nOne two\n";
+    let mut state = DocumentState::new(1, text, DocumentKind::Article);
+    let parts = MarkdownParts::parse(text);
+    state.mark_parsed(Ok(WorkspaceDocument::Article(ArticleDocument::new(
+      parts.frontmatter,
+      parts.body,
+    ))));
+
+    state
+      .apply_changes(2, vec![edit((3, 7), (3, 7), " three")])
+      .expect("body edit should apply");
+    assert!(state.reparse_body());
+    let Ok(WorkspaceDocument::Article(article)) = &state.parsed else {
+      panic!("article stays parsed");
+    };
+    assert_eq!(article.body, "One two three");
+    assert_eq!(article.title.as_deref(), Some("Hero"));
+    assert_eq!(article.word_count, 3);
+
+    state
+      .apply_changes(3, vec![edit((1, 7), (1, 11), "Villain")])
+      .expect("frontmatter edit should apply");
+    assert!(!state.reparse_body());
+  }
+
+  #[test]
+  fn frontmatter_affected_skips_body_only_edits() {
+    let mut state = DocumentState::new(1, "---\ntitle: Hero\n---\nBody", DocumentKind::Article);
+    state.mark_parsed(Err(DocumentError::NotParsed));
+    assert!(!state.frontmatter_affected());
+
+    state
+      .apply_changes(2, vec![edit((3, 4), (3, 4), " text")])
+      .expect("body edit should apply");
+    assert!(!state.frontmatter_affected());
+
+    state
+      .apply_changes(3, vec![edit((1, 7), (1, 11), "Villain")])
+      .expect("frontmatter edit should apply");
+    assert!(state.frontmatter_affected());
+  }
+
+  #[test]
+  fn in_frontmatter_follows_pending_frontmatter_edits() {
+    let mut state = DocumentState::new(1, "---\ntitle: Hero\n---\nBody", DocumentKind::Article);
+    state.mark_parsed(Err(DocumentError::NotParsed));
+    assert!(state.in_frontmatter(1));
+    assert!(!state.in_frontmatter(2));
+
+    state
+      .apply_changes(2, vec![edit((3, 4), (3, 4), "\nmore")])
+      .expect("body edit should apply");
+    assert!(state.in_frontmatter(1));
+    assert!(!state.in_frontmatter(3));
+
+    state
+      .apply_changes(3, vec![edit((1, 11), (1, 11), "\ntype: character")])
+      .expect("frontmatter edit should apply");
+    assert!(state.in_frontmatter(2));
+    assert!(!state.in_frontmatter(3));
+  }
+
+  #[test]
+  fn line_prefix_returns_text_before_cursor() {
+    let state = DocumentState::new(1, "first\n前缀英雄", DocumentKind::Article);
+
+    assert_eq!(
+      state.line_prefix(Position { line: 1, character: 2 }).as_deref(),
+      Some("前缀")
+    );
+    assert_eq!(state.line_prefix(Position { line: 2, character: 0 }), None);
+  }
+}
@@ -1,33 +1,234 @@
 mod backend;
 mod completion;
+mod config_completion;
+mod config_hover;
+mod control;
+mod diagnostics;
+mod document;
 mod position;
+mod progress;
+mod settings;
+mod workspace;
+
+use std::{io, net::SocketAddr, path::Path};
 
 pub use backend::Backend;
 #[allow(unused_imports)]
 pub use completion::{build_completion_candidates, extract_active_prefix};
+pub use control::find_live_control_socket;
+pub use document::DocumentState;
 #[allow(unused_imports)]
 pub use position::{offset_to_position, position_to_offset};
+use tokio::{
+  io::{AsyncRead, AsyncWrite},
+  net::{TcpListener, UnixListener},
+  signal::unix::{SignalKind, signal},
+};
 use tower_lsp::LspService;
 
+use crate::args::LspListen;
+
 /// 启动 LSP 服务器
-pub async fn start() {
+///
+/// `allow_remote` 允许 TCP 监听非回环地址（客户端无需认证）
+pub async fn start(listen: LspListen, allow_remote: bool) {
   eprintln!("NovelSaga LSP Server starting...");
 
+  let result = match listen {
+    LspListen::Stdio => {
+      serve_stdio().await;
+      Ok(())
+    }
+    LspListen::Tcp(addr) => serve_tcp(addr, allow_remote).await,
+    LspListen::Unix(path) => serve_unix(&path).await,
+  };
+
+  if let Err(error) = result {
+    eprintln!("LSP server failed: {error}");
+    std::process::exit(1);
+  }
+
+  // LSP exit notification received - exit cleanly
+  eprintln!("NovelSaga LSP Server exiting...");
+  std::process::exit(0);
+}
+
+async fn serve_stdio() {
   // Get stdin/stdout for LSP communication
   let stdin = tokio::io::stdin();
   let stdout = tokio::io::stdout();
 
-  // Create the LSP service
-  let (service, socket) = LspService::new(Backend::new);
-
   eprintln!("Starting LSP server...");
+  serve(stdin, stdout).await;
+  eprintln!("LSP server finished");
+}
 
-  // Run the server
-  tower_lsp::Server::new(stdin, stdout, socket).serve(service).await;
+/// Serve one client over an arbitrary byte stream with its own `Backend`.
+///
+/// The backend is closed when the connection ends, releasing its indexes and
+/// control sockets for the next client of the same workspace.
+async fn serve<I, O>(input: I, output: O)
+where
+  I: AsyncRead + Unpin,
+  O: AsyncWrite,
+{
+  let (service, socket) = LspService::build(Backend::new)
+    .custom_method("window/workDoneProgress/cancel", Backend::work_done_progress_cancel)
+    .finish();
+  let backend = service.inner().clone();
+  tower_lsp::Server::new(input, output, socket).serve(service).await;
+  backend.close().await;
+}
 
-  eprintln!("LSP server finished");
+/// Serve clients on `addr` until interrupted.
+///
+/// Clients are not authenticated and can index or read any path through
+/// `workspace/executeCommand`, so non-loopback addresses need `allow_remote`.
+async fn serve_tcp(addr: SocketAddr, allow_remote: bool) -> io::Result<()> {
+  if !addr.ip().is_loopback() {
+    if !allow_remote {
+      return Err(io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("refusing to listen on non-loopback address {addr}; pass --allow-remote to allow it"),
+      ));
+    }
+    eprintln!(
+      "WARNING: listening on non-loopback address {addr}; anyone who can reach it can read and index files on this machine"
+    );
+  }
+  let listener = TcpListener::bind(addr).await?;
+  eprintln!("Listening on tcp://{}", listener.local_addr()?);
 
-  // LSP exit notification received - exit cleanly
-  eprintln!("NovelSaga LSP Server exiting...");
-  std::process::exit(0);
+  tokio::select! {
+    result = accept_tcp(listener) => result,
+    result = shutdown_signal() => result,
+  }
+}
+
+async fn accept_tcp(listener: TcpListener) -> io::Result<()> {
+  loop {
+    let (stream, peer) = listener.accept().await?;
+    eprintln!("LSP client connected: {peer}");
+    tokio::spawn(async move {
+      let (read, write) = stream.into_split();
+      serve(read, write).await;
+      eprintln!("LSP client disconnected: {peer}");
+    });
+  }
+}
+
+/// Serve clients on the unix socket at `path` until interrupted; the socket
+/// file is removed on the way out.
+async fn serve_unix(path: &Path) -> io::Result<()> {
+  control::remove_stale_socket(path)?;
+  let listener = UnixListener::bind(path)?;
+  eprintln!("Listening on unix:{}", path.display());
+
+  let result = tokio::select! {
+    result = accept_unix(listener) => result,
+    result = shutdown_signal() => result,
+  };
+  let _ = std::fs::remove_file(path);
+  result
+}
+
+async fn accept_unix(listener: UnixListener) -> io::Result<()> {
+  loop {
+    let (stream, _) = listener.accept().await?;
+    eprintln!("LSP client connected");
+    tokio::spawn(async move {
+      let (read, write) = stream.into_split();
+      serve(read, write).await;
+      eprintln!("LSP client disconnected");
+    });
+  }
+}
+
+/// Resolves on SIGINT or SIGTERM.
+async fn shutdown_signal() -> io::Result<()> {
+  let mut terminate = signal(SignalKind::terminate())?;
+  tokio::select! {
+    result = tokio::signal::ctrl_c() => result,
+    _ = terminate.recv() => Ok(()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::{Value, json};
+  use tempfile::TempDir;
+  use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+  use tower_lsp::lsp_types::Url;
+
+  use super::{find_live_control_socket, serve, serve_tcp};
+  use crate::{config::loader::SharedRuntimeOverride, lsp::workspace::WorkspaceFolderState};
+
+  async fn send(writer: &mut (impl AsyncWriteExt + Unpin), message: &Value) {
+    let body = message.to_string();
+    let framed = format!("Content-Length: {}\r\n\r\n{body}", body.len());
+    writer.write_all(framed.as_bytes()).await.expect("write message");
+  }
+
+  async fn receive(reader: &mut (impl AsyncBufReadExt + Unpin)) -> Value {
+    let mut length = 0;
+    loop {
+      let mut header = String::new();
+      reader.read_line(&mut header).await.expect("read header");
+      let header = header.trim();
+      if header.is_empty() {
+        break;
+      }
+      if let Some(value) = header.strip_prefix("Content-Length: ") {
+        length = value.parse().expect("content length");
+      }
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).await.expect("read body");
+    serde_json::from_slice(&body).expect("json message")
+  }
+
+  #[tokio::test]
+  async fn sequential_connections_serve_the_same_workspace() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path().canonicalize().expect("canonical root");
+    let root_uri = Url::from_file_path(&root).expect("root uri");
+
+    for _ in 0..2 {
+      let (client, server) = tokio::io::duplex(1 << 16);
+      let (server_read, server_write) = tokio::io::split(server);
+      let serving = tokio::spawn(serve(server_read, server_write));
+      let (client_read, mut client_write) = tokio::io::split(client);
+      let mut reader = BufReader::new(client_read);
+
+      let initialize = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "initialize",
+        "params": {
+          "capabilities": {},
+          "workspaceFolders": [{ "uri": root_uri, "name": "book" }],
+        },
+      });
+      send(&mut client_write, &initialize).await;
+      while receive(&mut reader).await["id"] != 1 {}
+      assert!(find_live_control_socket(&root).is_some());
+
+      drop(client_write);
+      drop(reader);
+      serving.await.expect("server task");
+
+      assert!(find_live_control_socket(&root).is_none());
+      let folder = WorkspaceFolderState::open("book", root.clone(), &SharedRuntimeOverride::default());
+      assert!(folder.index_manager.is_some(), "index lock released");
+      drop(folder);
+    }
+  }
+
+  #[tokio::test]
+  async fn tcp_refuses_non_loopback_addresses_without_opt_in() {
+    let error = serve_tcp("0.0.0.0:0".parse().expect("address"), false)
+      .await
+      .expect_err("non-loopback address refused");
+    assert_eq!(error.kind(), std::io::ErrorKind::PermissionDenied);
+  }
 }
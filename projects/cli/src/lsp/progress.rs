@@ -0,0 +1,145 @@
+use std::sync::{
+  Arc,
+  atomic::{AtomicBool, Ordering},
+};
+
+use tower_lsp::{
+  Client,
+  lsp_types::{
+    ProgressParams, ProgressParamsValue, ProgressToken, WorkDoneProgress, WorkDoneProgressBegin, WorkDoneProgressEnd,
+    WorkDoneProgressReport, notification::Progress,
+  },
+};
+
+/// Cancellation flag shared between a request handler and its background work.
+#[derive(Debug, Clone, Default)]
+pub struct CancelFlag(Arc<AtomicBool>);
+
+impl CancelFlag {
+  pub fn cancel(&self) {
+    self.0.store(true, Ordering::Release);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.0.load(Ordering::Acquire)
+  }
+
+  /// Guard that cancels the flag when dropped.
+  ///
+  /// `tower-lsp` handles `$/cancelRequest` by dropping the request future, so
+  /// holding the guard in the handler turns that drop into a cancellation the
+  /// background work can observe.
+  pub fn cancel_on_drop(&self) -> CancelOnDrop {
+    CancelOnDrop(self.clone())
+  }
+}
+
+#[derive(Debug)]
+pub struct CancelOnDrop(CancelFlag);
+
+impl Drop for CancelOnDrop {
+  fn drop(&mut self) {
+    self.0.cancel();
+  }
+}
+
+/// Sends `$/progress` work-done notifications for one token.
+///
+/// Without a token (client did not ask for progress) every call is a no-op.
+/// Reports are only sent when the percentage changes.
+#[derive(Debug)]
+pub struct WorkDoneProgressReporter {
+  client: Client,
+  token: Option<ProgressToken>,
+  last_percentage: Option<u32>,
+}
+
+impl WorkDoneProgressReporter {
+  pub fn new(client: Client, token: Option<ProgressToken>) -> Self {
+    Self {
+      client,
+      token,
+      last_percentage: None,
+    }
+  }
+
+  pub async fn begin(&self, title: &str, message: Option<String>) {
+    self
+      .send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+        title: title.to_string(),
+        cancellable: Some(true),
+        message,
+        percentage: Some(0),
+      }))
+      .await;
+  }
+
+  pub async fn report(&mut self, done: usize, total: usize, message: String) {
+    let percentage = percentage(done, total);
+    if self.last_percentage == Some(percentage) {
+      return;
+    }
+    self.last_percentage = Some(percentage);
+
+    self
+      .send(WorkDoneProgress::Report(WorkDoneProgressReport {
+        cancellable: Some(true),
+        message: Some(message),
+        percentage: Some(percentage),
+      }))
+      .await;
+  }
+
+  pub async fn end(&self, message: String) {
+    self
+      .send(WorkDoneProgress::End(WorkDoneProgressEnd { message: Some(message) }))
+      .await;
+  }
+
+  async fn send(&self, value: WorkDoneProgress) {
+    let Some(token) = self.token.clone() else {
+      return;
+    };
+
+    self
+      .client
+      .send_notification::<Progress>(ProgressParams {
+        token,
+        value: ProgressParamsValue::WorkDone(value),
+      })
+      .await;
+  }
+}
+
+fn percentage(done: usize, total: usize) -> u32 {
+  if total == 0 {
+    return 100;
+  }
+  u32::try_from(done.min(total) * 100 / total).unwrap_or(100)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{CancelFlag, percentage};
+
+  #[test]
+  fn percentage_is_clamped_and_handles_empty_work() {
+    assert_eq!(percentage(0, 0), 100);
+    assert_eq!(percentage(0, 3), 0);
+    assert_eq!(percentage(1, 3), 33);
+    assert_eq!(percentage(5, 3), 100);
+  }
+
+  #[test]
+  fn cancel_on_drop_cancels_shared_flag() {
+    let flag = CancelFlag::default();
+    let observer = flag.clone();
+
+    {
+      let _guard = flag.cancel_on_drop();
+      assert!(!observer.is_cancelled());
+    }
+
+    assert!(observer.is_cancelled());
+  }
+}
@@ -0,0 +1,285 @@
+//! Editor-provided settings from the `novelsaga` configuration section.
+//!
+//! Settings are layered over the file-based config. Precedence, lowest first:
+//!
+//! 1. built-in defaults (and the `--runtime`/`--*-path` command line options)
+//! 2. config files: root config → directory cascade → document frontmatter
+//! 3. `initializationOptions` (either `{ "novelsaga": { … } }` or the section itself)
+//! 4. `workspace/didChangeConfiguration` settings and `workspace/configuration` replies
+//!
+//! The section holds keys of [`OverridableConfig`] (e.g. `fmt.indent_spaces`)
+//! plus editor-only settings:
+//!
+//! - `runtime`: JS runtime for script configs (`auto`, `node`, `bun`, `deno`),
+//!   with `runtime_path` as its executable
+//! - `lint.severity.<rule>`: `error`, `warning`, `information`, `hint` or `off`
+//!   for a lint rule (`frontmatter`, `config-schema`, `metadata-schema`,
+//!   `duplicate-id`)
+//!
+//! Unset keys keep the value from the lower layers.
+
+use std::path::PathBuf;
+
+use novelsaga_core::config::OverridableConfig;
+use serde_json::{Map, Value};
+use tower_lsp::lsp_types::DiagnosticSeverity;
+
+use crate::{
+  args::RuntimeChoice,
+  config::loader::RuntimeOverride,
+  lsp::diagnostics::{LintRule, LintSeverities},
+};
+
+/// Name of the client configuration section read by the server.
+pub const SETTINGS_SECTION: &str = "novelsaga";
+
+/// Settings of the section that are not config keys.
+const EDITOR_SETTINGS: [&str; 3] = ["runtime", "runtime_path", "lint"];
+
+/// Client settings from `initializationOptions` and workspace configuration.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientSettings {
+  initialization: Option<Value>,
+  configuration: Option<Value>,
+}
+
+impl ClientSettings {
+  pub fn from_initialization_options(options: Option<&Value>) -> Self {
+    Self {
+      initialization: options.and_then(section_of).cloned(),
+      configuration: None,
+    }
+  }
+
+  /// Replace the settings received via `workspace/didChangeConfiguration` or
+  /// `workspace/configuration`. `null` clears them.
+  pub fn set_configuration(&mut self, section: Option<Value>) {
+    self.configuration = section.filter(Value::is_object);
+  }
+
+  /// Layer the client settings over a config resolved from files.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error when a setting has the wrong type for its config key.
+  pub fn apply(&self, config: &OverridableConfig) -> Result<OverridableConfig, serde_json::Error> {
+    if self.initialization.is_none() && self.configuration.is_none() {
+      return Ok(config.clone());
+    }
+
+    let mut merged = serde_json::to_value(config)?;
+    for layer in self.layers() {
+      let config_keys: Map<String, Value> = layer
+        .iter()
+        .filter(|(key, _)| !EDITOR_SETTINGS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+      merge_json(&mut merged, &Value::Object(config_keys));
+    }
+    serde_json::from_value(merged)
+  }
+
+  /// JS runtime chosen in the editor, `None` to keep the command line choice.
+  ///
+  /// `runtime_path` is read from the same layer as `runtime`, so it never
+  /// points a newly chosen runtime at another runtime's executable.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error when `runtime` or `runtime_path` is not a valid value.
+  pub fn runtime(&self) -> Result<Option<RuntimeOverride>, String> {
+    let Some(layer) = self.layers().filter(|layer| layer.contains_key("runtime")).last() else {
+      return Ok(None);
+    };
+    let choice = &layer["runtime"];
+    let choice = choice
+      .as_str()
+      .ok_or_else(|| format!("`runtime` must be a string, got {choice}"))?
+      .parse::<RuntimeChoice>()?;
+    let path = match layer.get("runtime_path") {
+      None | Some(Value::Null) => None,
+      Some(Value::String(path)) => Some(PathBuf::from(path)),
+      Some(other) => return Err(format!("`runtime_path` must be a string, got {other}")),
+    };
+    Ok(Some(RuntimeOverride { choice, path }))
+  }
+
+  /// Severities of lint rules chosen in the editor, later layers winning per rule.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error naming the first unknown rule or severity.
+  pub fn lint_severities(&self) -> Result<LintSeverities, String> {
+    let mut severities = LintSeverities::default();
+    for layer in self.layers() {
+      let Some(rules) = layer.get("lint").and_then(|lint| lint.get("severity")) else {
+        continue;
+      };
+      let rules = rules
+        .as_object()
+        .ok_or_else(|| format!("`lint.severity` must be an object, got {rules}"))?;
+      for (code, severity) in rules {
+        let rule = LintRule::from_code(code).ok_or_else(|| format!("Unknown lint rule `{code}`"))?;
+        severities.set(rule, parse_severity(severity)?);
+      }
+    }
+    Ok(severities)
+  }
+
+  /// Sections from lowest to highest precedence.
+  fn layers(&self) -> impl Iterator<Item = &Map<String, Value>> {
+    [&self.initialization, &self.configuration]
+      .into_iter()
+      .flatten()
+      .filter_map(Value::as_object)
+  }
+}
+
+fn parse_severity(severity: &Value) -> Result<Option<DiagnosticSeverity>, String> {
+  match severity.as_str() {
+    Some("error") => Ok(Some(DiagnosticSeverity::ERROR)),
+    Some("warning") => Ok(Some(DiagnosticSeverity::WARNING)),
+    Some("information") => Ok(Some(DiagnosticSeverity::INFORMATION)),
+    Some("hint") => Ok(Some(DiagnosticSeverity::HINT)),
+    Some("off") => Ok(None),
+    _ => Err(format!(
+      "Invalid lint severity {severity}: expected error, warning, information, hint or off"
+    )),
+  }
+}
+
+/// The `novelsaga` section of a settings object, or the object itself when it
+/// has no such key and only holds settings keys (clients may send the section
+/// unwrapped).
+pub fn section_of(settings: &Value) -> Option<&Value> {
+  let object = settings.as_object()?;
+  match object.get(SETTINGS_SECTION) {
+    Some(section) => section.is_object().then_some(section),
+    None => object.keys().all(|key| is_settings_key(key)).then_some(settings),
+  }
+}
+
+/// Whether `key` is a top-level key of the `novelsaga` section.
+fn is_settings_key(key: &str) -> bool {
+  EDITOR_SETTINGS.contains(&key)
+    || serde_json::to_value(OverridableConfig::default())
+      .ok()
+      .is_some_and(|config| config.get(key).is_some())
+}
+
+/// Deep-merge `overlay` into `base`; objects merge per key, other values replace.
+fn merge_json(base: &mut Value, overlay: &Value) {
+  match (base, overlay) {
+    (Value::Object(base), Value::Object(overlay)) => {
+      for (key, value) in overlay {
+        match base.get_mut(key) {
+          Some(existing) => merge_json(existing, value),
+          None => {
+            base.insert(key.clone(), value.clone());
+          }
+        }
+      }
+    }
+    (base, overlay) => *base = overlay.clone(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use novelsaga_core::config::OverridableConfig;
+  use serde_json::json;
+  use tower_lsp::lsp_types::DiagnosticSeverity;
+
+  use super::{ClientSettings, section_of};
+  use crate::{
+    args::RuntimeChoice,
+    lsp::diagnostics::{LintRule, LintSeverities},
+  };
+
+  #[test]
+  fn apply_layers_configuration_over_initialization_and_files() {
+    let mut file_config = OverridableConfig::default();
+    file_config.fmt.indent_spaces = 2;
+    file_config.fmt.blank_lines_between_paragraphs = 3;
+
+    let mut settings = ClientSettings::from_initialization_options(Some(&json!({
+      "novelsaga": { "fmt": { "indent_spaces": 4 } }
+    })));
+    let applied = settings.apply(&file_config).expect("settings should apply");
+    assert_eq!(applied.fmt.indent_spaces, 4);
+    assert_eq!(applied.fmt.blank_lines_between_paragraphs, 3);
+
+    settings.set_configuration(Some(json!({ "fmt": { "indent_spaces": 0 } })));
+    let applied = settings.apply(&file_config).expect("settings should apply");
+    assert_eq!(applied.fmt.indent_spaces, 0);
+
+    settings.set_configuration(None);
+    let applied = settings.apply(&file_config).expect("settings should apply");
+    assert_eq!(applied.fmt.indent_spaces, 4);
+  }
+
+  #[test]
+  fn apply_rejects_mistyped_settings() {
+    let mut settings = ClientSettings::default();
+    settings.set_configuration(Some(json!({ "fmt": { "indent_spaces": "two" } })));
+
+    assert!(settings.apply(&OverridableConfig::default()).is_err());
+  }
+
+  #[test]
+  fn section_of_accepts_wrapped_and_bare_sections() {
+    let wrapped = json!({ "novelsaga": { "fmt": {} } });
+    let bare = json!({ "fmt": {} });
+
+    assert_eq!(section_of(&wrapped), Some(&json!({ "fmt": {} })));
+    assert_eq!(section_of(&bare), Some(&bare));
+    assert_eq!(section_of(&json!(null)), None);
+
+    let editor_only = json!({ "runtime": "bun", "lint": {} });
+    assert_eq!(section_of(&editor_only), Some(&editor_only));
+    assert_eq!(section_of(&json!({ "fmt": {}, "editor": { "tabSize": 2 } })), None);
+  }
+
+  #[test]
+  fn runtime_and_lint_severities_come_from_the_highest_layer() {
+    let mut settings = ClientSettings::from_initialization_options(Some(&json!({
+      "novelsaga": {
+        "fmt": { "indent_spaces": 4 },
+        "runtime": "node",
+        "runtime_path": "/opt/node/bin/node",
+        "lint": { "severity": { "duplicate-id": "warning", "frontmatter": "off" } }
+      }
+    })));
+    assert_eq!(
+      settings
+        .apply(&OverridableConfig::default())
+        .expect("settings should apply")
+        .fmt
+        .indent_spaces,
+      4
+    );
+    let runtime = settings.runtime().expect("valid runtime").expect("runtime set");
+    assert_eq!(runtime.choice, RuntimeChoice::Node);
+    assert_eq!(
+      runtime.path.as_deref(),
+      Some(std::path::Path::new("/opt/node/bin/node"))
+    );
+
+    settings.set_configuration(Some(json!({
+      "runtime": "bun",
+      "lint": { "severity": { "duplicate-id": "hint" } }
+    })));
+    let runtime = settings.runtime().expect("valid runtime").expect("runtime set");
+    assert_eq!((runtime.choice, runtime.path), (RuntimeChoice::Bun, None));
+    let mut expected = LintSeverities::default();
+    expected.set(LintRule::DuplicateId, Some(DiagnosticSeverity::HINT));
+    expected.set(LintRule::Frontmatter, None);
+    assert_eq!(settings.lint_severities(), Ok(expected));
+
+    settings.set_configuration(Some(
+      json!({ "runtime": "rhino", "lint": { "severity": { "typos": "error" } } }),
+    ));
+    assert!(settings.runtime().is_err());
+    assert!(settings.lint_severities().is_err());
+  }
+}
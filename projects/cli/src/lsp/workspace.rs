@@ -0,0 +1,325 @@
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+  sync::{Arc, LazyLock, Mutex, Weak},
+  time::Duration,
+};
+
+use novelsaga_core::{
+  config::metadata::MetadataConfig,
+  discovery::FileDiscovery,
+  metadata::{EntitySchemas, MetadataError, metadata_dir_of},
+  state::{ConfigManager, init::Initializer},
+};
+
+use crate::{
+  config::loader::{SharedRuntimeOverride, with_runtime_override},
+  metadata::{
+    IndexManager, pipeline,
+    resolver::{MetadataResolver, ResolutionContext},
+  },
+};
+
+/// How often opening an index locked by the previous connection is retried
+const INDEX_LOCK_RETRIES: u32 = 20;
+const INDEX_LOCK_RETRY_DELAY: Duration = Duration::from_millis(25);
+
+/// Indexes open in this process by path. Folders under one root config share
+/// its cache dir, and sled allows a single handle per database.
+static OPEN_INDEXES: LazyLock<Mutex<HashMap<PathBuf, Weak<IndexManager>>>> = LazyLock::new(Mutex::default);
+
+/// One root of a (possibly multi-root) LSP workspace with its own index and config.
+#[derive(Debug)]
+pub struct WorkspaceFolderState {
+  pub name: String,
+  pub root: PathBuf,
+  pub index_manager: Option<Arc<IndexManager>>,
+  pub config_manager: Option<ConfigManager>,
+  /// Compiled frontmatter schemas by enclosing metadata directory (`None` for
+  /// the root ones); cleared when a schema file or the config changes
+  entity_schemas: Mutex<HashMap<Option<PathBuf>, Arc<EntitySchemas>>>,
+}
+
+impl WorkspaceFolderState {
+  /// Open the metadata index and config for a folder.
+  ///
+  /// Either may be missing (e.g. unwritable cache dir or broken root config);
+  /// requests routed to the folder then degrade instead of failing.
+  ///
+  /// The index lives under the `workspace.cache_dir` of the folder's config,
+  /// shared with the other folders of that config. Script configs are
+  /// evaluated with the connection's `runtime` override.
+  pub fn open(name: impl Into<String>, root: PathBuf, runtime: &SharedRuntimeOverride) -> Self {
+    let config_manager = Self::open_config_manager(&root, runtime)
+      .inspect_err(|error| eprintln!("Failed to load config for {}: {error}", root.display()))
+      .ok();
+    let index_manager = Self::open_index_manager(&root, config_manager.as_ref());
+
+    Self {
+      name: name.into(),
+      root,
+      index_manager,
+      config_manager,
+      entity_schemas: Mutex::default(),
+    }
+  }
+
+  /// Whether entities of the other workspace folders are visible from this one.
+  pub fn cross_folder_resolution(&self) -> bool {
+    self
+      .config_manager
+      .as_ref()
+      .and_then(|manager| manager.get_root_config().workspace)
+      .is_some_and(|workspace| workspace.cross_folder_resolution)
+  }
+
+  /// Load the config found from the folder root upwards, independent of the
+  /// directory the server was started in, evaluating script configs with the
+  /// connection's `runtime` override.
+  ///
+  /// # Errors
+  ///
+  /// Returns a message when core state is uninitialized or the root config
+  /// cannot be parsed.
+  pub fn open_config_manager(root: &Path, runtime: &SharedRuntimeOverride) -> Result<ConfigManager, String> {
+    let state = Initializer::get().map_err(|error| error.to_string())?;
+    let feature = with_runtime_override(state.feature().clone(), runtime);
+    ConfigManager::new(feature, root).map_err(|error| error.to_string())
+  }
+
+  /// The same folder using `config_manager`, sharing the open index.
+  pub fn with_config_manager(&self, config_manager: ConfigManager) -> Self {
+    Self {
+      name: self.name.clone(),
+      root: self.root.clone(),
+      index_manager: self.index_manager.clone(),
+      config_manager: Some(config_manager),
+      entity_schemas: Mutex::default(),
+    }
+  }
+
+  /// Files of this folder honoring its ignore files and `workspace` globs.
+  pub fn file_discovery(&self) -> FileDiscovery {
+    pipeline::workspace_discovery(&self.root, self.config_manager.as_ref())
+  }
+
+  /// Metadata directory layout from this folder's root config.
+  pub fn metadata_layout(&self) -> MetadataConfig {
+    pipeline::metadata_layout(self.config_manager.as_ref())
+  }
+
+  /// Frontmatter schemas that apply to the metadata document at `path`,
+  /// including those of a nested metadata directory enclosing it.
+  ///
+  /// Compiled once per metadata directory; reading the `_schema` files blocks,
+  /// so call this off the async runtime. Edits to a schema file or the config
+  /// apply after [`Self::invalidate_entity_schemas`].
+  ///
+  /// # Errors
+  ///
+  /// Returns the first schema that cannot be loaded.
+  pub fn entity_schemas(&self, path: &Path) -> Result<Arc<EntitySchemas>, MetadataError> {
+    let layout = self.metadata_layout();
+    let metadata_dir = metadata_dir_of(path, &self.root, &layout).filter(|dir| dir.parent() != Some(&self.root));
+    if let Some(schemas) = self.schema_cache().get(&metadata_dir) {
+      return Ok(Arc::clone(schemas));
+    }
+
+    let schemas = Arc::new(EntitySchemas::load_for(&self.root, metadata_dir.as_deref(), &layout)?);
+    self.schema_cache().insert(metadata_dir, Arc::clone(&schemas));
+    Ok(schemas)
+  }
+
+  /// Forget the compiled schemas after a schema file or the config changed.
+  pub fn invalidate_entity_schemas(&self) {
+    self.schema_cache().clear();
+  }
+
+  fn schema_cache(&self) -> std::sync::MutexGuard<'_, HashMap<Option<PathBuf>, Arc<EntitySchemas>>> {
+    self
+      .entity_schemas
+      .lock()
+      .unwrap_or_else(std::sync::PoisonError::into_inner)
+  }
+
+  fn open_index_manager(root: &Path, config_manager: Option<&ConfigManager>) -> Option<Arc<IndexManager>> {
+    let context = ResolutionContext {
+      workspace_root: Some(root.to_path_buf()),
+      cli_target_path: None,
+      cli_cwd: None,
+      show_target_parent: None,
+      lsp_startup_dir: Some(root.to_path_buf()),
+      cache_dir: pipeline::cache_dir(config_manager),
+      config_root: pipeline::config_root(config_manager),
+    };
+
+    let index_path = MetadataResolver::resolve_for_write(&context)
+      .inspect_err(|error| eprintln!("Failed to locate metadata index for {}: {error}", root.display()))
+      .ok()?;
+    let mut open_indexes = OPEN_INDEXES.lock().unwrap_or_else(std::sync::PoisonError::into_inner);
+    open_indexes.retain(|_, index| index.strong_count() > 0);
+    if let Some(index_manager) = open_indexes.get(&index_path).and_then(Weak::upgrade) {
+      return Some(index_manager);
+    }
+
+    // sled releases its file lock from background threads shortly after the
+    // last handle drops, so a client reconnecting right away may still find it held
+    let mut opened = IndexManager::open(&index_path);
+    for _ in 0..INDEX_LOCK_RETRIES {
+      if !matches!(opened, Err(sled::Error::Io(_))) {
+        break;
+      }
+      std::thread::sleep(INDEX_LOCK_RETRY_DELAY);
+      opened = IndexManager::open(&index_path);
+    }
+    match opened {
+      Ok(manager) => {
+        let manager = Arc::new(manager);
+        open_indexes.insert(index_path, Arc::downgrade(&manager));
+        Some(manager)
+      }
+      Err(error) => {
+        eprintln!("Failed to open metadata index at {}: {error}", index_path.display());
+        None
+      }
+    }
+  }
+}
+
+/// Workspace folders known to the server, in the order the client reported them.
+#[derive(Debug, Default)]
+pub struct WorkspaceFolders {
+  folders: Vec<Arc<WorkspaceFolderState>>,
+}
+
+impl WorkspaceFolders {
+  /// Add a folder, replacing any folder with the same root.
+  pub fn insert(&mut self, folder: WorkspaceFolderState) {
+    self.remove(&folder.root);
+    self.folders.push(Arc::new(folder));
+  }
+
+  pub fn remove(&mut self, root: &Path) -> Option<Arc<WorkspaceFolderState>> {
+    let index = self.folders.iter().position(|folder| folder.root == root)?;
+    Some(self.folders.remove(index))
+  }
+
+  pub fn contains(&self, root: &Path) -> bool {
+    self.folders.iter().any(|folder| folder.root == root)
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.folders.is_empty()
+  }
+
+  /// First folder; used when a request carries no path to route by.
+  pub fn primary(&self) -> Option<Arc<WorkspaceFolderState>> {
+    self.folders.first().cloned()
+  }
+
+  pub fn all(&self) -> impl Iterator<Item = &Arc<WorkspaceFolderState>> {
+    self.folders.iter()
+  }
+
+  /// Folder owning `path`; with nested folders the innermost one wins. Paths
+  /// outside every folder have none, so callers fall back to defaults.
+  pub fn owner_of(&self, path: &Path) -> Option<Arc<WorkspaceFolderState>> {
+    self
+      .folders
+      .iter()
+      .filter(|folder| path.starts_with(&folder.root))
+      .max_by_key(|folder| folder.root.components().count())
+      .cloned()
+  }
+
+  /// Index managers visible from `folder`: its own, then the other folders'
+  /// when it enables cross-folder resolution.
+  pub fn visible_index_managers(&self, folder: &WorkspaceFolderState) -> Vec<Arc<IndexManager>> {
+    let own = folder.index_manager.iter().cloned();
+    if !folder.cross_folder_resolution() {
+      return own.collect();
+    }
+
+    own
+      .chain(
+        self
+          .folders
+          .iter()
+          .filter(|other| other.root != folder.root)
+          .filter_map(|other| other.index_manager.clone()),
+      )
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+  };
+
+  use novelsaga_core::state::{feat::Feature, init::Initializer};
+  use tempfile::TempDir;
+
+  use super::{WorkspaceFolderState, WorkspaceFolders};
+  use crate::config::loader::SharedRuntimeOverride;
+
+  fn folder(root: &str) -> WorkspaceFolderState {
+    WorkspaceFolderState {
+      name: root.to_string(),
+      root: PathBuf::from(root),
+      index_manager: None,
+      config_manager: None,
+      entity_schemas: std::sync::Mutex::default(),
+    }
+  }
+
+  #[test]
+  fn owner_of_routes_to_innermost_folder() {
+    let mut folders = WorkspaceFolders::default();
+    folders.insert(folder("/series/book-01"));
+    folders.insert(folder("/series"));
+    folders.insert(folder("/series/book-02"));
+
+    let owner = |path: &str| folders.owner_of(Path::new(path)).map(|folder| folder.root.clone());
+
+    assert_eq!(owner("/series/book-02/ch1.md"), Some(PathBuf::from("/series/book-02")));
+    assert_eq!(owner("/series/shared/world.md"), Some(PathBuf::from("/series")));
+    assert_eq!(owner("/series/book-01x/ch1.md"), Some(PathBuf::from("/series")));
+    assert_eq!(owner("/elsewhere/ch1.md"), None);
+  }
+
+  #[test]
+  fn insert_replaces_and_remove_drops_folder() {
+    let mut folders = WorkspaceFolders::default();
+    folders.insert(folder("/series/book-01"));
+    folders.insert(folder("/series/book-01"));
+    assert_eq!(folders.all().count(), 1);
+
+    assert!(folders.remove(Path::new("/series/book-01")).is_some());
+    assert!(folders.is_empty());
+    assert!(folders.remove(Path::new("/series/book-01")).is_none());
+  }
+
+  #[test]
+  fn folders_of_one_root_config_share_its_index() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let root = temp_dir.path().canonicalize()?;
+    std::fs::write(
+      root.join("novelsaga.config.yaml"),
+      "workspace:\n  cache_dir: build/index\n",
+    )?;
+    std::fs::create_dir_all(root.join("book-01"))?;
+
+    let _ = Initializer::init(Feature::new(None, None));
+    let series = WorkspaceFolderState::open("series", root.clone(), &SharedRuntimeOverride::default());
+    let book = WorkspaceFolderState::open("book-01", root.join("book-01"), &SharedRuntimeOverride::default());
+
+    let series_index = series.index_manager.expect("series index");
+    let book_index = book.index_manager.expect("book index");
+    assert_eq!(series_index.db_path(), root.join("build/index/sled"));
+    assert!(Arc::ptr_eq(&series_index, &book_index));
+    Ok(())
+  }
+}
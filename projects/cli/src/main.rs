@@ -1,9 +1,12 @@
 use std::sync::Arc;
 
-use novelsaga_core::state::{feat::Feature, init::Initializer};
+use novelsaga_core::{
+  config::overrides::ConfigOverrides,
+  state::{feat::Feature, init::Initializer},
+};
 
 use crate::{
-  args::{Cli, Commands},
+  args::{Cli, Commands, LspListen},
   bridge::BridgeManager,
   config::loader::ConfigLoader,
 };
@@ -30,13 +33,34 @@ async fn main() {
   let js_loader = Some(config_loader.create_js_loader());
   let ts_loader = Some(config_loader.create_ts_loader());
 
+  // 环境变量与 `--config` 覆盖项：`--config` 有误时直接退出；
+  // 无效的环境变量只有 `config` 命令视为错误，其他命令（如 LSP）警告后忽略
+  let config_overrides = if matches!(cli.command, Some(Commands::Config { .. })) {
+    ConfigOverrides::from_sources(std::env::vars(), cli.get_config_overrides())
+  } else {
+    ConfigOverrides::from_sources_skipping_invalid_env(std::env::vars(), cli.get_config_overrides()).map(
+      |(overrides, skipped)| {
+        for error in skipped {
+          eprintln!("Ignoring invalid config override: {error}");
+        }
+        overrides
+      },
+    )
+  }
+  .unwrap_or_else(|error| {
+    eprintln!("Invalid config override: {error}");
+    std::process::exit(2);
+  });
+
   // 初始化 Core Feature（提供配置加载能力）
-  let feature = Feature::new(js_loader, ts_loader);
+  let feature = Feature::new(js_loader, ts_loader).with_config_overrides(config_overrides);
   Initializer::init(feature).expect("Failed to initialize");
 
   match &cli.command {
-    Some(Commands::Lsp {}) => {
-      lsp::start().await;
+    Some(Commands::Lsp {
+      listen, allow_remote, ..
+    }) => {
+      lsp::start(listen.clone().unwrap_or(LspListen::Stdio), *allow_remote).await;
     }
     Some(Commands::Init { path }) => {
       commands::init::run(path);
@@ -48,8 +72,19 @@ async fn main() {
         check
       );
     }
-    Some(Commands::Check { files }) => {
-      todo!("Check command not implemented yet. Files: {:?}", files);
+    Some(Commands::Check { files }) => match commands::check::run(files) {
+      Ok(0) => {}
+      Ok(_) => std::process::exit(1),
+      Err(error) => {
+        eprintln!("Error: {error:#}");
+        std::process::exit(1);
+      }
+    },
+    Some(Commands::Config { command }) => {
+      if let Err(error) = commands::config::handle_config_command(command) {
+        eprintln!("Error: {error:#}");
+        std::process::exit(1);
+      }
     }
     Some(Commands::Metadata(cmd)) => {
       commands::metadata::handle_metadata_command(cmd.clone())
@@ -5,10 +5,29 @@ use std::{
 };
 
 use blake3;
-use novelsaga_core::metadata::model::MetadataEntity;
+use novelsaga_core::metadata::{model::MetadataEntity, parser::resolve_entity_id};
+use serde::{Deserialize, Serialize};
 use serde_json;
 use sled::Db;
 
+const SCHEMA_VERSION_KEY: &[u8] = b"meta:schema_version";
+/// Version 2: ids from `resolve_entity_id` (was: path hashes and file stems),
+/// plus `claim:` records of every file defining an id.
+const SCHEMA_VERSION: u32 = 2;
+
+/// Last indexed state of a metadata file, used to skip unchanged files.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileState {
+  /// blake3 hash of the file content (hex)
+  pub content_hash: String,
+  /// Modification time in nanoseconds since the Unix epoch
+  pub mtime_ns: u64,
+  /// Fingerprint of the workspace root and metadata layout the file was
+  /// indexed under; the same content yields another entity when they change
+  #[serde(default)]
+  pub layout_fingerprint: String,
+}
+
 /// `IndexManager` provides persistent key-value storage for metadata entities
 /// with secondary indexes for name, type, and namespace lookups.
 #[derive(Debug)]
@@ -36,6 +55,7 @@ impl IndexManager {
       db_path: path.to_path_buf(),
       path_to_id: RwLock::new(HashMap::new()),
     };
+    manager.migrate()?;
     manager.rebuild_path_to_id_index()?;
     Ok(manager)
   }
@@ -66,9 +86,19 @@ impl IndexManager {
   /// * `Ok(())` on success
   /// * `Err(sled::Error)` if database operation fails
   pub fn index_entity(&self, entity: &MetadataEntity) -> Result<(), sled::Error> {
-    // Clean up old indexes if entity already exists (prevents ghost indexes)
-    if self.get_by_id(&entity.id)?.is_some() {
-      self.remove_entity(&entity.id)?;
+    let path = Self::entity_index_path(entity);
+
+    // The file previously defined another id (e.g. its frontmatter `id` changed)
+    if let Some(path) = &path
+      && self.get_id_by_path(path).is_some_and(|id| id != entity.id)
+    {
+      self.remove_path(path)?;
+    }
+
+    // Clean up old indexes if entity already exists (prevents ghost indexes);
+    // claims of other files defining the same id are kept
+    if let Some(existing) = self.get_by_id(&entity.id)? {
+      self.remove_entity_records(&existing)?;
     }
 
     // Serialize entity
@@ -92,19 +122,104 @@ impl IndexManager {
     let ns_key = format!("ns:{}:{}", entity.namespace, entity.id);
     self.db.insert(ns_key.as_bytes(), entity.id.as_bytes())?;
 
-    if let Some(path) = Self::entity_index_path(entity) {
+    if let Some(path) = path {
+      // Claim: claim:{id}\0{path}
+      self
+        .db
+        .insert(Self::claim_key(&entity.id, &path), path.to_string_lossy().as_bytes())?;
       self.path_index_write().insert(path, entity.id.clone());
     }
 
     Ok(())
   }
 
+  /// Files currently defining `id`; more than one means a duplicate id.
+  ///
+  /// # Returns
+  /// * `Ok(Vec<PathBuf>)` - Claiming file paths, sorted
+  /// * `Err(sled::Error)` if database operation fails
+  pub fn claimants(&self, id: &str) -> Result<Vec<PathBuf>, sled::Error> {
+    let mut paths = Vec::new();
+    for item in self.db.scan_prefix(Self::claim_prefix(id)) {
+      let (_key, path_bytes) = item?;
+      paths.push(PathBuf::from(String::from_utf8_lossy(&path_bytes).into_owned()));
+    }
+    paths.sort();
+    Ok(paths)
+  }
+
+  /// Ids defined by more than one file, with the defining files.
+  ///
+  /// # Returns
+  /// Duplicate ids sorted by id
+  #[must_use]
+  pub fn duplicate_ids(&self) -> Vec<(String, Vec<PathBuf>)> {
+    let mut claims: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for (path, id) in self.path_index_read().iter() {
+      claims.entry(id.clone()).or_default().push(path.clone());
+    }
+
+    let mut duplicates: Vec<(String, Vec<PathBuf>)> = claims
+      .into_iter()
+      .filter(|(_, paths)| paths.len() > 1)
+      .map(|(id, mut paths)| {
+        paths.sort();
+        (id, paths)
+      })
+      .collect();
+    duplicates.sort();
+    duplicates
+  }
+
+  /// Forget the file at `path` (deleted or renamed away).
+  ///
+  /// The entity is removed when this file was the one stored for its id.
+  ///
+  /// # Returns
+  /// * `Ok(Vec<PathBuf>)` - Files still defining the same id; callers should
+  ///   reindex one of them when the stored entity was removed
+  /// * `Err(sled::Error)` if database operation fails
+  pub fn remove_path(&self, path: &Path) -> Result<Vec<PathBuf>, sled::Error> {
+    let path = Self::normalize_path(path);
+    let Some(id) = self.path_index_write().remove(&path) else {
+      return Ok(Vec::new());
+    };
+    self.db.remove(Self::claim_key(&id, &path))?;
+
+    if let Some(entity) = self.get_by_id(&id)?
+      && Self::entity_index_path(&entity).as_deref() == Some(path.as_path())
+    {
+      self.remove_entity_records(&entity)?;
+    }
+
+    self.claimants(&id)
+  }
+
   #[must_use]
   pub fn get_id_by_path(&self, path: &Path) -> Option<String> {
     let normalized_path = Self::normalize_path(path);
     self.path_index_read().get(&normalized_path).cloned()
   }
 
+  /// Retrieves the entity defined by the file at `path`.
+  ///
+  /// # Returns
+  /// * `Ok(Some(entity))` if the file's entity is stored
+  /// * `Ok(None)` if the file is not indexed, or another file won its duplicate id
+  /// * `Err(sled::Error)` if database operation fails
+  pub fn get_by_path(&self, path: &Path) -> Result<Option<MetadataEntity>, sled::Error> {
+    let normalized_path = Self::normalize_path(path);
+    let Some(id) = self.get_id_by_path(&normalized_path) else {
+      return Ok(None);
+    };
+
+    Ok(
+      self
+        .get_by_id(&id)?
+        .filter(|entity| Self::entity_index_path(entity).as_deref() == Some(normalized_path.as_path())),
+    )
+  }
+
   /// Retrieves an entity by its ID.
   ///
   /// # Arguments
@@ -166,27 +281,156 @@ impl IndexManager {
       if let Some(path) = Self::entity_index_path(&entity) {
         self.path_index_write().remove(&path);
       }
+      self.remove_entity_records(&entity)?;
+    }
+
+    // Remove claims: claim:{id}\0{path}
+    for path in self.claimants(id)? {
+      self.path_index_write().remove(&path);
+      self.db.remove(Self::claim_key(id, &path))?;
+    }
+
+    Ok(())
+  }
+
+  /// Remove the entity record and its secondary indexes, keeping claims.
+  fn remove_entity_records(&self, entity: &MetadataEntity) -> Result<(), sled::Error> {
+    let id = &entity.id;
+
+    // Remove entity: entity:{id}
+    let entity_key = format!("entity:{id}");
+    self.db.remove(entity_key.as_bytes())?;
 
-      // Remove entity: entity:{id}
-      let entity_key = format!("entity:{id}");
-      self.db.remove(entity_key.as_bytes())?;
+    // Remove name index: name:{namespace}:{id}
+    let name_key = format!("name:{}:{}", entity.namespace, id);
+    self.db.remove(name_key.as_bytes())?;
 
-      // Remove name index: name:{namespace}:{id}
-      let name_key = format!("name:{}:{}", entity.namespace, id);
-      self.db.remove(name_key.as_bytes())?;
+    // Remove type index: type:{type}:{id}
+    let type_key = format!("type:{}:{}", entity.type_, id);
+    self.db.remove(type_key.as_bytes())?;
 
-      // Remove type index: type:{type}:{id}
-      let type_key = format!("type:{}:{}", entity.type_, id);
-      self.db.remove(type_key.as_bytes())?;
+    // Remove namespace index: ns:{namespace}:{id}
+    let ns_key = format!("ns:{}:{}", entity.namespace, id);
+    self.db.remove(ns_key.as_bytes())?;
+
+    Ok(())
+  }
+
+  /// Upgrade a database written by an older schema.
+  ///
+  /// Version 1 keyed entities by a hash of the absolute path (CLI) or the file
+  /// stem (LSP); every entity is re-keyed with `resolve_entity_id`. Entities
+  /// without a recorded path cannot be re-keyed and are dropped, and all file
+  /// states are forgotten, so the next index run re-adds them under their new ids.
+  fn migrate(&self) -> Result<(), sled::Error> {
+    let version = self
+      .db
+      .get(SCHEMA_VERSION_KEY)?
+      .and_then(|bytes| <[u8; 4]>::try_from(bytes.as_ref()).ok())
+      .map_or(1, u32::from_be_bytes);
+    if version >= SCHEMA_VERSION {
+      return Ok(());
+    }
 
-      // Remove namespace index: ns:{namespace}:{id}
-      let ns_key = format!("ns:{}:{}", entity.namespace, id);
-      self.db.remove(ns_key.as_bytes())?;
+    // Claims are re-created under the new ids
+    let stale_claims: Vec<_> = self
+      .db
+      .scan_prefix(b"claim:")
+      .keys()
+      .collect::<Result<_, sled::Error>>()?;
+    let stale_file_states: Vec<_> = self
+      .db
+      .scan_prefix(b"file:")
+      .keys()
+      .collect::<Result<_, sled::Error>>()?;
+    for key in stale_claims.into_iter().chain(stale_file_states) {
+      self.db.remove(key)?;
     }
 
+    for entity in self.list_all()? {
+      self.remove_entity_records(&entity)?;
+      if let Some(entity) = Self::migrated_entity(entity) {
+        self.index_entity(&entity)?;
+      }
+    }
+
+    self.db.insert(SCHEMA_VERSION_KEY, &SCHEMA_VERSION.to_be_bytes())?;
+    self.db.flush()?;
+    Ok(())
+  }
+
+  /// The entity re-keyed with its stable id; `None` when its file is unknown.
+  fn migrated_entity(mut entity: MetadataEntity) -> Option<MetadataEntity> {
+    let path = Self::entity_index_path(&entity)?;
+    entity.id = resolve_entity_id(&path, &entity.frontmatter, &entity.namespace, &entity.type_)?;
+    Some(entity)
+  }
+
+  /// Stored state of the file at `path`, if it was indexed before.
+  ///
+  /// # Returns
+  /// * `Ok(Some(state))` if the file has a recorded state
+  /// * `Ok(None)` if not
+  /// * `Err(sled::Error)` if database operation fails
+  pub fn file_state(&self, path: &Path) -> Result<Option<FileState>, sled::Error> {
+    match self.db.get(Self::file_state_key(&Self::normalize_path(path)))? {
+      Some(bytes) => Ok(serde_json::from_slice(&bytes).ok()),
+      None => Ok(None),
+    }
+  }
+
+  /// Record the state of the file at `path` after indexing it.
+  ///
+  /// # Returns
+  /// * `Ok(())` on success
+  /// * `Err(sled::Error)` if database operation fails
+  pub fn set_file_state(&self, path: &Path, state: &FileState) -> Result<(), sled::Error> {
+    let bytes = serde_json::to_vec(state)
+      .map_err(|e| sled::Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string())))?;
+    self
+      .db
+      .insert(Self::file_state_key(&Self::normalize_path(path)), bytes)?;
+    Ok(())
+  }
+
+  /// Forget the recorded state of the file at `path`.
+  ///
+  /// # Returns
+  /// * `Ok(())` on success
+  /// * `Err(sled::Error)` if database operation fails
+  pub fn remove_file_state(&self, path: &Path) -> Result<(), sled::Error> {
+    self.db.remove(Self::file_state_key(&Self::normalize_path(path)))?;
     Ok(())
   }
 
+  /// Files under `root` with a recorded state or an indexed entity.
+  ///
+  /// Entities indexed before file states were recorded have no state but are
+  /// still tracked, so they are removed once their file is gone.
+  ///
+  /// # Returns
+  /// * `Ok(Vec<PathBuf>)` - Tracked file paths, sorted
+  /// * `Err(sled::Error)` if database operation fails
+  pub fn tracked_files_under(&self, root: &Path) -> Result<Vec<PathBuf>, sled::Error> {
+    let root = Self::normalize_path(root);
+    let mut paths: Vec<PathBuf> = self
+      .path_index_read()
+      .keys()
+      .filter(|path| path.starts_with(&root))
+      .cloned()
+      .collect();
+    for key in self.db.scan_prefix(b"file:").keys() {
+      let key = key?;
+      let path = PathBuf::from(String::from_utf8_lossy(&key[b"file:".len()..]).into_owned());
+      if path.starts_with(&root) {
+        paths.push(path);
+      }
+    }
+    paths.sort();
+    paths.dedup();
+    Ok(paths)
+  }
+
   /// Rebuilds all indexes from scratch by scanning all entities.
   ///
   /// # Returns
@@ -222,7 +466,8 @@ impl IndexManager {
       self.index_entity(&entity)?;
     }
 
-    Ok(())
+    // Files that lost a duplicate id to another file keep their claims
+    self.rebuild_path_to_id_index()
   }
 
   /// Flushes the database to ensure all writes are persisted.
@@ -301,6 +546,20 @@ impl IndexManager {
     path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
   }
 
+  fn file_state_key(path: &Path) -> Vec<u8> {
+    format!("file:{}", path.to_string_lossy()).into_bytes()
+  }
+
+  fn claim_prefix(id: &str) -> Vec<u8> {
+    format!("claim:{id}\0").into_bytes()
+  }
+
+  fn claim_key(id: &str, path: &Path) -> Vec<u8> {
+    let mut key = Self::claim_prefix(id);
+    key.extend_from_slice(path.to_string_lossy().as_bytes());
+    key
+  }
+
   fn rebuild_path_to_id_index(&self) -> Result<(), sled::Error> {
     let mut path_to_id = HashMap::new();
 
@@ -313,6 +572,18 @@ impl IndexManager {
       }
     }
 
+    for item in self.db.scan_prefix(b"claim:") {
+      let (key, path_bytes) = item?;
+      let Some(id) = key[b"claim:".len()..]
+        .split(|byte| *byte == 0)
+        .next()
+        .map(|id| String::from_utf8_lossy(id).into_owned())
+      else {
+        continue;
+      };
+      path_to_id.insert(PathBuf::from(String::from_utf8_lossy(&path_bytes).into_owned()), id);
+    }
+
     *self.path_index_write() = path_to_id;
     Ok(())
   }
@@ -334,10 +605,14 @@ impl IndexManager {
 
 #[cfg(test)]
 mod tests {
+  use std::{ops::ControlFlow, sync::Arc};
+
+  use novelsaga_core::{config::metadata::MetadataConfig, discovery::FileDiscovery};
   use serde_json::json;
   use tempfile::TempDir;
 
   use super::*;
+  use crate::metadata::pipeline;
 
   #[test]
   fn test_generate_id() {
@@ -626,4 +901,119 @@ mod tests {
 
     Ok(())
   }
+
+  #[test]
+  fn test_open_migrates_v1_ids_to_stable_ids() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let entity_path = temp_dir.path().join("book-01/metadata/characters/hero.md");
+    std::fs::create_dir_all(entity_path.parent().expect("parent"))?;
+    std::fs::write(&entity_path, "hero")?;
+    let legacy_id = IndexManager::generate_id(&entity_path.to_string_lossy());
+
+    {
+      let manager = IndexManager::open(&temp_dir.path().join("db"))?;
+      let entity = MetadataEntity::new(
+        &legacy_id,
+        "character",
+        "book-01",
+        json!({"canonical_path": entity_path.to_string_lossy()}),
+        "body",
+      );
+      manager.index_entity(&entity)?;
+      // Databases written before schema versioning carry no version key
+      manager.db.remove(SCHEMA_VERSION_KEY)?;
+      manager.flush()?;
+    }
+
+    let reopened = IndexManager::open(&temp_dir.path().join("db"))?;
+    assert!(reopened.get_by_id(&legacy_id)?.is_none());
+    let migrated = reopened.get_by_id("book-01/character/hero")?.expect("migrated entity");
+    assert_eq!(migrated.body, "body");
+    assert_eq!(
+      reopened.get_id_by_path(&entity_path),
+      Some("book-01/character/hero".to_string())
+    );
+    assert_eq!(reopened.list_by_namespace("book-01")?.len(), 1);
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_open_drops_v1_entities_without_path() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let workspace = temp_dir.path().join("workspace");
+    let entity_path = workspace.join("metadata/characters/hero.md");
+    std::fs::create_dir_all(entity_path.parent().expect("parent"))?;
+    std::fs::write(&entity_path, "---\nname: Hero\n---\nbody")?;
+    let legacy_id = IndexManager::generate_id(&entity_path.to_string_lossy());
+    let db_path = temp_dir.path().join("db");
+
+    {
+      let manager = IndexManager::open(&db_path)?;
+      // Baseline indexes stored entities without any path field
+      let entity = MetadataEntity::new(&legacy_id, "character", "global", json!({"name": "Hero"}), "body");
+      manager.index_entity(&entity)?;
+      manager.set_file_state(
+        &entity_path,
+        &FileState {
+          content_hash: "stale".to_string(),
+          mtime_ns: 1,
+          layout_fingerprint: String::new(),
+        },
+      )?;
+      manager.db.remove(SCHEMA_VERSION_KEY)?;
+      manager.flush()?;
+    }
+
+    let reopened = Arc::new(IndexManager::open(&db_path)?);
+    assert!(reopened.get_by_id(&legacy_id)?.is_none());
+    assert_eq!(reopened.file_state(&entity_path)?, None);
+
+    let layout = MetadataConfig::default();
+    let discovery = FileDiscovery::new(&workspace);
+    pipeline::index_directory(
+      &reopened,
+      &discovery,
+      &layout,
+      &workspace,
+      &pipeline::IndexOptions::default(),
+      |_| ControlFlow::Continue(()),
+    )?;
+    let entities = reopened.list_all()?;
+    assert_eq!(entities.len(), 1, "{entities:?}");
+    assert_eq!(entities[0].get_field("name"), Some(&json!("Hero")));
+
+    Ok(())
+  }
+
+  #[test]
+  fn test_file_state_roundtrip_and_tracking() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let manager = IndexManager::open(&temp_dir.path().join("db"))?;
+    let inside = temp_dir.path().join("book-01/hero.md");
+    let outside = temp_dir.path().join("book-02/hero.md");
+    let state = FileState {
+      content_hash: "abc".to_string(),
+      mtime_ns: 42,
+      layout_fingerprint: "layout".to_string(),
+    };
+
+    manager.set_file_state(&inside, &state)?;
+    manager.set_file_state(&outside, &state)?;
+    assert_eq!(manager.file_state(&inside)?, Some(state));
+    assert_eq!(
+      manager.tracked_files_under(&temp_dir.path().join("book-01"))?,
+      vec![inside.clone()]
+    );
+
+    manager.remove_file_state(&inside)?;
+    assert_eq!(manager.file_state(&inside)?, None);
+    assert!(
+      manager
+        .tracked_files_under(&temp_dir.path().join("book-01"))?
+        .is_empty()
+    );
+
+    Ok(())
+  }
 }
@@ -0,0 +1,1053 @@
+//! Metadata indexing pipeline shared by `novelsaga index` and the LSP.
+//!
+//! discover → classify (`DocumentKind`) → parse (`MarkdownParts`) → build
+//! `MetadataEntity` → write to the `IndexManager`.
+//!
+//! Every entry point (CLI batch index, LSP `novelsaga/index`, LSP watched-file
+//! updates) goes through [`build_entity`], so a file yields the same entity and
+//! id no matter how it was indexed. Ids come from `resolve_entity_id`: an
+//! explicit frontmatter `id`, else `{namespace}/{type}/{stem}`; `show` looks
+//! entities up by path.
+//!
+//! Batch indexing is incremental: each file's mtime and content hash are kept
+//! in the index together with a fingerprint of the workspace root and layout,
+//! unchanged files are skipped and vanished files are removed.
+//!
+//! Files come from the workspace [`FileDiscovery`], so `.gitignore`,
+//! `.novelsagaignore` and the configured include/exclude globs apply; files
+//! that become ignored are removed like deleted ones.
+//!
+//! It is also parallel: discovery uses the `ignore` parallel walker, and files
+//! are parsed by [`IndexOptions::workers`] threads one batch at a time. Each
+//! batch is queued in path order on a [`WriteBackWorker`], which writes it while
+//! the next batch is parsed, so the final index does not depend on the number
+//! of workers. The index is flushed once, after the last write.
+
+use std::{
+  collections::{HashMap, HashSet},
+  num::NonZeroUsize,
+  ops::ControlFlow,
+  path::{Path, PathBuf},
+  sync::Arc,
+  thread,
+  time::{Duration, UNIX_EPOCH},
+};
+
+use novelsaga_core::{
+  config::{metadata::MetadataConfig, schema::SchemaViolation},
+  discovery::FileDiscovery,
+  document::{DocumentKind, MarkdownParts, ParseSeverity},
+  metadata::{EntitySchemas, MetadataEntity, resolve_type},
+  state::ConfigManager,
+};
+
+use crate::metadata::{IndexManager, WriteBackWorker, WriteTask, index::FileState};
+
+/// Files parsed and then written per batch by default.
+const DEFAULT_BATCH_SIZE: usize = 256;
+/// How long queued writes of a partial batch wait for more tasks.
+const WRITE_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Tuning of batch indexing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexOptions {
+  /// Threads walking the tree and parsing files
+  pub workers: usize,
+  /// Files held in memory between parsing and writing
+  pub batch_size: usize,
+}
+
+impl Default for IndexOptions {
+  fn default() -> Self {
+    Self {
+      workers: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+      batch_size: DEFAULT_BATCH_SIZE,
+    }
+  }
+}
+
+/// Progress after one file of a batch index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexProgress {
+  pub done: usize,
+  pub total: usize,
+  pub file: PathBuf,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndexOutcome {
+  /// Files indexed for the first time
+  pub added: usize,
+  /// Files re-indexed because their content changed
+  pub changed: usize,
+  /// Files skipped because their mtime or content hash is unchanged
+  pub unchanged: usize,
+  /// Indexed files that no longer exist (or are no longer metadata)
+  pub removed: usize,
+  pub failed: usize,
+  pub cancelled: bool,
+  /// Why each failed file could not be indexed or removed, for the caller to report
+  pub errors: Vec<String>,
+}
+
+impl IndexOutcome {
+  /// Files written to the index in this run.
+  pub fn indexed(&self) -> usize {
+    self.added + self.changed
+  }
+}
+
+/// What indexing one file did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileChange {
+  Added,
+  Changed,
+  Unchanged,
+}
+
+/// Whether `path` is a metadata document the index should hold.
+pub fn is_metadata_file(path: &Path, layout: &MetadataConfig) -> bool {
+  path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("md"))
+    && DocumentKind::classify_path(path, layout) == DocumentKind::Metadata
+}
+
+/// The metadata layout of a workspace: the `metadata` settings of its root
+/// config when one is loaded, otherwise the default `metadata/` layout.
+pub fn metadata_layout(config_manager: Option<&ConfigManager>) -> MetadataConfig {
+  config_manager
+    .map(|manager| manager.get_root_config().metadata)
+    .unwrap_or_default()
+}
+
+/// The `workspace.cache_dir` of a workspace's root config, when one is loaded.
+pub fn cache_dir(config_manager: Option<&ConfigManager>) -> Option<String> {
+  config_manager
+    .and_then(|manager| manager.get_root_config().workspace)
+    .map(|workspace| workspace.cache_dir)
+}
+
+/// The directory of a workspace's root config, which a relative
+/// `workspace.cache_dir` is resolved against, when one is loaded.
+pub fn config_root(config_manager: Option<&ConfigManager>) -> Option<PathBuf> {
+  config_manager.map(ConfigManager::root_dir)
+}
+
+/// File discovery for a workspace `root`, using the `workspace` settings of its
+/// config when one is loaded.
+///
+/// Invalid include/exclude globs are reported and ignored rather than
+/// blocking indexing.
+pub fn workspace_discovery(root: &Path, config_manager: Option<&ConfigManager>) -> FileDiscovery {
+  let config = config_manager
+    .and_then(|manager| manager.get_root_config().workspace)
+    .unwrap_or_default();
+  FileDiscovery::from_config(root, &config).unwrap_or_else(|error| {
+    eprintln!("Invalid workspace include/exclude globs in {}: {error}", root.display());
+    FileDiscovery::new(root)
+  })
+}
+
+/// Metadata documents of the workspace under `dir`, sorted.
+pub fn discover_metadata_files(discovery: &FileDiscovery, dir: &Path, layout: &MetadataConfig) -> Vec<PathBuf> {
+  discovery.files_under(dir, |path| is_metadata_file(path, layout))
+}
+
+/// Build the entity for a metadata document from its content.
+///
+/// Type and namespace come from the frontmatter and the location relative to
+/// `workspace_root` under `layout`; the canonical path is recorded as
+/// `canonical_path`.
+///
+/// # Errors
+///
+/// Returns an error when the entity cannot be built from the document.
+pub fn build_entity(
+  file_path: &Path,
+  content: &str,
+  workspace_root: &Path,
+  layout: &MetadataConfig,
+) -> Result<MetadataEntity, String> {
+  let file_path = normalize_path(file_path);
+  let workspace_root = normalize_path(workspace_root);
+
+  let parts = MarkdownParts::parse(content);
+  let mut entity = MetadataEntity::try_from((parts, file_path.as_path(), workspace_root.as_path(), layout))
+    .map_err(|error| error.to_string())?;
+
+  if let Some(frontmatter) = entity.frontmatter.as_object_mut() {
+    frontmatter.insert(
+      "canonical_path".to_string(),
+      serde_json::Value::String(file_path.to_string_lossy().into_owned()),
+    );
+  }
+  Ok(entity)
+}
+
+/// Other existing files defining the same id as the entity built for `file_path`.
+pub fn duplicate_definitions(index_manager: &IndexManager, id: &str, file_path: &Path) -> Vec<PathBuf> {
+  let file_path = normalize_path(file_path);
+  index_manager
+    .claimants(id)
+    .unwrap_or_default()
+    .into_iter()
+    .filter(|path| *path != file_path && path.exists())
+    .collect()
+}
+
+/// Frontmatter of a metadata document checked against the schema of its type.
+///
+/// Empty when the type declares no schema or the frontmatter does not parse;
+/// the latter is reported by the document lint.
+pub fn frontmatter_violations(
+  schemas: &EntitySchemas,
+  file_path: &Path,
+  content: &str,
+  layout: &MetadataConfig,
+) -> Vec<SchemaViolation> {
+  let report = MarkdownParts::parse_with_issues(content);
+  if report.issues.iter().any(|issue| issue.severity == ParseSeverity::Error) {
+    return Vec::new();
+  }
+  let type_ = resolve_type(file_path, &report.parts.frontmatter, layout);
+  schemas
+    .get(&type_)
+    .map(|schema| schema.validate(&report.parts.frontmatter, content))
+    .unwrap_or_default()
+}
+
+/// Forget a deleted or renamed-away document.
+///
+/// When it was the stored definition of a duplicated id, the next remaining
+/// file is indexed in its place.
+///
+/// # Errors
+///
+/// Returns a message naming the file when the index cannot be updated.
+pub fn remove_file(
+  index_manager: &IndexManager,
+  file_path: &Path,
+  workspace_root: &Path,
+  layout: &MetadataConfig,
+) -> Result<(), String> {
+  let to_error = |error: sled::Error| format!("Failed to remove {} from the index: {error}", file_path.display());
+  index_manager.remove_file_state(file_path).map_err(to_error)?;
+  let remaining = index_manager.remove_path(file_path).map_err(to_error)?;
+
+  match remaining.into_iter().find(|path| path.exists()) {
+    Some(successor) => index_file(index_manager, &successor, workspace_root, layout).map(|_| ()),
+    None => Ok(()),
+  }
+}
+
+/// Read, build and index one metadata document, returning the indexed entity.
+///
+/// # Errors
+///
+/// Returns a message naming the file when reading, parsing or writing fails.
+pub fn index_file(
+  index_manager: &IndexManager,
+  file_path: &Path,
+  workspace_root: &Path,
+  layout: &MetadataConfig,
+) -> Result<MetadataEntity, String> {
+  let content = read_file(file_path)?;
+  index_content(index_manager, file_path, &content, workspace_root, layout)
+}
+
+/// Result of the read-only parse stage for one file of a batch.
+enum PreparedFile {
+  Unchanged,
+  /// Touched but not edited: only the recorded mtime needs refreshing
+  Touched(FileState),
+  Index {
+    entity: Box<MetadataEntity>,
+    state: FileState,
+    change: FileChange,
+  },
+}
+
+/// Decide what indexing `file_path` needs, parsing it unless its mtime or
+/// content hash is unchanged and it was indexed under the same layout. Only
+/// reads the index, so batches of files can be prepared in parallel.
+fn prepare_file(
+  index_manager: &IndexManager,
+  file_path: &Path,
+  workspace_root: &Path,
+  layout: &MetadataConfig,
+  fingerprint: &str,
+) -> Result<PreparedFile, String> {
+  let previous = index_manager
+    .file_state(file_path)
+    .map_err(|error| format!("Failed to read index state of {}: {error}", file_path.display()))?
+    .filter(|previous| previous.layout_fingerprint == fingerprint);
+  let mtime_ns = file_mtime_ns(file_path);
+  if let Some(previous) = &previous
+    && mtime_ns == Some(previous.mtime_ns)
+  {
+    return Ok(PreparedFile::Unchanged);
+  }
+
+  let content = read_file(file_path)?;
+  let content_hash = content_hash(&content);
+  if let Some(previous) = previous
+    && previous.content_hash == content_hash
+  {
+    return Ok(PreparedFile::Touched(FileState {
+      mtime_ns: mtime_ns.unwrap_or(previous.mtime_ns),
+      ..previous
+    }));
+  }
+
+  let entity = parse_entity(file_path, &content, workspace_root, layout)?;
+  let change = if index_manager.get_id_by_path(file_path).is_some() {
+    FileChange::Changed
+  } else {
+    FileChange::Added
+  };
+  Ok(PreparedFile::Index {
+    entity: Box::new(entity),
+    state: FileState {
+      content_hash,
+      mtime_ns: mtime_ns.unwrap_or_default(),
+      layout_fingerprint: fingerprint.to_string(),
+    },
+    change,
+  })
+}
+
+/// Prepare `files` on up to `workers` threads, returning results in file order.
+fn prepare_batch(
+  index_manager: &IndexManager,
+  files: &[PathBuf],
+  workspace_root: &Path,
+  layout: &MetadataConfig,
+  workers: usize,
+) -> Vec<Result<PreparedFile, String>> {
+  let fingerprint = layout_fingerprint(workspace_root, layout);
+  let prepare = |files: &[PathBuf]| {
+    files
+      .iter()
+      .map(|file| prepare_file(index_manager, file, workspace_root, layout, &fingerprint))
+      .collect::<Vec<_>>()
+  };
+
+  let workers = workers.clamp(1, files.len().max(1));
+  if workers == 1 {
+    return prepare(files);
+  }
+
+  let chunk_size = files.len().div_ceil(workers);
+  thread::scope(|scope| {
+    let handles: Vec<_> = files
+      .chunks(chunk_size)
+      .map(|chunk| scope.spawn(move || prepare(chunk)))
+      .collect();
+    handles
+      .into_iter()
+      .flat_map(|handle| handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic)))
+      .collect()
+  })
+}
+
+fn parse_entity(
+  file_path: &Path,
+  content: &str,
+  workspace_root: &Path,
+  layout: &MetadataConfig,
+) -> Result<MetadataEntity, String> {
+  build_entity(file_path, content, workspace_root, layout)
+    .map_err(|error| format!("Failed to parse metadata file {}: {error}", file_path.display()))
+}
+
+fn index_content(
+  index_manager: &IndexManager,
+  file_path: &Path,
+  content: &str,
+  workspace_root: &Path,
+  layout: &MetadataConfig,
+) -> Result<MetadataEntity, String> {
+  let entity = parse_entity(file_path, content, workspace_root, layout)?;
+
+  index_manager
+    .index_entity(&entity)
+    .map_err(|error| format!("Failed to index {}: {error}", file_path.display()))?;
+
+  let state = FileState {
+    content_hash: content_hash(content),
+    mtime_ns: file_mtime_ns(file_path).unwrap_or_default(),
+    layout_fingerprint: layout_fingerprint(workspace_root, layout),
+  };
+  index_manager
+    .set_file_state(file_path, &state)
+    .map_err(|error| format!("Failed to record index state of {}: {error}", file_path.display()))?;
+  Ok(entity)
+}
+
+/// Incrementally index the workspace metadata documents under `scan_root`
+/// (inside `discovery`'s root, classified by `layout`) and flush.
+///
+/// Unchanged files are skipped and indexed files that disappeared from
+/// `scan_root` are removed. Files are parsed in parallel one batch of
+/// `options.batch_size` at a time, and each batch is queued in path order on a
+/// [`WriteBackWorker`] that writes it while the next one is parsed.
+/// `on_progress` runs for each file once its batch is queued; returning `Break`
+/// stops after that batch (before removals), and the work queued so far is
+/// still written and flushed. Per-file failures are counted and collected in
+/// [`IndexOutcome::errors`], not fatal.
+///
+/// # Errors
+///
+/// Returns an error when the writes cannot be finished or the index cannot be flushed.
+pub fn index_directory(
+  index_manager: &Arc<IndexManager>,
+  discovery: &FileDiscovery,
+  layout: &MetadataConfig,
+  scan_root: &Path,
+  options: &IndexOptions,
+  mut on_progress: impl FnMut(&IndexProgress) -> ControlFlow<()>,
+) -> Result<IndexOutcome, String> {
+  let workspace_root = discovery.root();
+  let files = discover_metadata_files(&discovery.clone().threads(options.workers), scan_root, layout);
+  let total = files.len();
+  let batch_size = options.batch_size.max(1);
+
+  let mut outcome = IndexOutcome::default();
+  let mut seen = HashSet::with_capacity(total);
+  // What each queued file changed, settled once the worker reports its failures
+  let mut queued = HashMap::new();
+  let worker = WriteBackWorker::new(index_manager.clone(), batch_size, WRITE_INTERVAL);
+  let mut done = 0;
+  'batches: for batch in files.chunks(batch_size) {
+    for (file, prepared) in batch.iter().zip(prepare_batch(
+      index_manager,
+      batch,
+      workspace_root,
+      layout,
+      options.workers,
+    )) {
+      seen.insert(normalize_path(file));
+      match prepared {
+        Ok(PreparedFile::Unchanged) => outcome.unchanged += 1,
+        Ok(PreparedFile::Touched(state)) => {
+          worker.submit(WriteTask::RecordFileState {
+            path: file.clone(),
+            state,
+          });
+          queued.insert(file.clone(), FileChange::Unchanged);
+        }
+        Ok(PreparedFile::Index { entity, state, change }) => {
+          worker.submit(WriteTask::IndexFile {
+            path: file.clone(),
+            entity,
+            state,
+          });
+          queued.insert(file.clone(), change);
+        }
+        Err(error) => {
+          outcome.failed += 1;
+          outcome.errors.push(error);
+        }
+      }
+    }
+
+    let batch_end = done + batch.len();
+    for file in batch {
+      done += 1;
+      let progress = IndexProgress {
+        done,
+        total,
+        file: file.clone(),
+      };
+      if on_progress(&progress).is_break() {
+        outcome.cancelled = batch_end < total;
+        break 'batches;
+      }
+    }
+  }
+
+  for failure in worker.finish()? {
+    if let Some(path) = &failure.path {
+      queued.remove(path);
+    }
+    outcome.failed += 1;
+    outcome.errors.push(failure.message);
+  }
+  for change in queued.into_values() {
+    match change {
+      FileChange::Added => outcome.added += 1,
+      FileChange::Changed => outcome.changed += 1,
+      FileChange::Unchanged => outcome.unchanged += 1,
+    }
+  }
+
+  if !outcome.cancelled {
+    let tracked = index_manager
+      .tracked_files_under(&normalize_path(scan_root))
+      .map_err(|error| format!("Failed to list indexed files: {error}"))?;
+    for vanished in tracked.into_iter().filter(|path| !seen.contains(path)) {
+      match remove_file(index_manager, &vanished, workspace_root, layout) {
+        Ok(()) => outcome.removed += 1,
+        Err(error) => {
+          outcome.failed += 1;
+          outcome.errors.push(error);
+        }
+      }
+    }
+  }
+
+  index_manager.flush().map_err(|error| {
+    format!(
+      "Failed to flush metadata index at {}: {error}",
+      index_manager.db_path().display()
+    )
+  })?;
+  Ok(outcome)
+}
+
+fn read_file(file_path: &Path) -> Result<String, String> {
+  std::fs::read_to_string(file_path)
+    .map_err(|error| format!("Failed to read metadata file {}: {error}", file_path.display()))
+}
+
+fn content_hash(content: &str) -> String {
+  blake3::hash(content.as_bytes()).to_hex().to_string()
+}
+
+/// Hash of what besides the content shapes a file's entity: the workspace root
+/// (namespaces) and the layout's directories and types. Schemas only affect
+/// diagnostics, so they are left out.
+fn layout_fingerprint(workspace_root: &Path, layout: &MetadataConfig) -> String {
+  let key = serde_json::json!([
+    normalize_path(workspace_root),
+    layout.dirs,
+    layout.folder_types,
+    layout.types,
+  ]);
+  content_hash(&key.to_string())
+}
+
+fn file_mtime_ns(path: &Path) -> Option<u64> {
+  let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+  u64::try_from(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos()).ok()
+}
+
+fn normalize_path(path: &Path) -> PathBuf {
+  path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{ops::ControlFlow, path::Path, sync::Arc, time::Instant};
+
+  use novelsaga_core::{config::metadata::MetadataConfig, discovery::FileDiscovery};
+  use tempfile::TempDir;
+
+  use super::{
+    IndexOptions, build_entity, discover_metadata_files, duplicate_definitions, index_directory, index_file,
+    remove_file,
+  };
+  use crate::metadata::IndexManager;
+
+  const HERO: &str = "---\ntype: character\ntitle: The Hero\n---\n# Hero\n";
+
+  fn write(root: &Path, relative: &str, content: &str) {
+    let path = root.join(relative);
+    std::fs::create_dir_all(path.parent().expect("parent")).expect("create dirs");
+    std::fs::write(path, content).expect("write");
+  }
+
+  /// `count` metadata files spread over books and types, with some duplicate ids.
+  fn write_fixture(root: &Path, count: usize) {
+    for n in 0..count {
+      let book = n % 4;
+      let kind = ["characters", "places", "items"][n % 3];
+      let id = if n % 50 == 0 {
+        format!("id: shared-{}\n", n % 150)
+      } else {
+        String::new()
+      };
+      write(
+        root,
+        &format!("book-{book:02}/metadata/{kind}/entry-{n:05}.md"),
+        &format!(
+          "---\n{id}title: Entry {n}\norder: {n}\n---\n# Entry {n}\n\n{}\n",
+          "Lorem ipsum. ".repeat(20)
+        ),
+      );
+    }
+  }
+
+  fn index_with(root: &Path, options: &IndexOptions) -> (Arc<IndexManager>, TempDir) {
+    let index_dir = TempDir::new().expect("tempdir");
+    let index_manager = Arc::new(IndexManager::open(index_dir.path()).expect("open index"));
+    let outcome = index_directory(
+      &index_manager,
+      &FileDiscovery::new(root),
+      &MetadataConfig::default(),
+      root,
+      options,
+      |_| ControlFlow::Continue(()),
+    )
+    .expect("index");
+    assert_eq!((outcome.failed, outcome.cancelled), (0, false));
+    (index_manager, index_dir)
+  }
+
+  fn snapshot(index_manager: &IndexManager) -> Vec<novelsaga_core::metadata::MetadataEntity> {
+    let mut entities = index_manager.list_all().expect("list");
+    entities.sort_by(|a, b| a.id.cmp(&b.id));
+    entities
+  }
+
+  #[test]
+  fn discover_keeps_only_metadata_documents() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    write(root, "chapter-01.md", "Body");
+    write(root, "metadata/characters/hero.md", HERO);
+    write(root, "book-01/metadata/places/city.md", "City");
+    write(root, "metadata/notes.txt", "Notes");
+    write(root, ".cache/novelsaga/metadata/stale.md", "Stale");
+
+    assert_eq!(
+      discover_metadata_files(&FileDiscovery::new(root), root, &MetadataConfig::default()),
+      vec![
+        root.join("book-01/metadata/places/city.md"),
+        root.join("metadata/characters/hero.md"),
+      ]
+    );
+  }
+
+  #[test]
+  fn configured_layout_indexes_its_own_directories() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    write(root, "设定/人物/主角.md", "# 主角\n");
+    write(root, "metadata/characters/hero.md", HERO);
+    let layout: MetadataConfig = serde_json::from_value(serde_json::json!({
+      "dirs": ["设定"],
+      "folder_types": { "人物": "character" },
+    }))
+    .expect("layout");
+
+    let index_dir = TempDir::new().expect("tempdir");
+    let index_manager = Arc::new(IndexManager::open(index_dir.path()).expect("open index"));
+    let outcome = index_directory(
+      &index_manager,
+      &FileDiscovery::new(root),
+      &layout,
+      root,
+      &IndexOptions::default(),
+      |_| ControlFlow::Continue(()),
+    )
+    .expect("index");
+
+    assert_eq!(outcome.added, 1);
+    let entity = index_manager
+      .get_by_id("global/character/主角")
+      .expect("get")
+      .expect("indexed");
+    assert_eq!(entity.type_, "character");
+  }
+
+  #[test]
+  fn ignored_files_are_skipped_and_dropped_from_the_index() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    write(root, "metadata/characters/hero.md", HERO);
+    write(root, "metadata/drafts/rival.md", "Rival");
+    write(root, "node_modules/pkg/metadata/readme.md", "Readme");
+    write(root, ".gitignore", "node_modules/\n");
+    write(root, ".novelsagaignore", "drafts/\n");
+
+    let index_dir = TempDir::new().expect("tempdir");
+    let index_manager = Arc::new(IndexManager::open(index_dir.path()).expect("open index"));
+    let discovery = FileDiscovery::new(root);
+    let reindex = || {
+      index_directory(
+        &index_manager,
+        &discovery,
+        &MetadataConfig::default(),
+        root,
+        &IndexOptions::default(),
+        |_| ControlFlow::Continue(()),
+      )
+      .expect("index")
+    };
+
+    assert_eq!(reindex().added, 1);
+    assert!(index_manager.get_by_id("global/character/hero").expect("get").is_some());
+
+    write(root, ".novelsagaignore", "drafts/\ncharacters/\n");
+    let outcome = reindex();
+    assert_eq!((outcome.added, outcome.removed), (0, 1));
+    assert!(index_manager.list_all().expect("list").is_empty());
+  }
+
+  #[test]
+  fn build_entity_keeps_frontmatter_and_derives_stable_id() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    write(root, "book-01/metadata/characters/hero.md", HERO);
+    let path = root.join("book-01/metadata/characters/hero.md");
+    let indirect = root.join("book-01/metadata/../metadata/characters/hero.md");
+
+    let entity = build_entity(&path, HERO, root, &MetadataConfig::default()).expect("entity");
+
+    assert_eq!(entity.id, "book-01/character/hero");
+    assert_eq!(entity.type_, "character");
+    assert_eq!(entity.namespace, "book-01");
+    assert!(entity.body.contains("# Hero"));
+    assert_eq!(entity.get_field("title"), Some(&serde_json::json!("The Hero")));
+    assert_eq!(
+      build_entity(&indirect, HERO, root, &MetadataConfig::default()).expect("entity"),
+      entity
+    );
+  }
+
+  #[test]
+  fn batch_and_single_file_indexing_produce_identical_entities() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    write(root, "metadata/characters/hero.md", HERO);
+    write(
+      root,
+      "book-01/metadata/places/city.md",
+      "---\ntype: location\n---\nCity\n",
+    );
+    write(root, "book-01/chapter-01.md", "Chapter");
+
+    let batch_dir = TempDir::new().expect("tempdir");
+    let batch = Arc::new(IndexManager::open(batch_dir.path()).expect("open index"));
+    let outcome = index_directory(
+      &batch,
+      &FileDiscovery::new(root),
+      &MetadataConfig::default(),
+      root,
+      &IndexOptions::default(),
+      |_| ControlFlow::Continue(()),
+    )
+    .expect("index");
+    assert_eq!((outcome.added, outcome.failed, outcome.cancelled), (2, 0, false));
+
+    let single_dir = TempDir::new().expect("tempdir");
+    let single = Arc::new(IndexManager::open(single_dir.path()).expect("open index"));
+    for file in discover_metadata_files(&FileDiscovery::new(root), root, &MetadataConfig::default()) {
+      index_file(&single, &file, root, &MetadataConfig::default()).expect("index file");
+    }
+
+    let mut batch_entities = batch.list_all().expect("list");
+    let mut single_entities = single.list_all().expect("list");
+    batch_entities.sort_by(|a, b| a.id.cmp(&b.id));
+    single_entities.sort_by(|a, b| a.id.cmp(&b.id));
+    assert_eq!(batch_entities.len(), 2);
+    assert_eq!(batch_entities, single_entities);
+  }
+
+  #[test]
+  fn index_directory_stops_on_break_and_keeps_indexed_entities() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    write(root, "metadata/characters/hero.md", HERO);
+    write(root, "metadata/characters/villain.md", "Villain");
+
+    let index_dir = TempDir::new().expect("tempdir");
+    let index_manager = Arc::new(IndexManager::open(index_dir.path()).expect("open index"));
+    let mut reported = Vec::new();
+    let options = IndexOptions {
+      batch_size: 1,
+      ..IndexOptions::default()
+    };
+    let outcome = index_directory(
+      &index_manager,
+      &FileDiscovery::new(root),
+      &MetadataConfig::default(),
+      root,
+      &options,
+      |progress| {
+        reported.push((progress.done, progress.total));
+        ControlFlow::Break(())
+      },
+    )
+    .expect("index");
+
+    assert_eq!(reported, vec![(1, 2)]);
+    assert_eq!((outcome.added, outcome.cancelled), (1, true));
+    assert_eq!(index_manager.list_all().expect("list").len(), 1);
+  }
+
+  #[test]
+  fn ids_survive_moving_the_workspace() {
+    let first = TempDir::new().expect("tempdir");
+    let second = TempDir::new().expect("tempdir");
+    for root in [first.path(), second.path()] {
+      write(root, "book-01/metadata/characters/hero.md", HERO);
+    }
+
+    let id_in = |root: &Path| {
+      build_entity(
+        &root.join("book-01/metadata/characters/hero.md"),
+        HERO,
+        root,
+        &MetadataConfig::default(),
+      )
+      .expect("entity")
+      .id
+    };
+    assert_eq!(id_in(first.path()), id_in(second.path()));
+
+    let explicit = "---\nid: hero-of-time\n---\nHero\n";
+    let moved = build_entity(
+      &first.path().join("metadata/people/link.md"),
+      explicit,
+      first.path(),
+      &MetadataConfig::default(),
+    )
+    .expect("entity");
+    assert_eq!(moved.id, "hero-of-time");
+  }
+
+  #[test]
+  fn duplicate_ids_are_detected_and_resolved_on_removal() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    let duplicate = "---\nid: hero\n---\nHero\n";
+    write(root, "book-01/metadata/characters/hero.md", duplicate);
+    write(root, "book-02/metadata/characters/hero.md", duplicate);
+    let first = root
+      .join("book-01/metadata/characters/hero.md")
+      .canonicalize()
+      .expect("path");
+    let second = root
+      .join("book-02/metadata/characters/hero.md")
+      .canonicalize()
+      .expect("path");
+
+    let index_dir = TempDir::new().expect("tempdir");
+    let index_manager = Arc::new(IndexManager::open(index_dir.path()).expect("open index"));
+    index_directory(
+      &index_manager,
+      &FileDiscovery::new(root),
+      &MetadataConfig::default(),
+      root,
+      &IndexOptions::default(),
+      |_| ControlFlow::Continue(()),
+    )
+    .expect("index");
+
+    assert_eq!(
+      duplicate_definitions(&index_manager, "hero", &first),
+      vec![second.clone()]
+    );
+    assert_eq!(
+      duplicate_definitions(&index_manager, "hero", &second),
+      vec![first.clone()]
+    );
+    assert_eq!(
+      index_manager.duplicate_ids(),
+      vec![("hero".to_string(), vec![first.clone(), second.clone()])]
+    );
+
+    // Removing the stored definition falls back to the remaining file
+    let stored = index_manager.get_by_id("hero").expect("get").expect("entity");
+    let stored_path = stored
+      .get_field("canonical_path")
+      .and_then(|value| value.as_str())
+      .expect("path")
+      .to_string();
+    std::fs::remove_file(&stored_path).expect("remove");
+    remove_file(
+      &index_manager,
+      Path::new(&stored_path),
+      root,
+      &MetadataConfig::default(),
+    )
+    .expect("remove from index");
+
+    let remaining = if stored_path == first.to_string_lossy() {
+      &second
+    } else {
+      &first
+    };
+    let entity = index_manager.get_by_id("hero").expect("get").expect("entity");
+    assert_eq!(
+      entity.get_field("canonical_path"),
+      Some(&serde_json::json!(remaining.to_string_lossy()))
+    );
+    assert!(index_manager.duplicate_ids().is_empty());
+  }
+
+  #[test]
+  fn reindex_skips_unchanged_and_reports_changes() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    write(root, "metadata/characters/hero.md", HERO);
+    write(root, "metadata/characters/villain.md", "Villain");
+    write(root, "metadata/places/city.md", "City");
+
+    let index_dir = TempDir::new().expect("tempdir");
+    let index_manager = Arc::new(IndexManager::open(index_dir.path()).expect("open index"));
+    let reindex = || {
+      index_directory(
+        &index_manager,
+        &FileDiscovery::new(root),
+        &MetadataConfig::default(),
+        root,
+        &IndexOptions::default(),
+        |_| ControlFlow::Continue(()),
+      )
+      .expect("index")
+    };
+
+    assert_eq!(reindex().added, 3);
+    let unchanged = reindex();
+    assert_eq!((unchanged.unchanged, unchanged.indexed()), (3, 0));
+
+    // Touched without edits: the content hash keeps it unchanged
+    write(root, "metadata/places/city.md", "City");
+    write(
+      root,
+      "metadata/characters/villain.md",
+      "---\ntitle: Villain\n---\nVillain\n",
+    );
+    write(root, "metadata/places/castle.md", "Castle");
+    std::fs::remove_file(root.join("metadata/characters/hero.md")).expect("remove");
+
+    let outcome = reindex();
+    assert_eq!(
+      (
+        outcome.added,
+        outcome.changed,
+        outcome.unchanged,
+        outcome.removed,
+        outcome.failed
+      ),
+      (1, 1, 1, 1, 0)
+    );
+    assert!(index_manager.get_by_id("global/character/hero").expect("get").is_none());
+    let villain = index_manager
+      .get_by_id("global/character/villain")
+      .expect("get")
+      .expect("entity");
+    assert_eq!(villain.get_field("title"), Some(&serde_json::json!("Villain")));
+  }
+
+  #[test]
+  fn layout_changes_reindex_unchanged_files() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    write(root, "metadata/characters/villain.md", "Villain");
+
+    let index_dir = TempDir::new().expect("tempdir");
+    let index_manager = Arc::new(IndexManager::open(index_dir.path()).expect("open index"));
+    let reindex = |layout: &MetadataConfig| {
+      index_directory(
+        &index_manager,
+        &FileDiscovery::new(root),
+        layout,
+        root,
+        &IndexOptions::default(),
+        |_| ControlFlow::Continue(()),
+      )
+      .expect("index")
+    };
+
+    assert_eq!(reindex(&MetadataConfig::default()).added, 1);
+    let mut layout = MetadataConfig::default();
+    layout
+      .folder_types
+      .insert("characters".to_string(), "person".to_string());
+    let outcome = reindex(&layout);
+    assert_eq!((outcome.unchanged, outcome.indexed()), (0, 1));
+    assert!(
+      index_manager
+        .get_by_id("global/character/villain")
+        .expect("get")
+        .is_none()
+    );
+    assert!(index_manager.get_by_id("global/person/villain").expect("get").is_some());
+    assert_eq!(reindex(&layout).unchanged, 1);
+  }
+
+  #[test]
+  fn entities_without_file_state_are_removed_with_their_file() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    write(root, "metadata/characters/hero.md", HERO);
+    let path = root.join("metadata/characters/hero.md");
+
+    let index_dir = TempDir::new().expect("tempdir");
+    let index_manager = Arc::new(IndexManager::open(index_dir.path()).expect("open index"));
+    let entity = build_entity(&path, HERO, root, &MetadataConfig::default()).expect("entity");
+    index_manager.index_entity(&entity).expect("index entity");
+    std::fs::remove_file(&path).expect("remove");
+
+    let outcome = index_directory(
+      &index_manager,
+      &FileDiscovery::new(root),
+      &MetadataConfig::default(),
+      root,
+      &IndexOptions::default(),
+      |_| ControlFlow::Continue(()),
+    )
+    .expect("index");
+    assert_eq!(outcome.removed, 1);
+    assert!(index_manager.list_all().expect("list").is_empty());
+  }
+
+  #[test]
+  fn parallel_indexing_is_deterministic() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    write_fixture(root, 300);
+
+    let sequential = IndexOptions {
+      workers: 1,
+      batch_size: 300,
+    };
+    let parallel = IndexOptions {
+      workers: 8,
+      batch_size: 32,
+    };
+    let (expected, _expected_dir) = index_with(root, &sequential);
+    let (actual, _actual_dir) = index_with(root, &parallel);
+
+    assert_eq!(snapshot(&actual), snapshot(&expected));
+    assert_eq!(actual.duplicate_ids(), expected.duplicate_ids());
+    assert!(!expected.duplicate_ids().is_empty());
+  }
+
+  /// Benchmark fixture: `cargo test -p novelsaga-cli --release -- --ignored
+  /// --nocapture index_benchmark`; set `NOVELSAGA_BENCH_FILES` to resize it.
+  #[test]
+  #[ignore = "benchmark"]
+  fn index_benchmark() {
+    let count = std::env::var("NOVELSAGA_BENCH_FILES")
+      .ok()
+      .and_then(|value| value.parse().ok())
+      .unwrap_or(5_000);
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    write_fixture(root, count);
+
+    let run = |options: &IndexOptions| {
+      let start = Instant::now();
+      let (index_manager, index_dir) = index_with(root, options);
+      (start.elapsed(), index_manager, index_dir)
+    };
+    let sequential = IndexOptions {
+      workers: 1,
+      ..IndexOptions::default()
+    };
+    let parallel = IndexOptions::default();
+    let (sequential_time, expected, _expected_dir) = run(&sequential);
+    let (parallel_time, actual, _actual_dir) = run(&parallel);
+
+    println!(
+      "{count} files: sequential {sequential_time:?}, {} workers {parallel_time:?} ({:.2}x)",
+      parallel.workers,
+      sequential_time.as_secs_f64() / parallel_time.as_secs_f64()
+    );
+    assert_eq!(snapshot(&actual), snapshot(&expected));
+  }
+}
@@ -1,11 +1,29 @@
 //! Metadata path resolver for locating workspace-specific metadata storage.
 //!
 //! The resolver handles multiple resolution strategies:
-//! 1. **Explicit workspace root** → canonical path: `<workspace>/.cache/novelsaga/sled`
+//! 1. **Explicit workspace root** → index under its `workspace.cache_dir`
 //! 2. **Fallback root from context** → CLI target path, CLI cwd, show target parent, or LSP startup directory
+//!
+//! `workspace.cache_dir` decides where the sled index lives:
+//! - a relative path is joined to the directory of the root config that sets it (the workspace when
+//!   there is none): `<root>/.cache/novelsaga/sled` by default, wherever the command runs
+//! - an absolute path, `~/…` or `$cache/…` (the per-user cache directory, e.g. `$XDG_CACHE_HOME/novelsaga`)
+//!   is shared by all workspaces, so each gets its own `<name>-<hash>/sled` below it, keyed by a hash of
+//!   the canonical root path
+//!
+//! An index left at the default location of the workspace by a previous configuration is moved
+//! to the resolved location by [`MetadataResolver::resolve_for_write`], or reported when it cannot
+//! be moved. Read-only lookups ([`MetadataResolver::resolve`]) use it in place until then.
 
 use std::path::{Path, PathBuf};
 
+use directories::{BaseDirs, ProjectDirs};
+
+/// `workspace.cache_dir` when the root config does not set it
+const DEFAULT_CACHE_DIR: &str = ".cache/novelsaga";
+/// `workspace.cache_dir` prefix standing for the per-user cache directory
+const USER_CACHE_PREFIX: &str = "$cache";
+
 /// Configuration for metadata path resolution
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
@@ -20,6 +38,11 @@ pub struct ResolutionContext {
   pub show_target_parent: Option<PathBuf>,
   /// LSP startup directory
   pub lsp_startup_dir: Option<PathBuf>,
+  /// `workspace.cache_dir` of the effective root config (default `.cache/novelsaga`)
+  pub cache_dir: Option<String>,
+  /// Directory of the effective root config; `cache_dir` is resolved against
+  /// it instead of the workspace
+  pub config_root: Option<PathBuf>,
 }
 
 /// Resolves metadata storage paths
@@ -34,12 +57,43 @@ impl MetadataResolver {
   /// 1. If explicit `workspace_root` is provided → use it
   /// 2. Use first available context path (CLI target, CLI cwd, show parent, LSP startup)
   ///
+  /// For reading only: nothing is moved, and an index still at the legacy
+  /// default location is used in place while none exists at the resolved path.
+  ///
   /// # Returns
-  /// The canonical metadata path: `<workspace>/.cache/novelsaga/sled`
+  /// The sled index path of the workspace under `context.cache_dir`
   pub fn resolve(context: &ResolutionContext) -> Result<PathBuf, ResolverError> {
+    let (workspace, index_path) = Self::resolve_paths(context)?;
+    let legacy = Self::canonical_path(&workspace);
+    if !index_path.exists() && legacy.is_dir() {
+      return Ok(legacy);
+    }
+    Ok(index_path)
+  }
+
+  /// Resolve the metadata path for writing, first moving an index found at the
+  /// legacy default location there (see [`Self::migrate_legacy_index`]).
+  ///
+  /// # Returns
+  /// The sled index path of the workspace under `context.cache_dir`
+  pub fn resolve_for_write(context: &ResolutionContext) -> Result<PathBuf, ResolverError> {
+    let (workspace, index_path) = Self::resolve_paths(context)?;
+    Self::migrate_legacy_index(&workspace, &index_path);
+    Ok(index_path)
+  }
+
+  /// The workspace and its index path under `context.cache_dir`.
+  fn resolve_paths(context: &ResolutionContext) -> Result<(PathBuf, PathBuf), ResolverError> {
+    let workspace = Self::resolve_workspace(context)?;
+    let root = context.config_root.as_deref().unwrap_or(&workspace);
+    let index_path = Self::index_path(root, context.cache_dir.as_deref().unwrap_or(DEFAULT_CACHE_DIR))?;
+    Ok((workspace, index_path))
+  }
+
+  fn resolve_workspace(context: &ResolutionContext) -> Result<PathBuf, ResolverError> {
     // Priority 1: Explicit workspace root
     if let Some(ref workspace) = context.workspace_root {
-      return Ok(Self::canonical_path(workspace));
+      return Ok(workspace.clone());
     }
 
     let candidates = [
@@ -54,7 +108,7 @@ impl MetadataResolver {
         continue;
       };
 
-      return Ok(Self::canonical_path(&anchor));
+      return Ok(anchor);
     }
 
     // No valid paths found
@@ -75,10 +129,110 @@ impl MetadataResolver {
 
   /// Compute the canonical metadata path for a workspace.
   ///
+  /// This is the location under the default `cache_dir`, and where indexes
+  /// lived before `cache_dir` was honored.
+  ///
   /// # Returns
   /// `<workspace>/.cache/novelsaga/sled`
   pub fn canonical_path(workspace: &Path) -> PathBuf {
-    workspace.join(".cache/novelsaga/sled")
+    workspace.join(DEFAULT_CACHE_DIR).join("sled")
+  }
+
+  /// Compute the index path of `workspace` for a `workspace.cache_dir` value.
+  ///
+  /// # Errors
+  /// Returns `InvalidWorkspaceRoot` for an empty `cache_dir`, and `IoError`
+  /// when `~` or `$cache` is used but the home directory cannot be determined.
+  pub fn index_path(workspace: &Path, cache_dir: &str) -> Result<PathBuf, ResolverError> {
+    if cache_dir.trim().is_empty() {
+      return Err(ResolverError::InvalidWorkspaceRoot(
+        "workspace.cache_dir must not be empty".to_string(),
+      ));
+    }
+
+    let cache_dir = Self::expand_cache_dir(cache_dir)?;
+    if cache_dir.is_absolute() {
+      Ok(cache_dir.join(Self::workspace_key(workspace)).join("sled"))
+    } else {
+      Ok(workspace.join(cache_dir).join("sled"))
+    }
+  }
+
+  /// Expand the `~` and `$cache` prefixes of a `cache_dir` value.
+  fn expand_cache_dir(cache_dir: &str) -> Result<PathBuf, ResolverError> {
+    let no_home = || ResolverError::IoError(format!("Cannot expand `{cache_dir}`: home directory not found"));
+    let expand = |base: PathBuf, rest: &str| {
+      let rest = rest.trim_start_matches(['/', '\\']);
+      if rest.is_empty() { base } else { base.join(rest) }
+    };
+
+    if let Some(rest) = cache_dir.strip_prefix(USER_CACHE_PREFIX)
+      && (rest.is_empty() || rest.starts_with(['/', '\\']))
+    {
+      let dirs = ProjectDirs::from("rs", "novelsaga", "novelsaga").ok_or_else(no_home)?;
+      return Ok(expand(dirs.cache_dir().to_path_buf(), rest));
+    }
+    if let Some(rest) = cache_dir.strip_prefix('~')
+      && (rest.is_empty() || rest.starts_with(['/', '\\']))
+    {
+      let dirs = BaseDirs::new().ok_or_else(no_home)?;
+      return Ok(expand(dirs.home_dir().to_path_buf(), rest));
+    }
+    Ok(PathBuf::from(cache_dir))
+  }
+
+  /// Directory name of a workspace inside a shared cache directory:
+  /// `<workspace name>-<16 hex chars of the blake3 hash of its canonical path>`.
+  fn workspace_key(workspace: &Path) -> String {
+    let workspace = workspace.canonicalize().unwrap_or_else(|_| workspace.to_path_buf());
+    let hash = blake3::hash(workspace.to_string_lossy().as_bytes()).to_hex();
+    let name = workspace
+      .file_name()
+      .map(|name| name.to_string_lossy().into_owned())
+      .unwrap_or_default();
+    if name.is_empty() {
+      hash[..16].to_string()
+    } else {
+      format!("{name}-{}", &hash[..16])
+    }
+  }
+
+  /// Move an index left at the legacy default location to `index_path`.
+  ///
+  /// Nothing happens when both are the same or no legacy index exists. When
+  /// `index_path` already holds an index, or the move fails (e.g. across file
+  /// systems), the legacy index is kept and a warning names it.
+  pub fn migrate_legacy_index(workspace: &Path, index_path: &Path) {
+    let legacy = Self::canonical_path(workspace);
+    if legacy == index_path || !legacy.is_dir() {
+      return;
+    }
+
+    if index_path.exists() {
+      eprintln!(
+        "⚠️  Ignoring the old metadata index at {} (the index now lives at {}); it can be deleted",
+        legacy.display(),
+        index_path.display()
+      );
+      return;
+    }
+
+    let moved = index_path
+      .parent()
+      .map_or(Ok(()), std::fs::create_dir_all)
+      .and_then(|()| std::fs::rename(&legacy, index_path));
+    match moved {
+      Ok(()) => eprintln!(
+        "Moved the metadata index from {} to {}",
+        legacy.display(),
+        index_path.display()
+      ),
+      Err(error) => eprintln!(
+        "⚠️  Could not move the old metadata index at {} to {}: {error}; it will be rebuilt there",
+        legacy.display(),
+        index_path.display()
+      ),
+    }
   }
 }
 
@@ -129,6 +283,8 @@ mod tests {
       cli_cwd: None,
       show_target_parent: None,
       lsp_startup_dir: None,
+      cache_dir: None,
+      config_root: None,
     };
 
     let resolved = MetadataResolver::resolve(&context)?;
@@ -153,6 +309,8 @@ mod tests {
       cli_cwd: None,
       show_target_parent: None,
       lsp_startup_dir: None,
+      cache_dir: None,
+      config_root: None,
     };
 
     let resolved = MetadataResolver::resolve(&context)?;
@@ -174,6 +332,8 @@ mod tests {
       cli_cwd: Some(workspace_root.clone()),
       show_target_parent: None,
       lsp_startup_dir: None,
+      cache_dir: None,
+      config_root: None,
     };
 
     let resolved = MetadataResolver::resolve(&context)?;
@@ -195,6 +355,8 @@ mod tests {
       cli_cwd: None,
       show_target_parent: Some(workspace_root.clone()),
       lsp_startup_dir: None,
+      cache_dir: None,
+      config_root: None,
     };
 
     let resolved = MetadataResolver::resolve(&context)?;
@@ -216,6 +378,8 @@ mod tests {
       cli_cwd: None,
       show_target_parent: None,
       lsp_startup_dir: Some(workspace_root.clone()),
+      cache_dir: None,
+      config_root: None,
     };
 
     let resolved = MetadataResolver::resolve(&context)?;
@@ -240,6 +404,8 @@ mod tests {
       cli_cwd: None,
       show_target_parent: None,
       lsp_startup_dir: None,
+      cache_dir: None,
+      config_root: None,
     };
 
     let resolved = MetadataResolver::resolve(&context)?;
@@ -265,6 +431,130 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_relative_cache_dir_is_joined_to_workspace() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let workspace = temp_dir.path().to_path_buf();
+
+    let context = ResolutionContext {
+      workspace_root: Some(workspace.clone()),
+      cli_target_path: None,
+      cli_cwd: None,
+      show_target_parent: None,
+      lsp_startup_dir: None,
+      cache_dir: Some("build/index".to_string()),
+      config_root: None,
+    };
+
+    assert_eq!(MetadataResolver::resolve(&context)?, workspace.join("build/index/sled"));
+    Ok(())
+  }
+
+  #[test]
+  fn test_relative_cache_dir_is_joined_to_config_root() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let root = temp_dir.path().to_path_buf();
+
+    let context = ResolutionContext {
+      workspace_root: None,
+      cli_target_path: None,
+      cli_cwd: Some(root.join("book-01/chapters")),
+      show_target_parent: None,
+      lsp_startup_dir: None,
+      cache_dir: Some("build/index".to_string()),
+      config_root: Some(root.clone()),
+    };
+
+    assert_eq!(MetadataResolver::resolve(&context)?, root.join("build/index/sled"));
+    Ok(())
+  }
+
+  #[test]
+  fn test_absolute_cache_dir_is_keyed_by_workspace() -> Result<(), Box<dyn std::error::Error>> {
+    let cache = TempDir::new()?;
+    let first = TempDir::new()?;
+    let second = TempDir::new()?;
+    let cache_dir = cache.path().to_string_lossy().into_owned();
+
+    let first_path = MetadataResolver::index_path(first.path(), &cache_dir)?;
+    let second_path = MetadataResolver::index_path(second.path(), &cache_dir)?;
+
+    assert!(first_path.starts_with(cache.path()));
+    assert!(first_path.ends_with("sled"));
+    assert_ne!(
+      first_path, second_path,
+      "Workspaces must not share a shared-cache index"
+    );
+    assert_eq!(first_path, MetadataResolver::index_path(first.path(), &cache_dir)?);
+
+    let key = first_path.parent().and_then(Path::file_name).expect("workspace key");
+    let name = first.path().file_name().expect("name").to_string_lossy().into_owned();
+    assert!(key.to_string_lossy().starts_with(&format!("{name}-")));
+    Ok(())
+  }
+
+  #[test]
+  fn test_user_cache_and_home_prefixes_expand() -> Result<(), Box<dyn std::error::Error>> {
+    let workspace = PathBuf::from("/home/user/my-project");
+
+    let user_cache = MetadataResolver::index_path(&workspace, "$cache/indexes")?;
+    assert!(user_cache.is_absolute());
+    assert!(
+      user_cache
+        .parent()
+        .and_then(Path::parent)
+        .is_some_and(|dir| dir.ends_with("indexes"))
+    );
+
+    let home = MetadataResolver::index_path(&workspace, "~/.novelsaga")?;
+    assert!(home.is_absolute());
+
+    // Only whole prefixes expand
+    assert_eq!(
+      MetadataResolver::index_path(&workspace, "$cached")?,
+      workspace.join("$cached/sled")
+    );
+    assert!(MetadataResolver::index_path(&workspace, " ").is_err());
+    Ok(())
+  }
+
+  #[test]
+  fn test_legacy_index_is_migrated() -> Result<(), Box<dyn std::error::Error>> {
+    let temp_dir = TempDir::new()?;
+    let workspace = temp_dir.path().to_path_buf();
+    let legacy = MetadataResolver::canonical_path(&workspace);
+    std::fs::create_dir_all(&legacy)?;
+    std::fs::write(legacy.join("db"), "index")?;
+
+    let context = ResolutionContext {
+      workspace_root: Some(workspace.clone()),
+      cli_target_path: None,
+      cli_cwd: None,
+      show_target_parent: None,
+      lsp_startup_dir: None,
+      cache_dir: Some("out".to_string()),
+      config_root: None,
+    };
+    // Reading uses the legacy index in place
+    assert_eq!(MetadataResolver::resolve(&context)?, legacy);
+    assert!(legacy.exists());
+
+    let resolved = MetadataResolver::resolve_for_write(&context)?;
+
+    assert_eq!(resolved, workspace.join("out/sled"));
+    assert_eq!(std::fs::read_to_string(resolved.join("db"))?, "index");
+    assert!(!legacy.exists(), "Legacy index should have been moved");
+    assert_eq!(MetadataResolver::resolve(&context)?, resolved);
+
+    // An existing index at the new location is never overwritten
+    std::fs::create_dir_all(&legacy)?;
+    std::fs::write(legacy.join("db"), "stale")?;
+    MetadataResolver::resolve_for_write(&context)?;
+    assert_eq!(std::fs::read_to_string(resolved.join("db"))?, "index");
+    assert!(legacy.exists());
+    Ok(())
+  }
+
   #[test]
   fn test_no_valid_workspace_returns_error() {
     let context = ResolutionContext {
@@ -273,6 +563,8 @@ mod tests {
       cli_cwd: None,
       show_target_parent: None,
       lsp_startup_dir: None,
+      cache_dir: None,
+      config_root: None,
     };
 
     let result = MetadataResolver::resolve(&context);
@@ -294,6 +586,8 @@ mod tests {
       cli_cwd: None,
       show_target_parent: None,
       lsp_startup_dir: None,
+      cache_dir: None,
+      config_root: None,
     };
 
     let resolved = MetadataResolver::resolve(&context)?;
@@ -321,6 +615,8 @@ mod tests {
       cli_cwd: Some(workspace2.clone()),
       show_target_parent: None,
       lsp_startup_dir: None,
+      cache_dir: None,
+      config_root: None,
     };
 
     let resolved = MetadataResolver::resolve(&context)?;
@@ -344,6 +640,8 @@ mod tests {
       cli_cwd: None,
       show_target_parent: None,
       lsp_startup_dir: None,
+      cache_dir: None,
+      config_root: None,
     };
 
     let lsp_context = ResolutionContext {
@@ -352,6 +650,8 @@ mod tests {
       cli_cwd: None,
       show_target_parent: None,
       lsp_startup_dir: Some(workspace.clone()),
+      cache_dir: None,
+      config_root: None,
     };
 
     let cli_path = MetadataResolver::resolve(&cli_context)?;
@@ -1,10 +1,14 @@
-use std::{sync::Arc, time::Duration};
-
-use flume::{Receiver, Sender};
+use std::{
+  path::{Path, PathBuf},
+  sync::Arc,
+  thread,
+  time::{Duration, Instant},
+};
+
+use flume::{Receiver, RecvTimeoutError, Sender};
 use novelsaga_core::metadata::model::MetadataEntity;
-use tokio::time::interval;
 
-use crate::metadata::index::IndexManager;
+use crate::metadata::index::{FileState, IndexManager};
 
 /// Represents a write task to be processed by the worker
 #[derive(Debug, Clone)]
@@ -14,34 +18,66 @@ pub enum WriteTask {
   Upsert { id: String, data: Vec<u8> },
   /// Delete a metadata entity by ID
   Delete { id: String },
+  /// Index the entity parsed from a metadata file and record the file's state
+  IndexFile {
+    path: PathBuf,
+    entity: Box<MetadataEntity>,
+    state: FileState,
+  },
+  /// Record the state of a file whose content did not change
+  RecordFileState { path: PathBuf, state: FileState },
   /// Flush all pending writes to persistent storage
   Flush,
 }
 
+impl WriteTask {
+  /// Metadata file the task writes, if any
+  fn path(&self) -> Option<&Path> {
+    match self {
+      WriteTask::IndexFile { path, .. } | WriteTask::RecordFileState { path, .. } => Some(path),
+      WriteTask::Upsert { .. } | WriteTask::Delete { .. } | WriteTask::Flush => None,
+    }
+  }
+}
+
+/// A write task that could not be applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteFailure {
+  /// Metadata file of the failed task, if it wrote one
+  pub path: Option<PathBuf>,
+  pub message: String,
+}
+
 /// `WriteBackWorker` processes metadata write tasks in batches
+///
+/// Tasks are applied in submission order on a dedicated thread, so
+/// synchronous callers (batch indexing from the CLI or a blocking LSP task)
+/// can keep parsing while earlier batches are written. The index is only
+/// flushed by [`WriteTask::Flush`] or by its owner.
 #[allow(dead_code)]
 pub struct WriteBackWorker {
   task_sender: Sender<WriteTask>,
-  worker_handle: tokio::task::JoinHandle<()>,
+  worker_handle: thread::JoinHandle<Vec<WriteFailure>>,
 }
 
 #[allow(dead_code)]
 impl WriteBackWorker {
-  /// Creates a new `WriteBackWorker` and starts the background worker task
+  /// Creates a new `WriteBackWorker` and starts the background worker thread
   ///
   /// # Arguments
   ///
   /// * `index` - Shared `IndexManager` for persisting metadata
   /// * `batch_size` - Maximum number of tasks to batch before processing
-  /// * `flush_interval` - Time interval for flushing partial batches
+  /// * `flush_interval` - Time interval for processing partial batches
   ///
   /// # Returns
   ///
-  /// A new `WriteBackWorker` instance with a running background task
+  /// A new `WriteBackWorker` instance with a running background thread
   pub fn new(index: Arc<IndexManager>, batch_size: usize, flush_interval: Duration) -> Self {
     let (task_sender, task_receiver) = flume::unbounded();
 
-    let worker_handle = tokio::spawn(Self::run_worker(task_receiver, index, batch_size, flush_interval));
+    let worker_handle =
+      thread::spawn(move || Self::run_worker(&task_receiver, &index, batch_size.max(1), flush_interval));
 
     WriteBackWorker {
       task_sender,
@@ -51,101 +87,113 @@ impl WriteBackWorker {
 
   /// Submits a write task to the worker queue
   ///
+  /// A worker that died cannot take tasks; [`WriteBackWorker::finish`] reports it.
+  ///
   /// # Arguments
   ///
   /// * `task` - The `WriteTask` to be processed
   pub fn submit(&self, task: WriteTask) {
-    if let Err(e) = self.task_sender.send(task) {
-      eprintln!("Failed to submit task to WriteBackWorker: {e}");
-    }
+    let _ = self.task_sender.send(task);
+  }
+
+  /// Stops the worker once every submitted task has been applied
+  ///
+  /// # Returns
+  ///
+  /// The tasks that failed, in submission order
+  ///
+  /// # Errors
+  ///
+  /// Returns an error when the worker thread panicked
+  pub fn finish(self) -> Result<Vec<WriteFailure>, String> {
+    drop(self.task_sender);
+    self
+      .worker_handle
+      .join()
+      .map_err(|_| "Metadata write-back worker panicked".to_string())
   }
 
-  /// Background worker task that processes tasks in batches
+  /// Background worker loop that processes tasks in batches
   ///
-  /// Collects tasks and processes them either when the batch is full or when the flush timer fires.
-  /// Uses `tokio::select`! to handle both conditions.
-  async fn run_worker(
-    receiver: Receiver<WriteTask>,
-    index: Arc<IndexManager>,
+  /// Collects tasks and processes them either when the batch is full or when
+  /// the flush interval passes, until every sender is dropped.
+  fn run_worker(
+    receiver: &Receiver<WriteTask>,
+    index: &IndexManager,
     batch_size: usize,
     flush_interval: Duration,
-  ) {
+  ) -> Vec<WriteFailure> {
     let mut batch = Vec::with_capacity(batch_size);
-    let mut ticker = interval(flush_interval);
-    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let mut failures = Vec::new();
+    let mut deadline = Instant::now() + flush_interval;
 
     loop {
-      tokio::select! {
-          // Receive tasks from the channel
-          result = receiver.recv_async() => {
-              if let Ok(task) = result {
-                  batch.push(task);
-
-                  // Process batch if it's full
-                  if batch.len() >= batch_size {
-                      Self::process_batch(&batch, &index).await;
-                      batch.clear();
-                      ticker.reset();
-                  }
-              } else {
-                  // Channel closed, process remaining tasks and exit
-                  if !batch.is_empty() {
-                      Self::process_batch(&batch, &index).await;
-                  }
-                  break;
-              }
-          }
-
-          // Flush batch on timer
-          _ = ticker.tick() => {
-              if !batch.is_empty() {
-                  Self::process_batch(&batch, &index).await;
-                  batch.clear();
-              }
+      match receiver.recv_deadline(deadline) {
+        Ok(task) => {
+          batch.push(task);
+          // Process batch if it's full
+          if batch.len() >= batch_size {
+            Self::process_batch(&mut batch, index, &mut failures);
+            deadline = Instant::now() + flush_interval;
           }
+        }
+        Err(RecvTimeoutError::Timeout) => {
+          Self::process_batch(&mut batch, index, &mut failures);
+          deadline = Instant::now() + flush_interval;
+        }
+        Err(RecvTimeoutError::Disconnected) => {
+          // Channel closed, process remaining tasks and exit
+          Self::process_batch(&mut batch, index, &mut failures);
+          break;
+        }
       }
     }
+    failures
   }
 
-  /// Processes a batch of write tasks
+  /// Applies and drains a batch of write tasks in order
   ///
   /// # Arguments
   ///
-  /// * `batch` - Slice of tasks to process
+  /// * `batch` - Tasks to process
   /// * `index` - Reference to the `IndexManager`
-  #[allow(clippy::unused_async)]
-  async fn process_batch(batch: &[WriteTask], index: &IndexManager) {
-    for task in batch {
-      match task {
-        WriteTask::Upsert { id, data } => {
-          // Deserialize the data into a MetadataEntity
-          match serde_json::from_slice::<MetadataEntity>(data) {
-            Ok(entity) => {
-              if let Err(e) = index.index_entity(&entity) {
-                eprintln!("Failed to index entity {id}: {e}");
-              }
-            }
-            Err(e) => {
-              eprintln!("Failed to deserialize entity {id}: {e}");
-            }
-          }
-        }
-        WriteTask::Delete { id } => {
-          if let Err(e) = index.remove_entity(id) {
-            eprintln!("Failed to remove entity {id}: {e}");
-          }
-        }
-        WriteTask::Flush => {
-          if let Err(e) = index.flush() {
-            eprintln!("Failed to flush index: {e}");
-          }
-        }
+  /// * `failures` - Receives the tasks that failed
+  fn process_batch(batch: &mut Vec<WriteTask>, index: &IndexManager, failures: &mut Vec<WriteFailure>) {
+    for task in batch.drain(..) {
+      if let Err(message) = Self::apply_task(&task, index) {
+        failures.push(WriteFailure {
+          path: task.path().map(Path::to_path_buf),
+          message,
+        });
       }
     }
+  }
 
-    // Flush after processing batch
-    if let Err(e) = index.flush() {
-      eprintln!("Failed to flush index after batch: {e}");
+  fn apply_task(task: &WriteTask, index: &IndexManager) -> Result<(), String> {
+    match task {
+      WriteTask::Upsert { id, data } => {
+        // Deserialize the data into a MetadataEntity
+        let entity = serde_json::from_slice::<MetadataEntity>(data)
+          .map_err(|e| format!("Failed to deserialize entity {id}: {e}"))?;
+        index
+          .index_entity(&entity)
+          .map_err(|e| format!("Failed to index entity {id}: {e}"))
+      }
+      WriteTask::Delete { id } => index
+        .remove_entity(id)
+        .map_err(|e| format!("Failed to remove entity {id}: {e}")),
+      WriteTask::IndexFile { path, entity, state } => {
+        index
+          .index_entity(entity)
+          .map_err(|e| format!("Failed to index {}: {e}", path.display()))?;
+        index
+          .set_file_state(path, state)
+          .map_err(|e| format!("Failed to record index state of {}: {e}", path.display()))
+      }
+      WriteTask::RecordFileState { path, state } => index
+        .set_file_state(path, state)
+        .map_err(|e| format!("Failed to record index state of {}: {e}", path.display())),
+      WriteTask::Flush => index.flush().map_err(|e| format!("Failed to flush index: {e}")),
     }
   }
 }
@@ -210,8 +258,8 @@ mod tests {
       id: "test-id".to_string(),
     });
 
-    // Give worker time to process
-    tokio::time::sleep(Duration::from_millis(100)).await;
+    // Wait for the partial batch to be processed
+    worker.finish().expect("Worker panicked");
 
     // Verify entity was deleted
     let retrieved = index.get_by_id("test-id").expect("Failed to query index");
@@ -279,4 +327,36 @@ mod tests {
     let deleted_entity = index.get_by_id("delete-me").expect("Failed to query");
     assert!(deleted_entity.is_none());
   }
+
+  #[tokio::test]
+  async fn test_worker_finish_applies_queued_tasks_and_reports_failures() {
+    let temp_dir = TempDir::new().expect("Failed to create temp dir");
+    let index = Arc::new(IndexManager::open(temp_dir.path()).expect("Failed to open index"));
+
+    // Neither a full batch nor a flush interval: only `finish` drains the queue
+    let worker = WriteBackWorker::new(index.clone(), 10, Duration::from_secs(30));
+
+    let entity = MetadataEntity::new("test-id", "article", "blog", json!({"title": "Test"}), "Body");
+    let path = temp_dir.path().join("test.md");
+    worker.submit(WriteTask::Upsert {
+      id: "broken".to_string(),
+      data: b"not json".to_vec(),
+    });
+    worker.submit(WriteTask::IndexFile {
+      path: path.clone(),
+      entity: Box::new(entity),
+      state: FileState {
+        content_hash: "abc".to_string(),
+        mtime_ns: 1,
+        layout_fingerprint: String::new(),
+      },
+    });
+
+    let failures = worker.finish().expect("Worker panicked");
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].path, None);
+    assert!(failures[0].message.starts_with("Failed to deserialize entity broken"));
+    assert!(index.get_by_id("test-id").expect("Failed to query").is_some());
+    assert!(index.file_state(&path).expect("Failed to query").is_some());
+  }
 }
@@ -0,0 +1,66 @@
+//! 配置模块的错误
+//!
+//! 读取和反序列化单个配置文件的错误沿用 `config` crate 的 [`config::ConfigError`]；
+//! 这里是本模块自身的错误：加载某个路径的配置、合并配置、通过脚本加载器加载 JS/TS 配置、解析 frontmatter。
+
+use std::{error::Error as StdError, path::PathBuf};
+
+use crate::{error::ErrorCode, state::init::InitError};
+
+/// 配置模块的错误
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+  /// 配置文件被 ignore 文件排除，或位于根配置目录之外
+  #[error("ignored config file: {}", path.display())]
+  IgnoredFile { path: PathBuf },
+  /// 配置文件无法读取、解析或反序列化
+  #[error("failed to load config {}: {source}", path.display())]
+  Load {
+    path: PathBuf,
+    source: Box<config::ConfigError>,
+  },
+  /// 环境变量或命令行的配置覆盖无法应用到配置上
+  #[error("invalid config override: {source}")]
+  Override { source: Box<config::ConfigError> },
+  /// 合并覆盖配置失败
+  #[error("failed to merge config: {source}")]
+  Merge { source: serde_json::Error },
+  /// 加载 JS/TS 配置前尚未初始化全局状态
+  #[error("call Initializer::init() before loading script configs: {source}")]
+  StateUninitialized { source: InitError },
+  /// 当前环境没有提供该语言的脚本配置加载器
+  #[error("{language} config loader is not available")]
+  LoaderUnavailable { language: &'static str },
+  /// 脚本配置加载器执行失败
+  #[error("script config loader failed: {source}")]
+  Script { source: Box<dyn StdError + Send + Sync> },
+  /// 脚本配置的结果或合并后的配置无法序列化
+  #[error("failed to serialize config: {source}")]
+  Serialize { source: serde_json::Error },
+  /// markdown frontmatter 无法解析
+  #[error("invalid markdown frontmatter: {source}")]
+  Frontmatter { source: gray_matter::Error },
+}
+
+impl ConfigError {
+  /// 把脚本配置加载器的任意错误包装为 [`ConfigError::Script`]
+  pub fn script(source: impl Into<Box<dyn StdError + Send + Sync>>) -> Self {
+    ConfigError::Script { source: source.into() }
+  }
+}
+
+impl ErrorCode for ConfigError {
+  fn code(&self) -> &'static str {
+    match self {
+      ConfigError::IgnoredFile { .. } => "config.ignored_file",
+      ConfigError::Load { .. } => "config.load",
+      ConfigError::Override { .. } => "config.override",
+      ConfigError::Merge { .. } => "config.merge",
+      ConfigError::StateUninitialized { .. } => "config.state_uninitialized",
+      ConfigError::LoaderUnavailable { .. } => "config.loader_unavailable",
+      ConfigError::Script { .. } => "config.script",
+      ConfigError::Serialize { .. } => "config.serialize",
+      ConfigError::Frontmatter { .. } => "config.frontmatter",
+    }
+  }
+}
@@ -0,0 +1,303 @@
+//! 配置文件的 `extends`：先合并其他配置文件或内置预设，再合并本文件
+//!
+//! `extends` 中的路径相对于声明它的配置文件；与 [`PRESETS`] 同名的条目为内置预设。
+//! 被继承的文件可以继续 `extends`，出现环时报错。各图层通过 `merge_struct` 合并：
+//! 对象逐键合并、数组拼接、标量以后者为准。
+
+use std::path::{Path, PathBuf};
+
+use config::{ConfigBuilder, ConfigError, FileFormat, builder::DefaultState};
+use serde_json::{Map, Value};
+
+use super::file_def::{add_config_source, is_config_file};
+
+/// 内置预设：名称与 YAML 内容
+pub const PRESETS: &[(&str, &str)] = &[
+  (
+    "zh-CN-publishing",
+    "# 简体中文出版稿：段首缩进两个全角字符，段落紧排\nfmt:\n  indent_spaces: 4\n  blank_lines_between_paragraphs: 0\n",
+  ),
+  (
+    "zh-TW",
+    "# 繁體中文：段首縮排兩個全形字元，段落之間空一行\nfmt:\n  indent_spaces: 4\n  blank_lines_between_paragraphs: 1\n",
+  ),
+  (
+    "en-manuscript",
+    "# English manuscript: no first-line indent, blank line between paragraphs\nfmt:\n  indent_spaces: 0\n  blank_lines_between_paragraphs: 1\n",
+  ),
+];
+
+/// 一个图层的来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayerOrigin {
+  File(PathBuf),
+  Preset(&'static str),
+}
+
+/// 展开 `extends` 后的一个图层：原文（用于定位行号）与解析结果（已去掉 `extends`）
+#[derive(Debug, Clone)]
+pub struct ConfigLayer {
+  pub origin: LayerOrigin,
+  pub content: String,
+  pub value: Value,
+}
+
+/// `path` 及其 `extends` 链展开后的图层，优先级从低到高，`path` 本身在最后
+///
+/// 只有配置文件（`novelsaga.config.*`、`.novelsaga.*`）及其继承的文件会处理 `extends`；
+/// markdown frontmatter 原样作为单个图层。
+///
+/// # Errors
+///
+/// 文件无法读取或解析、`extends` 格式错误、找不到被继承的文件或预设、出现环时返回错误
+pub fn config_layers(path: &Path) -> Result<Vec<ConfigLayer>, ConfigError> {
+  let mut layers = Vec::new();
+  collect_layers(path, None, &mut Vec::new(), &mut layers)?;
+  Ok(layers)
+}
+
+/// 同 [`config_layers`]，但 `path` 本身的内容取 `content`（如编辑器中未保存的文本），被继承的文件仍从磁盘读取
+///
+/// # Errors
+///
+/// 同 [`config_layers`]
+pub fn config_layers_with_content(path: &Path, content: &str) -> Result<Vec<ConfigLayer>, ConfigError> {
+  let mut layers = Vec::new();
+  collect_layers(path, Some(content), &mut Vec::new(), &mut layers)?;
+  Ok(layers)
+}
+
+/// 按 `extends` 展开并合并 `path`，作为单个图层加入构建器
+///
+/// # Errors
+///
+/// 同 [`config_layers`]
+pub fn add_extended_config_source(
+  builder: ConfigBuilder<DefaultState>,
+  path: &Path,
+) -> Result<ConfigBuilder<DefaultState>, ConfigError> {
+  add_config_layers_source(builder, path, &config_layers(path)?)
+}
+
+/// 将 [`config_layers`] 展开的 `path` 的图层合并后加入构建器
+///
+/// # Errors
+///
+/// 图层无法合并时返回错误
+pub fn add_config_layers_source(
+  builder: ConfigBuilder<DefaultState>,
+  path: &Path,
+  layers: &[ConfigLayer],
+) -> Result<ConfigBuilder<DefaultState>, ConfigError> {
+  if let [layer] = layers
+    && !is_config_file(path)
+  {
+    // 没有 `extends` 可展开的文件直接交给原有加载入口
+    debug_assert_eq!(layer.origin, LayerOrigin::File(path.to_path_buf()));
+    return add_config_source(builder, path, Some(&layer.content));
+  }
+
+  let merged = merge_layers(layers)?;
+  let json = serde_json::to_string(&merged).map_err(|error| ConfigError::Foreign(Box::new(error)))?;
+  Ok(builder.add_source(config::File::from_str(&json, FileFormat::Json)))
+}
+
+/// 图层中经由 `extends` 读取的文件（不含最后一个图层，即被展开的文件本身），已规范化
+#[must_use]
+pub fn extended_files(layers: &[ConfigLayer]) -> Vec<PathBuf> {
+  let Some((_, extended)) = layers.split_last() else {
+    return Vec::new();
+  };
+  extended
+    .iter()
+    .filter_map(|layer| match &layer.origin {
+      LayerOrigin::File(path) => Some(path.canonicalize().unwrap_or_else(|_| path.clone())),
+      LayerOrigin::Preset(_) => None,
+    })
+    .collect()
+}
+
+fn merge_layers(layers: &[ConfigLayer]) -> Result<Value, ConfigError> {
+  layers.iter().try_fold(Value::Object(Map::new()), |merged, layer| {
+    merge_struct::merge(&merged, &layer.value).map_err(|error| ConfigError::Foreign(Box::new(error)))
+  })
+}
+
+fn collect_layers(
+  path: &Path,
+  content: Option<&str>,
+  chain: &mut Vec<PathBuf>,
+  out: &mut Vec<ConfigLayer>,
+) -> Result<(), ConfigError> {
+  let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+  if let Some(start) = chain.iter().position(|visited| *visited == canonical) {
+    let cycle: Vec<String> = chain[start..]
+      .iter()
+      .chain([&canonical])
+      .map(|path| path.display().to_string())
+      .collect();
+    return Err(ConfigError::Message(format!(
+      "Config extends cycle: {}",
+      cycle.join(" -> ")
+    )));
+  }
+
+  let content = match content {
+    Some(content) => content.to_string(),
+    None => std::fs::read_to_string(path)
+      .map_err(|error| ConfigError::Message(format!("Cannot read config file {}: {error}", path.display())))?,
+  };
+  let mut value = parse_layer(path, &content)?;
+  // 被继承的文件不论文件名都视为配置文件
+  let extends = if !chain.is_empty() || is_config_file(path) {
+    take_extends(&mut value, path)?
+  } else {
+    Vec::new()
+  };
+
+  chain.push(canonical);
+  let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+  for entry in extends {
+    if let Some((name, content)) = PRESETS.iter().find(|(name, _)| *name == entry) {
+      out.push(ConfigLayer {
+        origin: LayerOrigin::Preset(name),
+        content: (*content).to_string(),
+        value: parse_layer(Path::new("preset.yaml"), content)?,
+      });
+      continue;
+    }
+    if !entry.contains(['/', '\\', '.']) {
+      let available: Vec<&str> = PRESETS.iter().map(|(name, _)| *name).collect();
+      return Err(ConfigError::Message(format!(
+        "Unknown config preset `{entry}` in {} (available: {})",
+        path.display(),
+        available.join(", ")
+      )));
+    }
+    let extended = base_dir.join(&entry);
+    if !extended.is_file() {
+      return Err(ConfigError::Message(format!(
+        "Cannot find `{entry}` extended by {}",
+        path.display()
+      )));
+    }
+    collect_layers(&extended, None, chain, out)?;
+  }
+  chain.pop();
+
+  out.push(ConfigLayer {
+    origin: LayerOrigin::File(path.to_path_buf()),
+    content,
+    value,
+  });
+  Ok(())
+}
+
+fn parse_layer(path: &Path, content: &str) -> Result<Value, ConfigError> {
+  add_config_source(config::Config::builder(), path, Some(content))?
+    .build()?
+    .try_deserialize::<Value>()
+}
+
+/// 取出并移除 `extends`：字符串数组，也接受单个字符串
+fn take_extends(value: &mut Value, path: &Path) -> Result<Vec<String>, ConfigError> {
+  let invalid = || {
+    ConfigError::Message(format!(
+      "`extends` in {} must be a list of paths or preset names",
+      path.display()
+    ))
+  };
+  match value.as_object_mut().and_then(|map| map.remove("extends")) {
+    None | Some(Value::Null) => Ok(Vec::new()),
+    Some(Value::String(entry)) => Ok(vec![entry]),
+    Some(Value::Array(entries)) => entries
+      .into_iter()
+      .map(|entry| match entry {
+        Value::String(entry) => Ok(entry),
+        _ => Err(invalid()),
+      })
+      .collect(),
+    Some(_) => Err(invalid()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+
+  use tempfile::TempDir;
+
+  use super::{LayerOrigin, add_extended_config_source, config_layers, extended_files};
+  use crate::config::NovelSagaConfig;
+
+  fn write(root: &Path, relative: &str, content: &str) {
+    let path = root.join(relative);
+    std::fs::create_dir_all(path.parent().expect("parent")).expect("create dirs");
+    std::fs::write(path, content).expect("write");
+  }
+
+  fn load(path: &Path) -> Result<NovelSagaConfig, config::ConfigError> {
+    add_extended_config_source(config::Config::builder(), path)?
+      .build()?
+      .try_deserialize()
+  }
+
+  #[test]
+  fn extended_files_and_presets_merge_before_the_local_file() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    write(
+      root,
+      "house/style.yaml",
+      "extends: [zh-CN-publishing]\nworkspace:\n  exclude: [drafts/]\n",
+    );
+    write(
+      root,
+      "series/novelsaga.config.toml",
+      "extends = [\"../house/style.yaml\"]\n[fmt]\nblank_lines_between_paragraphs = 2\n[workspace]\nexclude = [\"notes/\"]\n",
+    );
+    let path = root.join("series/novelsaga.config.toml");
+
+    let origins: Vec<LayerOrigin> = config_layers(&path)
+      .expect("layers")
+      .into_iter()
+      .map(|layer| layer.origin)
+      .collect();
+    assert_eq!(
+      origins,
+      vec![
+        LayerOrigin::Preset("zh-CN-publishing"),
+        LayerOrigin::File(root.join("series/../house/style.yaml")),
+        LayerOrigin::File(path.clone()),
+      ]
+    );
+    let style = root.join("house/style.yaml").canonicalize().expect("canonical");
+    assert_eq!(extended_files(&config_layers(&path).expect("layers")), vec![style]);
+
+    let config = load(&path).expect("loads");
+    assert_eq!(config.overridable.fmt.indent_spaces, 4);
+    assert_eq!(config.overridable.fmt.blank_lines_between_paragraphs, 2);
+    assert_eq!(
+      config.root.workspace.expect("workspace").exclude,
+      vec!["drafts/", "notes/"]
+    );
+  }
+
+  #[test]
+  fn reports_cycles_missing_files_and_unknown_presets() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    write(root, "novelsaga.config.yaml", "extends: [./a/.novelsaga.yaml]\n");
+    write(root, "a/.novelsaga.yaml", "extends: [../novelsaga.config.yaml]\n");
+    let error = load(&root.join("novelsaga.config.yaml")).expect_err("cycle");
+    assert!(error.to_string().starts_with("Config extends cycle: "), "{error}");
+
+    write(root, "b/.novelsaga.yaml", "extends: [./missing.yaml]\n");
+    let error = load(&root.join("b/.novelsaga.yaml")).expect_err("missing");
+    assert!(error.to_string().contains("Cannot find `./missing.yaml`"), "{error}");
+
+    write(root, "c/.novelsaga.yaml", "extends: [ja-JP]\n");
+    let error = load(&root.join("c/.novelsaga.yaml")).expect_err("unknown preset");
+    assert!(error.to_string().contains("Unknown config preset `ja-JP`"), "{error}");
+  }
+}
@@ -1,34 +1,95 @@
-use std::sync::OnceLock;
+use std::{path::Path, sync::OnceLock};
 
-use config::FileStoredFormat;
+use config::{ConfigBuilder, ConfigError, FileFormat, FileStoredFormat, builder::DefaultState};
+
+use super::fileformat::NovelSagaFileFormat;
+
+/// 可由 `config` 直接加载的配置文件格式
+pub const BASE_CONFIG_FILE_FORMATS: [FileFormat; 7] = [
+  FileFormat::Corn,
+  FileFormat::Ron,
+  FileFormat::Toml,
+  FileFormat::Yaml,
+  FileFormat::Json5,
+  FileFormat::Json,
+  FileFormat::Ini,
+];
 
 static BASE_CONFIG_FILE_EXTENSIONS: OnceLock<&'static [&'static str]> = OnceLock::new();
 
-/// # Panics
 pub fn get_base_config_file_extensions() -> &'static [&'static str] {
   BASE_CONFIG_FILE_EXTENSIONS.get_or_init(|| {
-    use config::FileFormat;
-    let file_formats = [
-      FileFormat::Corn,
-      FileFormat::Ron,
-      FileFormat::Toml,
-      FileFormat::Yaml,
-      FileFormat::Json5,
-      FileFormat::Json,
-      FileFormat::Ini,
-    ];
     // map to extensions
-    let extensions: Vec<&'static str> = file_formats
+    let extensions: Vec<&'static str> = BASE_CONFIG_FILE_FORMATS
       .iter()
       .flat_map(FileStoredFormat::file_extensions)
       .copied()
       .collect();
     Box::leak(extensions.into_boxed_slice())
-  });
-  BASE_CONFIG_FILE_EXTENSIONS.get().unwrap()
+  })
 }
 
 /// 配置文件名(不含扩展名)
 pub const CONFIG_FILE_NAMES: &[&str] = &["novelsaga.config", ".novelsaga"];
 
 pub const IGNORE_CONFIG_FILE_NAMES: &[&str] = &[".novelsagaignore", ".nsignore", ".novelsaga.ignore", ".ns.ignore"];
+
+/// 扩展名对应的基础配置文件格式
+#[must_use]
+pub fn base_config_file_format(extension: &str) -> Option<FileFormat> {
+  BASE_CONFIG_FILE_FORMATS
+    .into_iter()
+    .find(|format| format.file_extensions().contains(&extension))
+}
+
+/// 文件名（不含扩展名）是否为配置文件名；ignore 文件（如 `.novelsaga.ignore`）除外
+#[must_use]
+pub fn is_config_file(path: &Path) -> bool {
+  let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+    return false;
+  };
+  if IGNORE_CONFIG_FILE_NAMES.contains(&file_name) {
+    return false;
+  }
+
+  path
+    .file_stem()
+    .and_then(|stem| stem.to_str())
+    .is_some_and(|stem| CONFIG_FILE_NAMES.contains(&stem))
+}
+
+/// 按扩展名选择格式，把配置文件加入构建器；`content` 为 `None` 时从磁盘读取
+///
+/// `ConfigManager` 加载与编辑器中未保存内容的校验共用这一入口。
+///
+/// # Errors
+///
+/// 扩展名不受支持时返回错误
+pub fn add_config_source(
+  builder: ConfigBuilder<DefaultState>,
+  path: &Path,
+  content: Option<&str>,
+) -> Result<ConfigBuilder<DefaultState>, ConfigError> {
+  let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+  let name = path.to_string_lossy();
+  if let Some(format) = base_config_file_format(ext) {
+    // JSON 等可被 `config` 直接加载的文件
+    return Ok(match content {
+      Some(content) => builder.add_source(config::File::from_str(content, format)),
+      None => builder.add_source(config::File::new(&name, format)),
+    });
+  }
+  // md 取 frontmatter，JS/TS 通过全局 loader 求值
+  let format = [
+    NovelSagaFileFormat::Markdown,
+    NovelSagaFileFormat::JavaScript,
+    NovelSagaFileFormat::TypeScript,
+  ]
+  .into_iter()
+  .find(|format| format.get_extensions().contains(&ext))
+  .ok_or_else(|| ConfigError::Message(format!("Unsupported config file extension: {ext}")))?;
+  Ok(match content {
+    Some(content) => builder.add_source(config::File::from_str(content, format)),
+    None => builder.add_source(config::File::new(&name, format)),
+  })
+}
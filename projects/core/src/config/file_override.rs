@@ -0,0 +1,155 @@
+//! 根配置中按 glob 限定范围的覆盖段（`overrides`），类似 `EditorConfig` 的 section
+//!
+//! ```yaml
+//! overrides:
+//!   - files: drafts/**
+//!     fmt:
+//!       indent_spaces: 0
+//! ```
+//!
+//! 按声明顺序应用在目录级联配置之后、文件自身的 frontmatter 之前。
+
+use std::path::Path;
+
+use ignore::overrides::{Override, OverrideBuilder};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use ts_rs::TS;
+
+use super::OverridableConfig;
+
+/// 一个或多个 glob
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export_to = "_file_patterns.ts")]
+#[serde(untagged)]
+pub enum FilePatterns {
+  One(String),
+  Many(Vec<String>),
+}
+
+impl FilePatterns {
+  #[must_use]
+  pub fn as_slice(&self) -> &[String] {
+    match self {
+      FilePatterns::One(pattern) => std::slice::from_ref(pattern),
+      FilePatterns::Many(patterns) => patterns,
+    }
+  }
+}
+
+/// 根配置 `overrides` 中的一段：`files` 匹配的文件使用其余字段覆盖配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(as = "FileOverrideBinding", export_to = "_file_override.ts")]
+#[schemars(deny_unknown_fields)]
+pub struct FileOverride {
+  /// 相对于根配置目录的 gitignore 风格 glob，如 `drafts/**`
+  pub files: FilePatterns,
+  /// 只包含本段写出的配置项，未写出的项保留级联结果
+  #[serde(flatten)]
+  #[schemars(with = "OverridableConfig")]
+  pub config: Map<String, Value>,
+}
+
+/// [`FileOverride`] 的 TypeScript 类型：`config` 按 [`OverridableConfig`] 展开
+#[derive(TS)]
+#[ts(rename = "FileOverride")]
+#[allow(dead_code)]
+struct FileOverrideBinding {
+  files: FilePatterns,
+  #[ts(flatten)]
+  config: OverridableConfig,
+}
+
+/// 编译后的覆盖段
+#[derive(Debug, Clone)]
+pub struct FileOverrideMatcher {
+  /// 在 `overrides` 中的下标
+  pub index: usize,
+  pub files: FilePatterns,
+  pub config: Value,
+  globs: Override,
+}
+
+impl FileOverrideMatcher {
+  /// 编译 `overrides`，glob 相对于 `root`
+  ///
+  /// # Errors
+  ///
+  /// glob 无效或某段配置的值类型不符时返回错误
+  pub fn compile(root: &Path, overrides: &[FileOverride]) -> Result<Vec<Self>, config::ConfigError> {
+    overrides
+      .iter()
+      .enumerate()
+      .map(|(index, file_override)| {
+        let invalid = |message: String| config::ConfigError::Message(format!("overrides[{index}]: {message}"));
+        let mut builder = OverrideBuilder::new(root);
+        for pattern in file_override.files.as_slice() {
+          builder.add(pattern).map_err(|error| invalid(error.to_string()))?;
+        }
+        let config = Value::Object(file_override.config.clone());
+        // 提前检查类型，避免到加载某个文件时才报错
+        config::Config::try_from(&config)
+          .and_then(config::Config::try_deserialize::<OverridableConfig>)
+          .map_err(|error| invalid(error.to_string()))?;
+        Ok(Self {
+          index,
+          files: file_override.files.clone(),
+          config,
+          globs: builder.build().map_err(|error| invalid(error.to_string()))?,
+        })
+      })
+      .collect()
+  }
+
+  /// `path`（或其某级父目录）是否匹配 `files`
+  #[must_use]
+  pub fn matches(&self, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(self.globs.path()) else {
+      return false;
+    };
+    relative
+      .ancestors()
+      .filter(|ancestor| !ancestor.as_os_str().is_empty())
+      .any(|ancestor| self.globs.matched(ancestor, ancestor != relative).is_whitelist())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+
+  use serde_json::json;
+
+  use super::{FileOverride, FileOverrideMatcher};
+
+  fn compile(overrides: serde_json::Value) -> Result<Vec<FileOverrideMatcher>, config::ConfigError> {
+    let overrides: Vec<FileOverride> = serde_json::from_value(overrides).expect("valid overrides");
+    FileOverrideMatcher::compile(Path::new("/book"), &overrides)
+  }
+
+  #[test]
+  fn matches_files_and_directories_relative_to_the_root() {
+    let matchers = compile(json!([
+      { "files": "drafts/**", "fmt": { "indent_spaces": 0 } },
+      { "files": ["*.txt", "notes/"] },
+    ]))
+    .expect("compiles");
+    assert!(matchers[0].matches(Path::new("/book/drafts/ch1.md")));
+    assert!(!matchers[0].matches(Path::new("/book/ch1.md")));
+    assert!(matchers[1].matches(Path::new("/book/a/b.txt")));
+    assert!(matchers[1].matches(Path::new("/book/notes/idea.md")));
+    assert!(!matchers[1].matches(Path::new("/elsewhere/notes/idea.md")));
+    assert_eq!(matchers[0].config["fmt"]["indent_spaces"], 0);
+  }
+
+  #[test]
+  fn rejects_invalid_values_with_the_section_index() {
+    let error = compile(json!([
+      { "files": "drafts/**" },
+      { "files": "a/**", "fmt": { "indent_spaces": "wide" } },
+    ]))
+    .expect_err("invalid");
+    assert!(error.to_string().starts_with("overrides[1]: "), "{error}");
+  }
+}
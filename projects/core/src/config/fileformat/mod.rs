@@ -3,7 +3,13 @@ use std::{collections::HashMap, error::Error};
 use config::{FileFormat, FileStoredFormat, Format};
 use gray_matter::{Matter, Pod};
 
-use crate::state::init::Initializer;
+use crate::{
+  config::error::ConfigError,
+  state::{
+    feat::{Feature, LoaderFn},
+    init::Initializer,
+  },
+};
 
 /// File extension constants for different file formats
 const MARKDOWN_EXTENSIONS: &[&str] = &["md", "markdown"];
@@ -35,6 +41,28 @@ impl FileStoredFormat for NovelSagaFileFormat {
   }
 }
 
+impl NovelSagaFileFormat {
+  /// 通过脚本加载器求值 JS/TS 配置，返回 JSON 文本
+  ///
+  /// 加载器取自 [`Feature::scope`] 设置的 Feature，未设置时取自全局状态。
+  fn load_script(
+    language: &'static str,
+    text: &str,
+    loader: fn(&Feature) -> Option<&LoaderFn>,
+  ) -> Result<String, ConfigError> {
+    let loader = if let Some(feature) = Feature::scoped() {
+      loader(&feature).cloned()
+    } else {
+      let state = Initializer::get().map_err(|source| ConfigError::StateUninitialized { source })?;
+      loader(state.feature()).cloned()
+    };
+    let loader = loader.ok_or(ConfigError::LoaderUnavailable { language })?;
+    let map = loader(text)?;
+    serde_json::to_string(&map).map_err(|source| ConfigError::Serialize { source })
+  }
+}
+
+/// 错误均为 [`ConfigError`]（或 `config` 自带格式的解析错误），可通过 `downcast_ref` 取回
 impl Format for NovelSagaFileFormat {
   fn parse(
     &self,
@@ -46,29 +74,17 @@ impl Format for NovelSagaFileFormat {
         use gray_matter::engine::YAML;
         let matter = Matter::<YAML>::new();
         // 使用 Pod 类型（gray_matter 的默认类型），我们只需要 parsed.matter（原始 YAML 字符串）
-        let parsed = matter.parse::<Pod>(text)?;
+        let parsed = matter
+          .parse::<Pod>(text)
+          .map_err(|source| ConfigError::Frontmatter { source })?;
         FileFormat::Yaml.parse(uri, &parsed.matter)
       }
       NovelSagaFileFormat::JavaScript => {
-        // 通过全局单例获取 loader
-        let state = Initializer::get().map_err(|_| "Global state not initialized. Call Initializer::init() first.")?;
-
-        let loader = state.feature().js_loader().ok_or("JavaScript loader not available")?;
-
-        let map = loader(text)?;
-        let json_str = serde_json::to_string(&map).map_err(|e| Box::new(e) as Box<dyn Error + Send + Sync>)?;
-
+        let json_str = Self::load_script("JavaScript", text, Feature::js_loader)?;
         FileFormat::Json.parse(uri, &json_str)
       }
       NovelSagaFileFormat::TypeScript => {
-        // 通过全局单例获取 loader
-        let state = Initializer::get().map_err(|_| "Global state not initialized. Call Initializer::init() first.")?;
-
-        let loader = state.feature().ts_loader().ok_or("TypeScript loader not available")?;
-
-        let map = loader(text)?;
-        let json_str = serde_json::to_string(&map).map_err(|e| Box::new(e) as Box<dyn Error + Send + Sync>)?;
-
+        let json_str = Self::load_script("TypeScript", text, Feature::ts_loader)?;
         FileFormat::Json.parse(uri, &json_str)
       }
     }
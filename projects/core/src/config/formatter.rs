@@ -1,12 +1,16 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
 /// 格式化配置
-#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
 #[ts(export_to = "_format_config.ts")]
 #[serde(default)]
+#[schemars(deny_unknown_fields)]
 pub struct FormatConfig {
+  /// 段首缩进的空格数
   pub indent_spaces: usize,
+  /// 段落之间的空行数
   pub blank_lines_between_paragraphs: usize,
 }
 
@@ -0,0 +1,83 @@
+//! 元数据目录布局：哪些目录存放元数据，目录名如何映射为元数据类型，各类型的 frontmatter schema
+
+use std::collections::BTreeMap;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use ts_rs::TS;
+
+use crate::metadata::schema::SCHEMA_DIR;
+
+/// 元数据目录布局配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, TS, JsonSchema)]
+#[ts(export_to = "_metadata_config.ts")]
+#[serde(default)]
+#[schemars(deny_unknown_fields)]
+pub struct MetadataConfig {
+  /// 元数据目录名；路径中任一级目录与之同名的 markdown 文件为元数据，如 `设定`
+  pub dirs: Vec<String>,
+  /// 目录名到元数据类型的映射，如 `人物: character`；未列出的目录按英文复数规则推断
+  pub folder_types: BTreeMap<String, String>,
+  /// 元数据类型列表；非空时，由目录推断出的类型不在列表中则记为 `metadata`
+  pub types: Vec<String>,
+  /// 各元数据类型 frontmatter 的 JSON Schema，如 `character: { required: [name] }`；
+  /// 也可写在元数据目录下的 `_schema/<类型>.yaml`，同一类型以此处为准
+  #[ts(type = "Record<string, Record<string, any>>")]
+  pub schemas: BTreeMap<String, Value>,
+}
+
+impl Default for MetadataConfig {
+  fn default() -> Self {
+    Self {
+      dirs: vec!["metadata".to_string()],
+      folder_types: BTreeMap::from([
+        ("characters".to_string(), "character".to_string()),
+        ("scenes".to_string(), "scene".to_string()),
+      ]),
+      types: Vec::new(),
+      schemas: BTreeMap::new(),
+    }
+  }
+}
+
+impl MetadataConfig {
+  /// `name` 是否为元数据目录名
+  #[must_use]
+  pub fn is_metadata_dir(&self, name: &str) -> bool {
+    self.dirs.iter().any(|dir| dir == name)
+  }
+
+  /// `type_` 是否为允许的类型（未配置类型列表时都允许）
+  #[must_use]
+  pub fn is_known_type(&self, type_: &str) -> bool {
+    self.types.is_empty() || self.types.iter().any(|known| known == type_)
+  }
+
+  /// 匹配所有元数据文件的 glob，每个元数据目录一个，如 `**/metadata/**/*.md`
+  #[must_use]
+  pub fn watch_globs(&self) -> Vec<String> {
+    self.dirs.iter().map(|dir| format!("**/{dir}/**/*.md")).collect()
+  }
+
+  /// 匹配所有 frontmatter schema 文件的 glob，每个元数据目录一个，如 `**/metadata/_schema/*`
+  #[must_use]
+  pub fn schema_watch_globs(&self) -> Vec<String> {
+    self.dirs.iter().map(|dir| format!("**/{dir}/{SCHEMA_DIR}/*")).collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::MetadataConfig;
+
+  #[test]
+  fn default_layout_matches_the_metadata_directory() {
+    let config = MetadataConfig::default();
+    assert!(config.is_metadata_dir("metadata"));
+    assert!(!config.is_metadata_dir("设定"));
+    assert!(config.is_known_type("anything"));
+    assert_eq!(config.watch_globs(), vec!["**/metadata/**/*.md"]);
+    assert_eq!(config.schema_watch_globs(), vec!["**/metadata/_schema/*"]);
+  }
+}
@@ -1,16 +1,23 @@
+pub mod error;
+pub mod extends;
 pub mod file_def;
+pub mod file_override;
 pub mod fileformat;
 pub mod formatter;
+pub mod metadata;
+pub mod overrides;
+pub mod schema;
 pub mod workspace;
 // pub mod manager;
 
 use merge_struct::merge;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
 pub trait Config {}
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS, JsonSchema)]
 #[ts(export_to = "_overridable_config.ts")]
 #[serde(default)]
 pub struct OverridableConfig {
@@ -19,17 +26,27 @@ pub struct OverridableConfig {
 
 impl Config for OverridableConfig {}
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default, TS)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, TS, JsonSchema)]
 #[ts(export_to = "_root_config.ts")]
 #[serde(default)]
 pub struct RootConfig {
   pub workspace: Option<workspace::WorkspaceConfig>,
+
+  /// 元数据目录布局：元数据目录名、目录到类型的映射、类型列表与各类型的 frontmatter schema
+  #[ts(optional, as = "Option<metadata::MetadataConfig>")]
+  pub metadata: metadata::MetadataConfig,
+
+  /// 按 glob 限定范围的覆盖段，按声明顺序应用在目录级联配置之后、frontmatter 之前
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  #[ts(optional, as = "Option<Vec<file_override::FileOverride>>")]
+  pub overrides: Vec<file_override::FileOverride>,
 }
 
 impl RootConfig {
   #[must_use]
   pub fn to_novelsaga_config(&self) -> NovelSagaConfig {
     NovelSagaConfig {
+      extends: Vec::new(),
       root: self.clone(),
       overridable: OverridableConfig::default(),
     }
@@ -38,10 +55,16 @@ impl RootConfig {
 
 impl Config for RootConfig {}
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, TS)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, TS, JsonSchema)]
 #[ts(export_to = "_novelsaga_config.ts", rename = "_NovelSagaConfig")]
 #[serde(default)]
+#[schemars(deny_unknown_fields)]
 pub struct NovelSagaConfig {
+  /// 先于本文件合并的配置：相对于本文件的路径，或内置预设名（如 `zh-CN-publishing`）
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  #[ts(optional, as = "Option<Vec<String>>")]
+  pub extends: Vec<String>,
+
   #[serde(flatten)]
   pub root: RootConfig,
 
@@ -53,6 +76,7 @@ impl NovelSagaConfig {
   #[must_use]
   pub fn from_root_and_override(root: Option<RootConfig>, overridable: Option<OverridableConfig>) -> Self {
     Self {
+      extends: Vec::new(),
       root: root.unwrap_or_default(),
       overridable: overridable.unwrap_or_default(),
     }
@@ -60,15 +84,15 @@ impl NovelSagaConfig {
 
   /// Merge this config with another overridable config.
   ///
-  /// # Panics
+  /// # Errors
   ///
-  /// Panics if the underlying merge operation fails (propagated from `merge_struct::merge`).
-  #[must_use]
-  pub fn merge(&self, other: &OverridableConfig) -> Self {
-    Self {
+  /// Returns [`error::ConfigError::Merge`] if the underlying `merge_struct::merge` fails.
+  pub fn merge(&self, other: &OverridableConfig) -> Result<Self, error::ConfigError> {
+    Ok(Self {
+      extends: self.extends.clone(),
       root: self.root.clone(),
-      overridable: merge(&self.overridable, other).expect("failed to merge OverridableConfig"),
-    }
+      overridable: merge(&self.overridable, other).map_err(|source| error::ConfigError::Merge { source })?,
+    })
   }
 }
 
@@ -88,6 +112,9 @@ mod tests {
 
     formatter::FormatConfig::export().expect("failed to export FormatConfig");
     workspace::WorkspaceConfig::export().expect("failed to export WorkspaceConfig");
+    metadata::MetadataConfig::export().expect("failed to export MetadataConfig");
+    file_override::FilePatterns::export().expect("failed to export FilePatterns");
+    file_override::FileOverride::export().expect("failed to export FileOverride");
     RootConfig::export().expect("failed to export RootConfig");
     OverridableConfig::export().expect("failed to export OverridableConfig");
     NovelSagaConfig::export().expect("failed to export NovelSagaConfig");
@@ -0,0 +1,306 @@
+//! 配置文件之外的覆盖来源：环境变量（`NOVELSAGA__FMT__INDENT_SPACES=0`）与命令行
+//! （`--config fmt.indent_spaces=0`），优先级高于所有配置文件，命令行高于环境变量。
+//!
+//! 覆盖项在创建时即按 schema 校验键名并转换类型，错误指明来源变量或参数。
+
+use std::fmt;
+
+use serde::{Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
+
+use super::{NovelSagaConfig, schema::config_key_doc};
+
+/// 环境变量前缀；其后以 `__` 分隔键名各段
+pub const CONFIG_ENV_PREFIX: &str = "NOVELSAGA__";
+
+/// 一个覆盖项的来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OverrideOrigin {
+  /// 环境变量名
+  Env(String),
+  /// `--config` 参数原文
+  Cli(String),
+}
+
+impl fmt::Display for OverrideOrigin {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      OverrideOrigin::Env(name) => write!(f, "environment variable {name}"),
+      OverrideOrigin::Cli(arg) => write!(f, "--config {arg}"),
+    }
+  }
+}
+
+/// 单个覆盖项：点分键名与已按 schema 转换类型的值
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigOverride {
+  pub key: String,
+  pub value: Value,
+  pub origin: OverrideOrigin,
+}
+
+/// 覆盖项无效：键名未知、格式错误或值无法转换为该配置项的类型
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{origin}: {message}")]
+pub struct ConfigOverrideError {
+  pub origin: OverrideOrigin,
+  pub message: String,
+}
+
+/// 按优先级从低到高排列的覆盖项
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigOverrides {
+  overrides: Vec<ConfigOverride>,
+}
+
+impl ConfigOverrides {
+  /// 从环境变量（只取 [`CONFIG_ENV_PREFIX`] 开头的）与 `--config key=value` 参数构建
+  ///
+  /// # Errors
+  ///
+  /// 任一覆盖项键名未知或值的类型不符时返回错误
+  pub fn from_sources(
+    env: impl IntoIterator<Item = (String, String)>,
+    cli: &[String],
+  ) -> Result<Self, ConfigOverrideError> {
+    Self::build(env, cli, false).map(|(overrides, _)| overrides)
+  }
+
+  /// 同 [`Self::from_sources`]，但跳过无效的环境变量，连同其错误一起返回
+  ///
+  /// 供不应因环境中无关变量而退出的命令（如 LSP）使用；`--config` 参数仍须有效。
+  ///
+  /// # Errors
+  ///
+  /// 任一 `--config` 参数键名未知或值的类型不符时返回错误
+  pub fn from_sources_skipping_invalid_env(
+    env: impl IntoIterator<Item = (String, String)>,
+    cli: &[String],
+  ) -> Result<(Self, Vec<ConfigOverrideError>), ConfigOverrideError> {
+    Self::build(env, cli, true)
+  }
+
+  fn build(
+    env: impl IntoIterator<Item = (String, String)>,
+    cli: &[String],
+    skip_invalid_env: bool,
+  ) -> Result<(Self, Vec<ConfigOverrideError>), ConfigOverrideError> {
+    let mut env: Vec<(String, String)> = env
+      .into_iter()
+      .filter(|(name, _)| name.starts_with(CONFIG_ENV_PREFIX))
+      .collect();
+    // 环境变量的遍历顺序不固定，排序保证同一键的多种写法结果确定
+    env.sort();
+
+    let mut overrides = Vec::new();
+    let mut skipped = Vec::new();
+    for (name, raw) in env {
+      let key = name[CONFIG_ENV_PREFIX.len()..]
+        .split("__")
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(".");
+      match ConfigOverride::parse(key, &raw, OverrideOrigin::Env(name)) {
+        Ok(config_override) => overrides.push(config_override),
+        Err(error) if skip_invalid_env => skipped.push(error),
+        Err(error) => return Err(error),
+      }
+    }
+    for arg in cli {
+      let origin = OverrideOrigin::Cli(arg.clone());
+      let Some((key, raw)) = arg.split_once('=') else {
+        return Err(ConfigOverrideError {
+          origin,
+          message: "expected KEY=VALUE".to_string(),
+        });
+      };
+      overrides.push(ConfigOverride::parse(key.trim().to_string(), raw, origin)?);
+    }
+    Ok((Self { overrides }, skipped))
+  }
+
+  #[must_use]
+  pub fn is_empty(&self) -> bool {
+    self.overrides.is_empty()
+  }
+
+  /// 把覆盖项叠加到 `config` 上；只应用 `config` 中存在的顶层分组
+  ///
+  /// # Errors
+  ///
+  /// 叠加后无法反序列化回 `T` 时返回错误（创建时已逐项校验，正常不会发生）
+  pub fn apply<T: Serialize + DeserializeOwned + Clone>(&self, config: &T) -> Result<T, config::ConfigError> {
+    if self.overrides.is_empty() {
+      return Ok(config.clone());
+    }
+    let mut value = serde_json::to_value(config).map_err(config_error)?;
+    for config_override in self.applicable(&value) {
+      set_key(&mut value, &config_override.key, config_override.value.clone());
+    }
+    serde_json::from_value(value).map_err(config_error)
+  }
+
+  /// 顶层分组存在于 `value` 中的覆盖项
+  pub(crate) fn applicable(&self, value: &Value) -> Vec<&ConfigOverride> {
+    self
+      .overrides
+      .iter()
+      .filter(|config_override| {
+        let section = config_override.key.split('.').next().unwrap_or_default();
+        value.get(section).is_some()
+      })
+      .collect()
+  }
+}
+
+impl ConfigOverride {
+  /// 按 schema 中该键的类型转换 `raw`，并确认能写入配置
+  fn parse(key: String, raw: &str, origin: OverrideOrigin) -> Result<Self, ConfigOverrideError> {
+    let error = |message: String| ConfigOverrideError {
+      origin: origin.clone(),
+      message,
+    };
+    let doc = config_key_doc(&key).ok_or_else(|| error(format!("unknown config key `{key}`")))?;
+    let raw = raw.trim();
+    let value = match doc.value_type.as_str() {
+      "integer" => raw
+        .parse::<i64>()
+        .map(Value::from)
+        .map_err(|_| error(format!("`{key}` expects an integer, got `{raw}`")))?,
+      "number" => raw
+        .parse::<f64>()
+        .map(Value::from)
+        .map_err(|_| error(format!("`{key}` expects a number, got `{raw}`")))?,
+      "boolean" => match raw.to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Value::Bool(true),
+        "false" | "0" | "no" | "off" => Value::Bool(false),
+        _ => return Err(error(format!("`{key}` expects true or false, got `{raw}`"))),
+      },
+      "array of string" if raw.starts_with('[') => serde_json::from_str::<Vec<String>>(raw)
+        .map(Value::from)
+        .map_err(|parse_error| error(format!("`{key}` expects a JSON array of strings: {parse_error}")))?,
+      "array of string" => Value::from(
+        raw
+          .split(',')
+          .map(str::trim)
+          .filter(|item| !item.is_empty())
+          .collect::<Vec<_>>(),
+      ),
+      "object" => {
+        return Err(error(format!(
+          "`{key}` is a section; override one of its keys (e.g. `{key}.<name>`)"
+        )));
+      }
+      _ => Value::String(raw.to_string()),
+    };
+
+    // 类型之外的约束（如非负整数）由配置类型本身检查
+    let mut defaults = serde_json::to_value(NovelSagaConfig::default()).map_err(|e| error(e.to_string()))?;
+    set_key(&mut defaults, &key, value.clone());
+    serde_json::from_value::<NovelSagaConfig>(defaults)
+      .map_err(|e| error(format!("invalid value for `{key}`: {e}")))?;
+    Ok(Self { key, value, origin })
+  }
+}
+
+/// 写入点分键名；中间缺失或为 `null` 的分组创建为空对象
+fn set_key(value: &mut Value, key: &str, new_value: Value) {
+  let mut current = value;
+  let mut segments = key.split('.').peekable();
+  while let Some(segment) = segments.next() {
+    if !current.is_object() {
+      *current = Value::Object(Map::new());
+    }
+    let Value::Object(map) = current else {
+      return;
+    };
+    if segments.peek().is_none() {
+      map.insert(segment.to_string(), new_value);
+      return;
+    }
+    current = map.entry(segment.to_string()).or_insert(Value::Null);
+  }
+}
+
+fn config_error(error: serde_json::Error) -> config::ConfigError {
+  config::ConfigError::Foreign(Box::new(error))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{ConfigOverrideError, ConfigOverrides, OverrideOrigin};
+  use crate::config::{NovelSagaConfig, OverridableConfig};
+
+  fn env(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+      .iter()
+      .map(|(name, value)| ((*name).to_string(), (*value).to_string()))
+      .collect()
+  }
+
+  #[test]
+  fn cli_overrides_env_and_both_override_files() {
+    let overrides = ConfigOverrides::from_sources(
+      env(&[
+        ("NOVELSAGA__FMT__INDENT_SPACES", "0"),
+        ("NOVELSAGA__WORKSPACE__EXCLUDE", "drafts/, notes/"),
+        ("PATH", "/usr/bin"),
+      ]),
+      &["fmt.indent_spaces=2".to_string()],
+    )
+    .expect("valid overrides");
+
+    let mut file_config = OverridableConfig::default();
+    file_config.fmt.indent_spaces = 8;
+    let config = overrides.apply(&file_config).expect("applies");
+    assert_eq!(config.fmt.indent_spaces, 2);
+    assert_eq!(config.fmt.blank_lines_between_paragraphs, 1);
+
+    let root = overrides.apply(&NovelSagaConfig::default().root).expect("applies");
+    let workspace = root.workspace.expect("workspace section created");
+    assert_eq!(workspace.exclude, vec!["drafts/", "notes/"]);
+    assert_eq!(workspace.cache_dir, ".cache/novelsaga");
+  }
+
+  #[test]
+  fn reports_unknown_keys_and_type_errors_with_their_origin() {
+    let error = ConfigOverrides::from_sources(env(&[("NOVELSAGA__FMT__INDENT", "2")]), &[]).expect_err("unknown key");
+    assert_eq!(
+      error.to_string(),
+      "environment variable NOVELSAGA__FMT__INDENT: unknown config key `fmt.indent`"
+    );
+
+    let error =
+      ConfigOverrides::from_sources(Vec::new(), &["fmt.indent_spaces=wide".to_string()]).expect_err("not an integer");
+    assert_eq!(error.origin, OverrideOrigin::Cli("fmt.indent_spaces=wide".to_string()));
+    assert_eq!(
+      error.to_string(),
+      "--config fmt.indent_spaces=wide: `fmt.indent_spaces` expects an integer, got `wide`"
+    );
+
+    let error = ConfigOverrides::from_sources(Vec::new(), &["fmt.indent_spaces=-1".to_string()]).expect_err("negative");
+    assert!(error.message.starts_with("invalid value for `fmt.indent_spaces`"));
+
+    assert!(ConfigOverrides::from_sources(Vec::new(), &["fmt".to_string()]).is_err());
+    assert!(ConfigOverrides::from_sources(Vec::new(), &["fmt=1".to_string()]).is_err());
+  }
+
+  #[test]
+  fn invalid_env_vars_can_be_skipped() {
+    let (overrides, skipped) = ConfigOverrides::from_sources_skipping_invalid_env(
+      env(&[("NOVELSAGA__FMT__INDENT", "2"), ("NOVELSAGA__FMT__INDENT_SPACES", "2")]),
+      &[],
+    )
+    .expect("valid --config");
+    assert_eq!(overrides.overrides.len(), 1);
+    assert_eq!(
+      skipped,
+      vec![ConfigOverrideError {
+        origin: OverrideOrigin::Env("NOVELSAGA__FMT__INDENT".to_string()),
+        message: "unknown config key `fmt.indent`".to_string(),
+      }]
+    );
+
+    assert!(ConfigOverrides::from_sources_skipping_invalid_env(Vec::new(), &["fmt=1".to_string()]).is_err());
+  }
+}
@@ -0,0 +1,330 @@
+//! 配置文件的 JSON Schema：由配置类型生成，供编辑器补全，也用于校验配置文件
+
+use std::{path::Path, sync::OnceLock};
+
+use config::FileFormat;
+use jsonschema::{ValidationError, error::ValidationErrorKind, paths::LocationSegment};
+use serde_json::Value;
+
+use super::{
+  NovelSagaConfig, OverridableConfig,
+  file_def::{add_config_source, base_config_file_format},
+};
+use crate::state::find_key_line;
+
+/// 配置文件（`novelsaga.config.*`、`.novelsaga.*`）的 JSON Schema
+#[must_use]
+pub fn novelsaga_config_schema() -> Value {
+  schemars::schema_for!(NovelSagaConfig).to_value()
+}
+
+/// 可在 markdown frontmatter 中覆盖的配置子集的 JSON Schema
+///
+/// frontmatter 还会包含其他字段（标题、元数据等），因此不拒绝未知字段。
+#[must_use]
+pub fn overridable_config_schema() -> Value {
+  schemars::schema_for!(OverridableConfig).to_value()
+}
+
+/// 一个配置项（或分组，如 `fmt`）的说明，取自 schema，即配置类型上的文档注释
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigKeyDoc {
+  /// 点分键名，如 `fmt.indent_spaces`
+  pub key: String,
+  pub description: Option<String>,
+  /// 值类型，如 `integer`、`array of string`；分组为 `object`
+  pub value_type: String,
+  pub default: Option<Value>,
+  /// 可选值（schema 中的 `enum`），没有限定时为空
+  pub allowed_values: Vec<Value>,
+}
+
+impl ConfigKeyDoc {
+  /// 键名最后一段
+  #[must_use]
+  pub fn name(&self) -> &str {
+    self.key.rsplit('.').next().unwrap_or(&self.key)
+  }
+}
+
+/// [`novelsaga_config_schema`] 中所有配置项与分组的说明，按键名排序
+pub fn config_key_docs() -> &'static [ConfigKeyDoc] {
+  static DOCS: OnceLock<Vec<ConfigKeyDoc>> = OnceLock::new();
+  DOCS.get_or_init(|| {
+    let schema = novelsaga_config_schema();
+    let mut docs = Vec::new();
+    collect_key_docs(&schema, &schema, "", &mut docs);
+    docs.sort_by(|a, b| a.key.cmp(&b.key));
+    docs
+  })
+}
+
+/// 某个配置项的说明
+#[must_use]
+pub fn config_key_doc(key: &str) -> Option<&'static ConfigKeyDoc> {
+  config_key_docs().iter().find(|doc| doc.key == key)
+}
+
+pub(crate) fn collect_key_docs(root: &Value, node: &Value, prefix: &str, out: &mut Vec<ConfigKeyDoc>) {
+  let Some(properties) = resolve_schema(root, node)["properties"].as_object() else {
+    return;
+  };
+  for (name, property) in properties {
+    let key = if prefix.is_empty() {
+      name.clone()
+    } else {
+      format!("{prefix}.{name}")
+    };
+    let resolved = resolve_schema(root, property);
+    out.push(ConfigKeyDoc {
+      key: key.clone(),
+      description: property["description"]
+        .as_str()
+        .or_else(|| resolved["description"].as_str())
+        .map(str::to_string),
+      value_type: value_type(resolved),
+      default: property.get("default").filter(|default| !default.is_null()).cloned(),
+      allowed_values: resolved["enum"].as_array().cloned().unwrap_or_default(),
+    });
+    collect_key_docs(root, resolved, &key, out);
+  }
+}
+
+/// 展开 `$ref` 与可选值的 `anyOf: [T, null]`
+fn resolve_schema<'a>(root: &'a Value, node: &'a Value) -> &'a Value {
+  if let Some(reference) = node["$ref"].as_str() {
+    let target = reference
+      .strip_prefix('#')
+      .and_then(|pointer| root.pointer(pointer))
+      .unwrap_or(&Value::Null);
+    return resolve_schema(root, target);
+  }
+  if let Some(branches) = node["anyOf"].as_array()
+    && let Some(branch) = branches.iter().find(|branch| branch["type"] != "null")
+  {
+    return resolve_schema(root, branch);
+  }
+  node
+}
+
+fn value_type(schema: &Value) -> String {
+  match schema["type"].as_str() {
+    Some("array") => match schema["items"]["type"].as_str() {
+      Some(item) => format!("array of {item}"),
+      None => "array".to_string(),
+    },
+    Some(kind) => kind.to_string(),
+    None => "any".to_string(),
+  }
+}
+
+/// 配置文件或元数据 frontmatter 中不符合 schema 的一处
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaViolation {
+  /// 出错配置项的点分键名（如 `fmt.indent_spaces`）；整个文件出错时为空
+  pub key: String,
+  pub message: String,
+  /// 出错配置项所在行（从 1 开始），无法定位时为 `None`
+  pub line: Option<usize>,
+}
+
+/// 按 [`novelsaga_config_schema`] 校验配置文件内容，`path` 仅用于按扩展名选择格式
+///
+/// 与 `ConfigManager` 使用相同的加载方式（[`add_config_source`]）：JS/TS 配置文件通过全局 loader 求值后再校验。
+///
+/// # Errors
+///
+/// 扩展名不受支持或内容无法解析时返回错误
+pub fn validate_config_source(path: &Path, content: &str) -> Result<Vec<SchemaViolation>, config::ConfigError> {
+  let builder = add_config_source(config::Config::builder(), path, Some(content))?;
+  let mut value = builder.build()?.try_deserialize::<Value>()?;
+  let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+  if base_config_file_format(ext) == Some(FileFormat::Ini) {
+    // INI 没有类型，所有值都是字符串；加载时由 `config` 转换，这里同样转换后再校验
+    coerce_scalars(&mut value);
+  }
+
+  let schema = novelsaga_config_schema();
+  let validator = jsonschema::validator_for(&schema)
+    .map_err(|error| config::ConfigError::Message(format!("Invalid config schema: {error}")))?;
+  let mut violations = Vec::new();
+  for error in validator.iter_errors(&value) {
+    collect_violations(&error, content, &mut violations);
+  }
+  violations.sort_by(|a, b| a.line.cmp(&b.line).then_with(|| a.key.cmp(&b.key)));
+  Ok(violations)
+}
+
+/// 展开 `anyOf`/`oneOf`（如 `Option<T>` 生成的 `T | null`）：若某个分支的错误都位于更深的字段，
+/// 报告这些具体错误而不是笼统的“不匹配任何分支”
+pub(crate) fn collect_violations(error: &ValidationError<'_>, content: &str, out: &mut Vec<SchemaViolation>) {
+  if let ValidationErrorKind::AnyOf { context } | ValidationErrorKind::OneOfNotValid { context } = error.kind() {
+    let depth = error.instance_path().iter().count();
+    if let Some(branch) = context
+      .iter()
+      .find(|branch| !branch.is_empty() && branch.iter().all(|error| error.instance_path().iter().count() > depth))
+    {
+      for error in branch {
+        collect_violations(error, content, out);
+      }
+      return;
+    }
+  }
+
+  let key = violation_key(error);
+  out.push(SchemaViolation {
+    line: key_line(content, &key),
+    message: error.to_string(),
+    key,
+  });
+}
+
+/// 出错位置的点分键名；多余字段指向第一个未知字段本身
+fn violation_key(error: &ValidationError<'_>) -> String {
+  let mut segments: Vec<String> = error
+    .instance_path()
+    .iter()
+    .map(|segment| match segment {
+      LocationSegment::Property(property) => property.into_owned(),
+      LocationSegment::Index(index) => index.to_string(),
+    })
+    .collect();
+  if let ValidationErrorKind::AdditionalProperties { unexpected } = error.kind()
+    && let Some(first) = unexpected.first()
+  {
+    segments.push(first.clone());
+  }
+  segments.join(".")
+}
+
+/// 键名所在行；找不到时退回最近的能定位的父级
+fn key_line(content: &str, key: &str) -> Option<usize> {
+  let mut key = key;
+  loop {
+    if key.is_empty() {
+      return None;
+    }
+    if let Some(line) = find_key_line(content, key) {
+      return Some(line);
+    }
+    key = key.rsplit_once('.').map_or("", |(parent, _)| parent);
+  }
+}
+
+fn coerce_scalars(value: &mut Value) {
+  match value {
+    Value::Object(map) => map.values_mut().for_each(coerce_scalars),
+    Value::Array(items) => items.iter_mut().for_each(coerce_scalars),
+    Value::String(text) => {
+      if let Ok(number) = text.parse::<u64>() {
+        *value = number.into();
+      } else if let Ok(number) = text.parse::<i64>() {
+        *value = number.into();
+      } else if let Ok(flag) = text.parse::<bool>() {
+        *value = flag.into();
+      }
+    }
+    _ => {}
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+
+  use super::{
+    SchemaViolation, config_key_doc, config_key_docs, novelsaga_config_schema, overridable_config_schema,
+    validate_config_source,
+  };
+
+  fn violations(file_name: &str, content: &str) -> Vec<SchemaViolation> {
+    validate_config_source(Path::new(file_name), content).expect("config parses")
+  }
+
+  #[test]
+  fn schemas_describe_config_keys() {
+    let schema = novelsaga_config_schema();
+    assert_eq!(schema["additionalProperties"], false);
+    assert!(schema["properties"]["workspace"].is_object());
+    assert!(schema["properties"]["fmt"].is_object());
+
+    let overridable = overridable_config_schema();
+    assert!(overridable["properties"]["fmt"].is_object());
+    assert!(overridable["properties"].get("workspace").is_none());
+    assert!(overridable.get("additionalProperties").is_none());
+  }
+
+  #[test]
+  fn key_docs_come_from_doc_comments() {
+    let keys: Vec<&str> = config_key_docs().iter().map(|doc| doc.key.as_str()).collect();
+    assert!(keys.contains(&"fmt"));
+    assert!(keys.contains(&"workspace.cache_dir"));
+
+    let indent = config_key_doc("fmt.indent_spaces").expect("documented");
+    assert_eq!(indent.name(), "indent_spaces");
+    assert_eq!(indent.value_type, "integer");
+    assert_eq!(indent.default, Some(serde_json::json!(4)));
+    assert_eq!(indent.description.as_deref(), Some("段首缩进的空格数"));
+
+    let workspace = config_key_doc("workspace").expect("documented");
+    assert_eq!(workspace.value_type, "object");
+    assert_eq!(workspace.default, None);
+    assert_eq!(
+      config_key_doc("workspace.exclude").expect("documented").value_type,
+      "array of string"
+    );
+  }
+
+  #[test]
+  fn valid_config_has_no_violations() {
+    assert!(
+      violations(
+        "novelsaga.config.yaml",
+        "workspace:\n  cache_dir: .cache\nfmt:\n  indent_spaces: 2\n"
+      )
+      .is_empty()
+    );
+    assert!(violations(".novelsaga.ini", "[fmt]\nindent_spaces = 2\n").is_empty());
+    assert!(violations(".novelsaga.md", "---\nfmt:\n  indent_spaces: 2\n---\n").is_empty());
+    assert!(
+      violations(
+        "novelsaga.config.yaml",
+        "overrides:\n  - files: drafts/**\n    fmt:\n      indent_spaces: 0\n  - files: [a/**, b/**]\n"
+      )
+      .is_empty()
+    );
+  }
+
+  #[test]
+  fn reports_wrong_types_and_unknown_keys_with_lines() {
+    let yaml = "fmt:\n  indent_spaces: two\n  indent_spacs: 2\nworkspace:\n  cache_dir: .cache\n  exclude: drafts\n";
+    let found = violations("novelsaga.config.yaml", yaml);
+    let summary: Vec<(&str, Option<usize>)> = found.iter().map(|v| (v.key.as_str(), v.line)).collect();
+    assert_eq!(
+      summary,
+      vec![
+        ("fmt.indent_spaces", Some(2)),
+        ("fmt.indent_spacs", Some(3)),
+        ("workspace.exclude", Some(6)),
+      ]
+    );
+
+    let found = violations(
+      "novelsaga.config.yaml",
+      "overrides:\n  - files: drafts/**\n    fmt:\n      indent: 0\n",
+    );
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].key, "overrides.0.fmt.indent");
+
+    let toml = "[fmt]\nindent_spaces = -1\n";
+    let found = violations(".novelsaga.toml", toml);
+    assert_eq!(found.len(), 1);
+    assert_eq!((found[0].key.as_str(), found[0].line), ("fmt.indent_spaces", Some(2)));
+  }
+
+  #[test]
+  fn rejects_unsupported_extensions() {
+    let error = validate_config_source(Path::new("novelsaga.config.xml"), "<fmt/>").expect_err("unsupported");
+    assert!(error.to_string().contains("Unsupported config file extension: xml"));
+  }
+}
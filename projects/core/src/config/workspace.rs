@@ -1,17 +1,34 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use ts_rs::TS;
 
-#[derive(Debug, Clone, Serialize, Deserialize, TS)]
+#[derive(Debug, Clone, Serialize, Deserialize, TS, JsonSchema)]
 #[ts(export_to = "_workspace_config.ts")]
 #[serde(default)]
+#[schemars(deny_unknown_fields)]
 pub struct WorkspaceConfig {
+  /// 索引缓存目录，相对于工作区根目录
+  ///
+  /// 绝对路径、`~/…` 或 `$cache/…`（用户缓存目录）为多个工作区共享的目录，
+  /// 每个工作区使用其中一个子目录
   pub cache_dir: String,
+  /// LSP 多根工作区中，是否也从其他文件夹解析元数据实体
+  pub cross_folder_resolution: bool,
+  /// gitignore 风格的 glob；设置后只有匹配的文件属于工作区
+  pub include: Vec<String>,
+  /// gitignore 风格的 glob，匹配的文件不参与索引、格式化、检查与导出
+  pub exclude: Vec<String>,
 }
 
 impl Default for WorkspaceConfig {
   fn default() -> Self {
     let cache_dir = ".cache/novelsaga".to_string();
-    Self { cache_dir }
+    Self {
+      cache_dir,
+      cross_folder_resolution: false,
+      include: Vec::new(),
+      exclude: Vec::new(),
+    }
   }
 }
 
@@ -29,5 +46,8 @@ mod tests {
   fn test_default_structure() {
     let config = WorkspaceConfig::default();
     assert_eq!(config.cache_dir, ".cache/novelsaga");
+    assert!(!config.cross_folder_resolution);
+    assert!(config.include.is_empty());
+    assert!(config.exclude.is_empty());
   }
 }
@@ -0,0 +1,537 @@
+//! Workspace file discovery shared by indexing, formatting, linting and export.
+//!
+//! A file is part of the workspace unless one of these excludes it:
+//!
+//! - hidden files and directories (relative to the workspace root);
+//! - `.gitignore` and the ignore files in [`IGNORE_CONFIG_FILE_NAMES`]
+//!   (`.novelsagaignore`, `.nsignore`, …), in the directory being walked and
+//!   its parents — a git repository is not required;
+//! - the configured `workspace.exclude` globs and the `workspace.cache_dir`;
+//! - when `workspace.include` is not empty, files matching none of its globs.
+//!
+//! Globs use gitignore syntax relative to the workspace root.
+//!
+//! Single-path lookups go through [`IgnoreMatchers`], which compiles each
+//! directory's ignore files once and only checks them again by stat, so a
+//! lookup costs O(depth) instead of a walk of the tree.
+
+use std::{
+  collections::HashMap,
+  path::{Path, PathBuf},
+  sync::Arc,
+  time::SystemTime,
+};
+
+use ignore::{
+  Match, WalkBuilder,
+  gitignore::{Gitignore, GitignoreBuilder},
+  overrides::{Override, OverrideBuilder},
+};
+use parking_lot::RwLock;
+
+use crate::config::{
+  file_def::{IGNORE_CONFIG_FILE_NAMES, is_config_file},
+  workspace::WorkspaceConfig,
+};
+
+/// Modification time and length of one ignore file, `None` when absent.
+type IgnoreFileStamp = Option<(SystemTime, u64)>;
+
+/// Compiled ignore files of one directory.
+#[derive(Debug)]
+struct DirectoryMatchers {
+  stamps: Vec<IgnoreFileStamp>,
+  matchers: Vec<Gitignore>,
+}
+
+/// Gitignore-style matchers per directory, compiled once and cached.
+///
+/// A cached directory is recompiled when one of its ignore files is created,
+/// edited or deleted (detected by modification time and length), so callers
+/// never need to invalidate it explicitly.
+#[derive(Debug)]
+pub struct IgnoreMatchers {
+  /// Ignore file names in increasing precedence
+  file_names: Vec<&'static str>,
+  case_insensitive: bool,
+  directories: RwLock<HashMap<PathBuf, Arc<DirectoryMatchers>>>,
+}
+
+impl IgnoreMatchers {
+  /// Matchers reading `file_names` in every directory; later names take
+  /// precedence over earlier ones.
+  #[must_use]
+  pub fn new(file_names: impl IntoIterator<Item = &'static str>) -> Self {
+    Self {
+      file_names: file_names.into_iter().collect(),
+      case_insensitive: false,
+      directories: RwLock::new(HashMap::new()),
+    }
+  }
+
+  #[must_use]
+  pub fn case_insensitive(mut self, yes: bool) -> Self {
+    self.case_insensitive = yes;
+    self
+  }
+
+  /// Whether the ignore files of `path`'s ancestors, from `root` down, ignore it.
+  ///
+  /// Deeper directories override shallower ones and a `!pattern` re-includes,
+  /// but, as with git and the walker, nothing inside an ignored directory can
+  /// be re-included. Ignore files above `root` do not apply, and paths outside
+  /// it are never ignored.
+  #[must_use]
+  pub fn is_ignored(&self, root: &Path, path: &Path, is_dir: bool) -> bool {
+    // Top-down: `root` first, `path` last
+    let mut candidates: Vec<&Path> = path
+      .ancestors()
+      .take_while(|ancestor| ancestor.starts_with(root))
+      .collect();
+    if candidates.len() < 2 {
+      return false;
+    }
+    candidates.reverse();
+    let levels: Vec<Arc<DirectoryMatchers>> = candidates[..candidates.len() - 1]
+      .iter()
+      .map(|directory| self.matchers_in(directory))
+      .collect();
+
+    candidates.iter().enumerate().skip(1).any(|(depth, candidate)| {
+      let candidate_is_dir = depth < candidates.len() - 1 || is_dir;
+      let mut ignored = false;
+      for matcher in levels[..depth].iter().flat_map(|level| &level.matchers) {
+        match matcher.matched(candidate, candidate_is_dir) {
+          Match::Ignore(_) => ignored = true,
+          Match::Whitelist(_) => ignored = false,
+          Match::None => {}
+        }
+      }
+      ignored
+    })
+  }
+
+  fn matchers_in(&self, directory: &Path) -> Arc<DirectoryMatchers> {
+    let stamps: Vec<IgnoreFileStamp> = self
+      .file_names
+      .iter()
+      .map(|name| {
+        let metadata = std::fs::metadata(directory.join(name)).ok()?;
+        Some((metadata.modified().ok()?, metadata.len()))
+      })
+      .collect();
+
+    if let Some(cached) = self.directories.read().get(directory)
+      && cached.stamps == stamps
+    {
+      return cached.clone();
+    }
+
+    let matchers = self
+      .file_names
+      .iter()
+      .zip(&stamps)
+      .filter(|(_, stamp)| stamp.is_some())
+      .filter_map(|(name, _)| {
+        let mut builder = GitignoreBuilder::new(directory);
+        builder.case_insensitive(self.case_insensitive).ok()?;
+        if let Some(error) = builder.add(directory.join(name)) {
+          eprintln!("Invalid ignore file {}: {error}", directory.join(name).display());
+        }
+        builder.build().ok()
+      })
+      .collect();
+    let compiled = Arc::new(DirectoryMatchers { stamps, matchers });
+    self
+      .directories
+      .write()
+      .insert(directory.to_path_buf(), compiled.clone());
+    compiled
+  }
+}
+
+/// Walks a workspace applying its ignore rules and include/exclude globs.
+#[derive(Debug, Clone)]
+pub struct FileDiscovery {
+  root: PathBuf,
+  /// `exclude` globs and the cache dir, as negated overrides
+  excludes: Override,
+  /// `include` globs; applied after the ignore files, which overrides would bypass
+  includes: Override,
+  /// `.gitignore` and the `NovelSaga` ignore files, shared between clones
+  ignore_files: Arc<IgnoreMatchers>,
+  threads: usize,
+}
+
+impl FileDiscovery {
+  /// Discovery for `root` with only the ignore-file and hidden-file rules.
+  #[must_use]
+  pub fn new(root: impl Into<PathBuf>) -> Self {
+    Self {
+      root: root.into(),
+      excludes: Override::empty(),
+      includes: Override::empty(),
+      ignore_files: Self::ignore_files(),
+      threads: 0,
+    }
+  }
+
+  /// Discovery for `root` using the include/exclude globs and cache directory
+  /// of `config`.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error when one of the configured globs is invalid.
+  pub fn from_config(root: impl Into<PathBuf>, config: &WorkspaceConfig) -> Result<Self, ignore::Error> {
+    let root = root.into();
+    let mut includes = OverrideBuilder::new(&root);
+    for glob in &config.include {
+      includes.add(glob)?;
+    }
+    let mut builder = OverrideBuilder::new(&root);
+    for glob in &config.exclude {
+      builder.add(&format!("!{glob}"))?;
+    }
+    let cache_dir = config.cache_dir.trim_matches('/');
+    if !cache_dir.is_empty() && Path::new(cache_dir).is_relative() {
+      builder.add(&format!("!/{cache_dir}/"))?;
+    }
+
+    Ok(Self {
+      excludes: builder.build()?,
+      includes: includes.build()?,
+      ignore_files: Self::ignore_files(),
+      root,
+      threads: 0,
+    })
+  }
+
+  /// Number of walker threads; `0` (the default) picks one per CPU.
+  #[must_use]
+  pub fn threads(mut self, threads: usize) -> Self {
+    self.threads = threads;
+    self
+  }
+
+  #[must_use]
+  pub fn root(&self) -> &Path {
+    &self.root
+  }
+
+  /// Workspace files accepted by `filter`, sorted by path.
+  #[must_use]
+  pub fn files(&self, filter: impl Fn(&Path) -> bool + Sync) -> Vec<PathBuf> {
+    self.files_under(&self.root, filter)
+  }
+
+  /// Workspace files under `dir` (inside the root) accepted by `filter`,
+  /// sorted by path.
+  #[must_use]
+  pub fn files_under(&self, dir: &Path, filter: impl Fn(&Path) -> bool + Sync) -> Vec<PathBuf> {
+    self.walk(dir, false, filter)
+  }
+
+  /// `NovelSaga` config files of the workspace (`novelsaga.config.*` and the
+  /// hidden `.novelsaga.*`), sorted by path.
+  ///
+  /// Config files are not subject to the `include` globs, and hidden
+  /// directories are still skipped; only hidden config files are kept.
+  #[must_use]
+  pub fn config_files(&self) -> Vec<PathBuf> {
+    self.walk(&self.root, true, is_config_file)
+  }
+
+  fn walk(&self, dir: &Path, config_files: bool, filter: impl Fn(&Path) -> bool + Sync) -> Vec<PathBuf> {
+    if dir != self.root && self.is_ignored(dir) {
+      return Vec::new();
+    }
+
+    // Walk from the root, descending only towards `dir`, so that the ignore
+    // files between them apply but none above the root do
+    let mut builder = WalkBuilder::new(&self.root);
+    let target = dir.to_path_buf();
+    builder.hidden(!config_files).filter_entry(move |entry| {
+      let path = entry.path();
+      if !path.starts_with(&target) && !target.starts_with(path) {
+        return false;
+      }
+      !config_files
+        || entry.depth() == 0
+        || !entry.file_name().to_string_lossy().starts_with('.')
+        || (entry.file_type().is_some_and(|file_type| file_type.is_file()) && is_config_file(path))
+    });
+    builder
+      .parents(false)
+      .ignore(false)
+      .git_global(false)
+      .git_exclude(false)
+      .git_ignore(true)
+      .require_git(false)
+      .overrides(self.excludes.clone())
+      .threads(self.threads);
+    for ignore_file_name in IGNORE_CONFIG_FILE_NAMES {
+      builder.add_custom_ignore_filename(ignore_file_name);
+    }
+
+    let filter = |path: &Path| (config_files || self.is_included(path)) && filter(path);
+
+    #[cfg(target_arch = "wasm32")]
+    let mut paths: Vec<PathBuf> = builder
+      .build()
+      .filter_map(Result::ok)
+      .filter(|entry| entry.file_type().is_some_and(|file_type| file_type.is_file()) && filter(entry.path()))
+      .map(ignore::DirEntry::into_path)
+      .collect();
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut paths: Vec<PathBuf> = {
+      let (tx, rx) = std::sync::mpsc::channel();
+      let filter = &filter;
+      builder.build_parallel().run(|| {
+        let tx = tx.clone();
+        Box::new(move |result| {
+          if let Ok(entry) = result
+            && entry.file_type().is_some_and(|file_type| file_type.is_file())
+            && filter(entry.path())
+          {
+            let _ = tx.send(entry.into_path());
+          }
+          ignore::WalkState::Continue
+        })
+      });
+      drop(tx);
+      rx.into_iter().collect()
+    };
+
+    // 并行遍历的产出顺序不固定，排序保证结果确定
+    paths.sort();
+    paths
+  }
+
+  /// Whether `path` would be left out of a walk of the workspace.
+  ///
+  /// Used for single files (e.g. file-watcher events) without walking the
+  /// tree; paths outside the root are never ignored by workspace rules.
+  #[must_use]
+  pub fn is_ignored(&self, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(&self.root) else {
+      return false;
+    };
+    if relative
+      .components()
+      .any(|component| component.as_os_str().to_string_lossy().starts_with('.'))
+    {
+      return true;
+    }
+
+    let is_dir = path.is_dir();
+    if !is_dir && !self.is_included(path) {
+      return true;
+    }
+    // The globs only apply to the path itself; excluded ancestors hide it too
+    for ancestor in relative.ancestors().filter(|ancestor| !ancestor.as_os_str().is_empty()) {
+      let ancestor_is_dir = ancestor != relative || is_dir;
+      if self.excludes.matched(ancestor, ancestor_is_dir).is_ignore() {
+        return true;
+      }
+    }
+
+    self.ignore_files.is_ignored(&self.root, path, is_dir)
+  }
+
+  fn ignore_files() -> Arc<IgnoreMatchers> {
+    Arc::new(IgnoreMatchers::new(
+      std::iter::once(".gitignore").chain(IGNORE_CONFIG_FILE_NAMES.iter().copied()),
+    ))
+  }
+
+  fn is_included(&self, path: &Path) -> bool {
+    self.includes.is_empty() || self.includes.matched(path, false).is_whitelist()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+
+  use tempfile::TempDir;
+
+  use super::{FileDiscovery, IgnoreMatchers};
+  use crate::config::workspace::WorkspaceConfig;
+
+  fn write(root: &Path, relative: &str, content: &str) {
+    let path = root.join(relative);
+    std::fs::create_dir_all(path.parent().expect("parent")).expect("create dirs");
+    std::fs::write(path, content).expect("write");
+  }
+
+  fn relative(root: &Path, paths: Vec<std::path::PathBuf>) -> Vec<String> {
+    paths
+      .into_iter()
+      .map(|path| {
+        path
+          .strip_prefix(root)
+          .expect("inside root")
+          .to_string_lossy()
+          .replace('\\', "/")
+      })
+      .collect()
+  }
+
+  fn fixture() -> TempDir {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    write(root, "chapter-01.md", "");
+    write(root, "drafts/idea.md", "");
+    write(root, "node_modules/pkg/readme.md", "");
+    write(root, "metadata/hero.md", "");
+    write(root, "metadata/notes.txt", "");
+    write(root, "metadata/secret.md", "");
+    write(root, "build/out.md", "");
+    write(root, ".cache/novelsaga/stale.md", "");
+    write(root, ".gitignore", "node_modules/\n");
+    write(root, ".novelsagaignore", "drafts/\n");
+    write(root, "metadata/.nsignore", "secret.md\n");
+    workspace
+  }
+
+  #[test]
+  fn honors_ignore_files_and_hidden_paths() {
+    let workspace = fixture();
+    let root = workspace.path();
+    let discovery = FileDiscovery::new(root);
+
+    assert_eq!(
+      relative(root, discovery.files(|_| true)),
+      vec![
+        "build/out.md",
+        "chapter-01.md",
+        "metadata/hero.md",
+        "metadata/notes.txt"
+      ]
+    );
+    for ignored in [
+      "drafts/idea.md",
+      "node_modules/pkg/readme.md",
+      "metadata/secret.md",
+      ".cache/novelsaga/stale.md",
+    ] {
+      assert!(discovery.is_ignored(&root.join(ignored)), "{ignored}");
+    }
+    assert!(!discovery.is_ignored(&root.join("metadata/hero.md")));
+  }
+
+  #[test]
+  fn applies_configured_include_exclude_and_cache_dir() {
+    let workspace = fixture();
+    let root = workspace.path();
+    write(root, "cache/index.md", "");
+    let config = WorkspaceConfig {
+      cache_dir: "cache".to_string(),
+      include: vec!["*.md".to_string()],
+      exclude: vec!["build/".to_string()],
+      ..WorkspaceConfig::default()
+    };
+    let discovery = FileDiscovery::from_config(root, &config).expect("valid globs");
+
+    assert_eq!(
+      relative(root, discovery.files(|_| true)),
+      vec!["chapter-01.md", "metadata/hero.md"]
+    );
+    assert_eq!(
+      relative(root, discovery.files_under(&root.join("metadata"), |_| true)),
+      vec!["metadata/hero.md"]
+    );
+    assert!(discovery.is_ignored(&root.join("build/out.md")));
+    assert!(discovery.is_ignored(&root.join("cache/index.md")));
+    assert!(discovery.is_ignored(&root.join("metadata/notes.txt")));
+    assert!(!discovery.is_ignored(&root.join("chapter-01.md")));
+  }
+
+  #[test]
+  fn config_files_include_hidden_config_files_only() {
+    let workspace = fixture();
+    let root = workspace.path();
+    write(root, "novelsaga.config.yaml", "");
+    write(root, "book/.novelsaga.toml", "");
+    write(root, "drafts/.novelsaga.toml", "");
+    write(root, ".git/novelsaga.config.json", "");
+    let config = WorkspaceConfig {
+      include: vec!["*.md".to_string()],
+      ..WorkspaceConfig::default()
+    };
+    let discovery = FileDiscovery::from_config(root, &config).expect("valid globs");
+
+    assert_eq!(
+      relative(root, discovery.config_files()),
+      vec!["book/.novelsaga.toml", "novelsaga.config.yaml"]
+    );
+  }
+
+  #[test]
+  fn rejects_invalid_globs() {
+    let config = WorkspaceConfig {
+      exclude: vec!["[".to_string()],
+      ..WorkspaceConfig::default()
+    };
+    assert!(FileDiscovery::from_config("/workspace", &config).is_err());
+  }
+
+  #[test]
+  fn ignore_matchers_are_cached_until_an_ignore_file_changes() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    write(root, "sub/deep/note.md", "");
+    let note = root.join("sub/deep/note.md");
+    let matchers = IgnoreMatchers::new([".nsignore"]);
+
+    assert!(!matchers.is_ignored(root, &note, false));
+    write(root, ".nsignore", "sub/\n");
+    assert!(matchers.is_ignored(root, &note, false));
+
+    let cached = matchers.directories.read().get(root).cloned().expect("cached");
+    assert!(matchers.is_ignored(root, &note, false));
+    assert!(std::sync::Arc::ptr_eq(
+      &cached,
+      matchers.directories.read().get(root).expect("cached")
+    ));
+
+    // Nothing inside an ignored directory can be re-included
+    write(root, "sub/deep/.nsignore", "!note.md\n");
+    assert!(matchers.is_ignored(root, &note, false));
+
+    // Editing the root ignore file is picked up, and deeper files override it
+    write(root, ".nsignore", "*.md\n");
+    assert!(!matchers.is_ignored(root, &note, false));
+    assert!(matchers.is_ignored(root, &root.join("sub/other.md"), false));
+  }
+
+  #[test]
+  fn ignore_files_above_the_root_do_not_apply() {
+    let workspace = TempDir::new().expect("tempdir");
+    let outer = workspace.path();
+    write(outer, ".nsignore", "book/\n");
+    write(outer, "book/chapter-01.md", "");
+    let root = outer.join("book");
+    let matchers = IgnoreMatchers::new([".nsignore"]);
+
+    assert!(!matchers.is_ignored(&root, &root.join("chapter-01.md"), false));
+    assert!(matchers.is_ignored(outer, &root.join("chapter-01.md"), false));
+    assert!(!matchers.is_ignored(&root, &outer.join("elsewhere.md"), false));
+    assert!(!matchers.directories.read().contains_key(std::path::Path::new("/")));
+
+    write(outer, "book/.nsignore", "*.tmp.md\n");
+    write(outer, "book/part-1/draft.tmp.md", "");
+    write(outer, "book/part-1/chapter-02.md", "");
+    let discovery = FileDiscovery::new(&root);
+    assert!(!discovery.is_ignored(&root.join("chapter-01.md")));
+    assert_eq!(
+      relative(&root, discovery.files(|_| true)),
+      vec!["chapter-01.md", "part-1/chapter-02.md"]
+    );
+    assert_eq!(
+      relative(&root, discovery.files_under(&root.join("part-1"), |_| true)),
+      vec!["part-1/chapter-02.md"]
+    );
+  }
+}
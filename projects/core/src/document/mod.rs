@@ -3,11 +3,16 @@
 //! This module provides types and utilities for parsing and classifying markdown documents
 //! used by both articles and metadata entities.
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use serde_json::{Value, json};
 
-use crate::{article::ArticleDocument, metadata::MetadataEntity};
+use crate::{
+  article::ArticleDocument,
+  config::metadata::MetadataConfig,
+  error::ErrorCode,
+  metadata::{MetadataEntity, MetadataError},
+};
 
 /// Parsed markdown document with separated frontmatter and body
 ///
@@ -159,7 +164,7 @@ impl MarkdownParts {
 /// Used to determine how to interpret and process markdown documents.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DocumentKind {
-  /// Metadata document - in metadata/** directories
+  /// Metadata document - in a metadata directory (`metadata/**` by default)
   Metadata,
   /// Article document - regular content files
   Article,
@@ -169,11 +174,13 @@ impl DocumentKind {
   /// Classify a document by its file path
   ///
   /// # Rules
-  /// - Paths matching `**/metadata/**/*.md` → Metadata
+  /// - Paths with a metadata directory (one of `layout.dirs`, `metadata` by
+  ///   default) among their components → Metadata
   /// - All other `.md` files → Article
   ///
   /// # Arguments
   /// * `path` - File path to classify
+  /// * `layout` - Metadata directory layout of the workspace
   ///
   /// # Returns
   /// Classified `DocumentKind`
@@ -181,17 +188,18 @@ impl DocumentKind {
   /// # Examples
   /// ```ignore
   /// let metadata_doc = Path::new("metadata/characters/hero.md");
-  /// assert_eq!(DocumentKind::classify_path(metadata_doc), DocumentKind::Metadata);
+  /// let layout = MetadataConfig::default();
+  /// assert_eq!(DocumentKind::classify_path(metadata_doc, &layout), DocumentKind::Metadata);
   ///
   /// let article_doc = Path::new("chapters/chapter-01.md");
-  /// assert_eq!(DocumentKind::classify_path(article_doc), DocumentKind::Article);
+  /// assert_eq!(DocumentKind::classify_path(article_doc, &layout), DocumentKind::Article);
   /// ```
   #[must_use]
-  pub fn classify_path(path: &Path) -> Self {
-    // Check if path contains "metadata" as a directory component
+  pub fn classify_path(path: &Path, layout: &MetadataConfig) -> Self {
+    // Check if path contains a metadata directory as a directory component
     for component in path.components() {
       if let std::path::Component::Normal(os_str) = component
-        && os_str.to_string_lossy() == "metadata"
+        && layout.is_metadata_dir(&os_str.to_string_lossy())
       {
         return DocumentKind::Metadata;
       }
@@ -214,6 +222,36 @@ pub enum WorkspaceDocument {
 }
 
 impl WorkspaceDocument {
+  /// Build the document of `kind` at `path` from its parsed parts
+  ///
+  /// # Arguments
+  /// * `kind` - How the document is interpreted, usually from [`DocumentKind::classify_path`]
+  /// * `parts` - Parsed markdown with frontmatter and body
+  /// * `path` - Full path to the document
+  /// * `workspace_root` - Workspace root; required for metadata documents
+  /// * `layout` - Metadata directory layout used to infer type and namespace
+  ///
+  /// # Errors
+  /// * [`DocumentError::MissingWorkspaceRoot`] for a metadata document without a workspace root
+  /// * [`DocumentError::Metadata`] when the metadata entity cannot be built
+  pub fn from_parts(
+    kind: DocumentKind,
+    parts: MarkdownParts,
+    path: &Path,
+    workspace_root: Option<&Path>,
+    layout: &MetadataConfig,
+  ) -> Result<Self, DocumentError> {
+    match kind {
+      DocumentKind::Metadata => {
+        let root = workspace_root.ok_or_else(|| DocumentError::MissingWorkspaceRoot {
+          path: path.to_path_buf(),
+        })?;
+        Ok(Self::Metadata(MetadataEntity::try_from((parts, path, root, layout))?))
+      }
+      DocumentKind::Article => Ok(Self::Article(ArticleDocument::from_parts(parts))),
+    }
+  }
+
   /// Get the document kind (Metadata or Article)
   ///
   /// # Returns
@@ -271,6 +309,34 @@ impl WorkspaceDocument {
   }
 }
 
+/// Failure to turn a file into a [`WorkspaceDocument`]
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DocumentError {
+  /// No parse result is available yet (e.g. a document store entry before its first parse)
+  #[error("document has not been parsed yet")]
+  NotParsed,
+  /// The document URI does not name a local file
+  #[error("not a local file: {uri}")]
+  NotAFile { uri: String },
+  /// Metadata documents derive their namespace from a workspace root
+  #[error("workspace root is required to parse metadata document {}", path.display())]
+  MissingWorkspaceRoot { path: PathBuf },
+  /// The metadata entity cannot be built
+  #[error(transparent)]
+  Metadata(#[from] MetadataError),
+}
+
+impl ErrorCode for DocumentError {
+  fn code(&self) -> &'static str {
+    match self {
+      DocumentError::NotParsed => "document.not_parsed",
+      DocumentError::NotAFile { .. } => "document.not_a_file",
+      DocumentError::MissingWorkspaceRoot { .. } => "document.missing_workspace_root",
+      DocumentError::Metadata(error) => error.code(),
+    }
+  }
+}
+
 fn markdown_without_frontmatter(content: &str) -> MarkdownParts {
   MarkdownParts {
     frontmatter: json!({}),
@@ -558,49 +624,93 @@ mod tests {
   #[test]
   fn test_classify_metadata_root() {
     let path = Path::new("metadata/characters/hero.md");
-    assert_eq!(DocumentKind::classify_path(path), DocumentKind::Metadata);
+    assert_eq!(
+      DocumentKind::classify_path(path, &MetadataConfig::default()),
+      DocumentKind::Metadata
+    );
   }
 
   #[test]
   fn test_classify_metadata_nested() {
     let path = Path::new("book-01/metadata/characters/hero.md");
-    assert_eq!(DocumentKind::classify_path(path), DocumentKind::Metadata);
+    assert_eq!(
+      DocumentKind::classify_path(path, &MetadataConfig::default()),
+      DocumentKind::Metadata
+    );
   }
 
   #[test]
   fn test_classify_metadata_deeply_nested() {
     let path = Path::new("series/book-01/part-01/metadata/scene.md");
-    assert_eq!(DocumentKind::classify_path(path), DocumentKind::Metadata);
+    assert_eq!(
+      DocumentKind::classify_path(path, &MetadataConfig::default()),
+      DocumentKind::Metadata
+    );
   }
 
   #[test]
   fn test_classify_article_root() {
     let path = Path::new("chapters/chapter-01.md");
-    assert_eq!(DocumentKind::classify_path(path), DocumentKind::Article);
+    assert_eq!(
+      DocumentKind::classify_path(path, &MetadataConfig::default()),
+      DocumentKind::Article
+    );
   }
 
   #[test]
   fn test_classify_article_nested() {
     let path = Path::new("book-01/chapters/chapter-01.md");
-    assert_eq!(DocumentKind::classify_path(path), DocumentKind::Article);
+    assert_eq!(
+      DocumentKind::classify_path(path, &MetadataConfig::default()),
+      DocumentKind::Article
+    );
   }
 
   #[test]
   fn test_classify_metadata_only_file() {
     let path = Path::new("metadata.md");
-    assert_eq!(DocumentKind::classify_path(path), DocumentKind::Article);
+    assert_eq!(
+      DocumentKind::classify_path(path, &MetadataConfig::default()),
+      DocumentKind::Article
+    );
   }
 
   #[test]
   fn test_classify_metadata_in_filename() {
     let path = Path::new("chapters/metadata-doc.md");
-    assert_eq!(DocumentKind::classify_path(path), DocumentKind::Article);
+    assert_eq!(
+      DocumentKind::classify_path(path, &MetadataConfig::default()),
+      DocumentKind::Article
+    );
   }
 
   #[test]
   fn test_classify_metadata_multiple_folders() {
     let path = Path::new("metadata/metadata/hero.md");
-    assert_eq!(DocumentKind::classify_path(path), DocumentKind::Metadata);
+    assert_eq!(
+      DocumentKind::classify_path(path, &MetadataConfig::default()),
+      DocumentKind::Metadata
+    );
+  }
+
+  #[test]
+  fn test_classify_configured_metadata_dirs() {
+    let layout = MetadataConfig {
+      dirs: vec!["设定".to_string(), "metadata".to_string()],
+      ..MetadataConfig::default()
+    };
+    assert_eq!(
+      DocumentKind::classify_path(Path::new("卷一/设定/人物/李白.md"), &layout),
+      DocumentKind::Metadata
+    );
+    assert_eq!(
+      DocumentKind::classify_path(Path::new("卷一/设定/人物/李白.md"), &MetadataConfig::default()),
+      DocumentKind::Article
+    );
+    assert_eq!(
+      DocumentKind::classify_path(Path::new("卷一/正文/第一章.md"), &layout),
+      DocumentKind::Article
+    );
   }
 
   #[test]
@@ -728,4 +838,32 @@ mod tests {
     assert!(doc1.as_metadata().is_some());
     assert_eq!(doc1.as_metadata(), doc2.as_metadata());
   }
+
+  #[test]
+  fn test_workspace_document_from_parts() {
+    let layout = MetadataConfig::default();
+    let path = Path::new("/project/metadata/characters/hero.md");
+    let parse = |root: Option<&Path>| {
+      WorkspaceDocument::from_parts(
+        DocumentKind::Metadata,
+        MarkdownParts::parse("# Hero"),
+        path,
+        root,
+        &layout,
+      )
+    };
+
+    let doc = parse(Some(Path::new("/project"))).expect("metadata document");
+    assert_eq!(
+      doc.as_metadata().map(|entity| entity.id.as_str()),
+      Some("global/character/hero")
+    );
+
+    let error = parse(None).expect_err("no workspace root");
+    assert_eq!(error.code(), "document.missing_workspace_root");
+
+    let article =
+      WorkspaceDocument::from_parts(DocumentKind::Article, MarkdownParts::parse("Text"), path, None, &layout);
+    assert!(article.is_ok_and(|doc| doc.is_article()));
+  }
 }
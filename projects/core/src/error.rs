@@ -0,0 +1,74 @@
+//! 核心库的错误类型
+//!
+//! 各模块定义自己的错误枚举：[`DocumentError`]、[`MetadataError`]、[`ConfigError`]、
+//! [`InitError`]、[`ConfigManagerError`]；[`Error`] 汇总它们，便于嵌入方统一处理。
+//!
+//! 每个错误都有稳定的错误码（[`ErrorCode::code`]），形如 `config.merge`：
+//! 错误信息的措辞可能调整，错误码不变，可用于匹配或上报。底层错误通过
+//! [`std::error::Error::source`] 串联。
+
+pub use crate::{
+  config::error::ConfigError,
+  document::DocumentError,
+  metadata::MetadataError,
+  state::{ConfigManagerError, init::InitError},
+};
+
+/// 稳定的错误码
+pub trait ErrorCode {
+  /// `模块.错误` 形式的错误码，如 `metadata.missing_file_name`
+  fn code(&self) -> &'static str;
+}
+
+/// 核心库所有错误的汇总
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+  #[error(transparent)]
+  Document(#[from] DocumentError),
+  #[error(transparent)]
+  Metadata(#[from] MetadataError),
+  #[error(transparent)]
+  Config(#[from] ConfigError),
+  #[error(transparent)]
+  Init(#[from] InitError),
+  #[error(transparent)]
+  ConfigManager(#[from] ConfigManagerError),
+}
+
+impl ErrorCode for Error {
+  fn code(&self) -> &'static str {
+    match self {
+      Error::Document(error) => error.code(),
+      Error::Metadata(error) => error.code(),
+      Error::Config(error) => error.code(),
+      Error::Init(error) => error.code(),
+      Error::ConfigManager(error) => error.code(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::{error::Error as _, path::PathBuf};
+
+  use super::{DocumentError, Error, ErrorCode, MetadataError};
+
+  #[test]
+  fn codes_and_sources_survive_wrapping() {
+    let metadata = MetadataError::MissingFileName {
+      path: PathBuf::from("/book/metadata/.."),
+    };
+    let error = Error::from(DocumentError::from(metadata.clone()));
+
+    assert_eq!(error.code(), "metadata.missing_file_name");
+    assert_eq!(error.to_string(), metadata.to_string());
+
+    let config = serde_json::from_str::<serde_json::Value>("{")
+      .map_err(|source| super::ConfigError::Merge { source })
+      .expect_err("invalid json");
+    let error = Error::from(config);
+    assert_eq!(error.code(), "config.merge");
+    let source = error.source().expect("source is chained");
+    assert!(source.is::<serde_json::Error>());
+  }
+}
@@ -3,7 +3,9 @@ pub mod diplomat_ffi;
 
 pub mod article;
 pub mod config;
+pub mod discovery;
 pub mod document;
+pub mod error;
 pub mod library;
 pub mod metadata;
 pub mod state;
@@ -0,0 +1,30 @@
+//! Errors of building metadata entities and loading their schemas
+
+use std::path::PathBuf;
+
+use crate::error::ErrorCode;
+
+/// Failure to build a [`MetadataEntity`](super::MetadataEntity) from a document
+/// or to load the [frontmatter schemas](super::EntitySchemas)
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MetadataError {
+  /// No frontmatter `id` and no file name to derive one from
+  #[error("cannot derive an entity id for {}: the path has no file name", path.display())]
+  MissingFileName { path: PathBuf },
+  /// A `_schema` file cannot be read or parsed
+  #[error("cannot load frontmatter schema {}: {message}", path.display())]
+  SchemaFile { path: PathBuf, message: String },
+  /// A declared frontmatter schema is not a valid JSON Schema
+  #[error("invalid frontmatter schema for `{type_}`: {message}")]
+  InvalidSchema { type_: String, message: String },
+}
+
+impl ErrorCode for MetadataError {
+  fn code(&self) -> &'static str {
+    match self {
+      MetadataError::MissingFileName { .. } => "metadata.missing_file_name",
+      MetadataError::SchemaFile { .. } => "metadata.schema_file",
+      MetadataError::InvalidSchema { .. } => "metadata.invalid_schema",
+    }
+  }
+}
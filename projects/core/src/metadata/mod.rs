@@ -2,10 +2,14 @@
 //!
 //! 提供小说元数据的定义、解析和查询接口
 
+pub mod error;
 pub mod model;
 pub mod parser;
 pub mod query;
+pub mod schema;
 
+pub use error::MetadataError;
 pub use model::MetadataEntity;
-pub use parser::{generate_namespace, infer_type_from_path, resolve_type};
+pub use parser::{generate_namespace, infer_type_from_path, resolve_entity_id, resolve_type};
 pub use query::{MetadataQuery, QueryResult};
+pub use schema::{EntitySchema, EntitySchemas, is_schema_file, metadata_dir_of};
@@ -5,8 +5,12 @@ use serde_json::Value;
 use ts_rs::TS;
 
 use crate::{
+  config::metadata::MetadataConfig,
   document::MarkdownParts,
-  metadata::parser::{generate_namespace, resolve_type},
+  metadata::{
+    MetadataError,
+    parser::{generate_namespace, resolve_entity_id, resolve_type},
+  },
 };
 
 /// Represents the parts needed to construct a `MetadataEntity`.
@@ -163,10 +167,10 @@ impl MetadataEntity {
   }
 }
 
-impl TryFrom<(MarkdownParts, &Path, &Path)> for MetadataEntity {
-  type Error = String;
+impl TryFrom<(MarkdownParts, &Path, &Path, &MetadataConfig)> for MetadataEntity {
+  type Error = MetadataError;
 
-  /// Create `MetadataEntity` from markdown parts, file path, and workspace root.
+  /// Create `MetadataEntity` from markdown parts, file path, workspace root and metadata layout.
   ///
   /// This is a convenience wrapper that constructs `MetadataEntityParts` and calls `from_parts()`.
   ///
@@ -174,25 +178,26 @@ impl TryFrom<(MarkdownParts, &Path, &Path)> for MetadataEntity {
   /// * `markdown_parts` - Parsed markdown with frontmatter and body
   /// * `file_path` - Full path to the metadata file
   /// * `workspace_root` - Path to the workspace root
+  /// * `layout` - Metadata directory layout used to infer type and namespace
   ///
   /// # Returns
   /// * `Ok(MetadataEntity)` on success
-  /// * `Err(String)` if entity construction fails
-  fn try_from(value: (MarkdownParts, &Path, &Path)) -> Result<Self, Self::Error> {
-    let (markdown_parts, file_path, workspace_root) = value;
-
-    // Generate ID from filename (without extension)
-    let id = file_path
-      .file_stem()
-      .and_then(|stem| stem.to_str())
-      .map(ToString::to_string)
-      .ok_or_else(|| "Failed to extract filename for ID".to_string())?;
+  /// * `Err(MetadataError)` if entity construction fails
+  fn try_from(value: (MarkdownParts, &Path, &Path, &MetadataConfig)) -> Result<Self, Self::Error> {
+    let (markdown_parts, file_path, workspace_root, layout) = value;
 
     // Resolve type: frontmatter takes priority
-    let type_ = resolve_type(file_path, &markdown_parts.frontmatter);
+    let type_ = resolve_type(file_path, &markdown_parts.frontmatter, layout);
 
     // Generate namespace from file location
-    let namespace = generate_namespace(file_path, workspace_root);
+    let namespace = generate_namespace(file_path, workspace_root, layout);
+
+    // Stable id: frontmatter `id`, else the namespace-qualified key
+    let id = resolve_entity_id(file_path, &markdown_parts.frontmatter, &namespace, &type_).ok_or_else(|| {
+      MetadataError::MissingFileName {
+        path: file_path.to_path_buf(),
+      }
+    })?;
 
     Ok(Self::from_parts(MetadataEntityParts {
       id,
@@ -284,9 +289,9 @@ mod tests {
     let workspace_root = Path::new("/project");
 
     // Use TryFrom to construct entity from markdown parts
-    let entity = MetadataEntity::try_from((parts, file_path, workspace_root)).unwrap();
+    let entity = MetadataEntity::try_from((parts, file_path, workspace_root, &MetadataConfig::default())).unwrap();
 
-    assert_eq!(entity.id, "entity-1");
+    assert_eq!(entity.id, "global/character/entity-1");
     assert_eq!(entity.type_, "character");
     assert_eq!(entity.namespace, "global");
     assert_eq!(entity.body, "A brave hero");
@@ -323,17 +328,32 @@ mod tests {
     let file_path = Path::new("/project/book-01/metadata/characters/hero.md");
     let workspace_root = Path::new("/project");
 
-    let result = MetadataEntity::try_from((parts, file_path, workspace_root));
+    let result = MetadataEntity::try_from((parts, file_path, workspace_root, &MetadataConfig::default()));
     assert!(result.is_ok());
 
     let entity = result.unwrap();
-    assert_eq!(entity.id, "hero");
+    assert_eq!(entity.id, "book-01/character/hero");
     assert_eq!(entity.type_, "character"); // From frontmatter
     assert_eq!(entity.namespace, "book-01");
     assert_eq!(entity.body, "# Character Details\nBrave and noble.");
     assert_eq!(entity.get_field("title"), Some(&json!("Hero")));
   }
 
+  #[test]
+  fn test_try_from_path_without_file_name_fails() {
+    let parts = crate::document::MarkdownParts::parse("# No id\n");
+    let file_path = Path::new("/project/metadata/..");
+
+    let error = MetadataEntity::try_from((parts, file_path, Path::new("/project"), &MetadataConfig::default()))
+      .expect_err("no file name");
+    assert_eq!(
+      error,
+      MetadataError::MissingFileName {
+        path: file_path.to_path_buf()
+      }
+    );
+  }
+
   #[test]
   fn test_try_from_markdown_parts_infers_type_from_path() {
     let markdown_content = "---\ntitle: Scene\n---\n## Opening scene";
@@ -342,8 +362,8 @@ mod tests {
     let file_path = Path::new("/project/book-01/part-01/metadata/scenes/opening.md");
     let workspace_root = Path::new("/project");
 
-    let entity = MetadataEntity::try_from((parts, file_path, workspace_root)).unwrap();
-    assert_eq!(entity.id, "opening");
+    let entity = MetadataEntity::try_from((parts, file_path, workspace_root, &MetadataConfig::default())).unwrap();
+    assert_eq!(entity.id, "book-01/part-01/scene/opening");
     assert_eq!(entity.type_, "scene"); // Inferred from path
     assert_eq!(entity.namespace, "book-01/part-01");
   }
@@ -356,8 +376,8 @@ mod tests {
     let file_path = Path::new("/project/metadata/settings.md");
     let workspace_root = Path::new("/project");
 
-    let entity = MetadataEntity::try_from((parts, file_path, workspace_root)).unwrap();
-    assert_eq!(entity.id, "settings");
+    let entity = MetadataEntity::try_from((parts, file_path, workspace_root, &MetadataConfig::default())).unwrap();
+    assert_eq!(entity.id, "global/metadata/settings");
     assert_eq!(entity.namespace, "global");
     assert_eq!(entity.body, "Global metadata");
   }
@@ -371,7 +391,7 @@ mod tests {
     let file_path = Path::new("/project/metadata/scenes/episode.md");
     let workspace_root = Path::new("/project");
 
-    let entity = MetadataEntity::try_from((parts, file_path, workspace_root)).unwrap();
+    let entity = MetadataEntity::try_from((parts, file_path, workspace_root, &MetadataConfig::default())).unwrap();
     assert_eq!(entity.type_, "event"); // Frontmatter priority
   }
 
@@ -2,16 +2,20 @@ use std::path::Path;
 
 use serde_json::Value;
 
+use crate::config::metadata::MetadataConfig;
+
 /// Infers the type from a file path based on its directory structure.
 ///
 /// # Rules
-/// - `metadata/*.md` → "metadata"
-/// - `metadata/characters/*.md` → "character"
-/// - `metadata/scenes/*.md` → "scene"
+/// - Files directly in a metadata directory (`metadata/*.md`) → "metadata"
+/// - Parent folders listed in `folder_types` use the mapped type
+///   (`metadata/characters/*.md` → "character", `设定/人物/*.md` → "character")
 /// - Others: singularize parent folder name
+/// - When `types` is set, an inferred type missing from it becomes "metadata"
 ///
 /// # Arguments
 /// * `path` - File path to infer type from
+/// * `layout` - Metadata directory layout of the workspace
 ///
 /// # Returns
 /// Inferred type as a String
@@ -19,10 +23,10 @@ use serde_json::Value;
 /// # Examples
 /// ```ignore
 /// let path = Path::new("metadata/characters/hero.md");
-/// assert_eq!(infer_type_from_path(path), "character");
+/// assert_eq!(infer_type_from_path(path, &MetadataConfig::default()), "character");
 /// ```
 #[must_use]
-pub fn infer_type_from_path(path: &Path) -> String {
+pub fn infer_type_from_path(path: &Path, layout: &MetadataConfig) -> String {
   let components: Vec<&std::ffi::OsStr> = path
     .components()
     .filter_map(|c| {
@@ -39,32 +43,35 @@ pub fn infer_type_from_path(path: &Path) -> String {
     return "metadata".to_string();
   }
 
-  // Get parent directory name (second to last component, before filename)
-  if components.len() >= 2 {
+  let inferred = if components.len() >= 2 {
+    // Get parent directory name (second to last component, before filename)
     let parent_name = components[components.len() - 2].to_string_lossy().to_string();
 
-    // Handle special cases for nested metadata directories
-    if parent_name == "characters" {
-      return "character".to_string();
+    if layout.is_metadata_dir(&parent_name) {
+      return "metadata".to_string();
     }
-    if parent_name == "scenes" {
-      return "scene".to_string();
+    match layout.folder_types.get(&parent_name) {
+      Some(type_) => type_.clone(),
+      None => singularize(&parent_name),
     }
+  } else {
+    // Default: singularize filename without extension
+    let filename = components
+      .last()
+      .map(|c| c.to_string_lossy())
+      .unwrap_or_default()
+      .to_string();
 
-    // Singularize the parent directory name
-    return singularize(&parent_name);
-  }
-
-  // Default: singularize filename without extension
-  let filename = components
-    .last()
-    .map(|c| c.to_string_lossy())
-    .unwrap_or_default()
-    .to_string();
+    let name_without_ext = filename.split('.').next().unwrap_or(&filename).to_string();
 
-  let name_without_ext = filename.split('.').next().unwrap_or(&filename).to_string();
+    singularize(&name_without_ext)
+  };
 
-  singularize(&name_without_ext)
+  if layout.is_known_type(&inferred) {
+    inferred
+  } else {
+    "metadata".to_string()
+  }
 }
 
 /// Converts a plural word to singular form using simple rules.
@@ -98,6 +105,7 @@ fn singularize(s: &str) -> String {
 /// # Arguments
 /// * `path` - File path to use for inference if needed
 /// * `frontmatter` - Frontmatter JSON to check for type field
+/// * `layout` - Metadata directory layout used for path inference
 ///
 /// # Returns
 /// Resolved type as a String
@@ -106,13 +114,13 @@ fn singularize(s: &str) -> String {
 /// ```ignore
 /// let path = Path::new("metadata/characters/hero.md");
 /// let fm = json!({ "type": "protagonist" });
-/// assert_eq!(resolve_type(path, &fm), "protagonist");
+/// assert_eq!(resolve_type(path, &fm, &MetadataConfig::default()), "protagonist");
 ///
 /// let fm_empty = json!({});
-/// assert_eq!(resolve_type(path, &fm_empty), "character");
+/// assert_eq!(resolve_type(path, &fm_empty, &MetadataConfig::default()), "character");
 /// ```
 #[must_use]
-pub fn resolve_type(path: &Path, frontmatter: &Value) -> String {
+pub fn resolve_type(path: &Path, frontmatter: &Value, layout: &MetadataConfig) -> String {
   // Check frontmatter for explicit type
   if let Some(type_value) = frontmatter.get("type")
     && let Some(type_str) = type_value.as_str()
@@ -121,7 +129,7 @@ pub fn resolve_type(path: &Path, frontmatter: &Value) -> String {
   }
 
   // Fall back to path inference
-  infer_type_from_path(path)
+  infer_type_from_path(path, layout)
 }
 
 /// Generates a namespace from a metadata file path relative to workspace root.
@@ -130,11 +138,13 @@ pub fn resolve_type(path: &Path, frontmatter: &Value) -> String {
 /// - `metadata/` → "global"
 /// - `book-01/metadata/` → "book-01"
 /// - `book-01/part-01/metadata/` → "book-01/part-01"
-/// - Extracts path segments between workspace root and metadata directory
+/// - Extracts path segments between workspace root and the first metadata
+///   directory (any of the configured `dirs`, e.g. `设定/`)
 ///
 /// # Arguments
 /// * `metadata_path` - Full path to metadata file
 /// * `workspace_root` - Path to workspace root
+/// * `layout` - Metadata directory layout of the workspace
 ///
 /// # Returns
 /// Generated namespace as a String (or "global" if at workspace root)
@@ -143,10 +153,10 @@ pub fn resolve_type(path: &Path, frontmatter: &Value) -> String {
 /// ```ignore
 /// let metadata_path = Path::new("/project/book-01/metadata/characters.md");
 /// let workspace_root = Path::new("/project");
-/// assert_eq!(generate_namespace(metadata_path, workspace_root), "book-01");
+/// assert_eq!(generate_namespace(metadata_path, workspace_root, &MetadataConfig::default()), "book-01");
 /// ```
 #[must_use]
-pub fn generate_namespace(metadata_path: &Path, workspace_root: &Path) -> String {
+pub fn generate_namespace(metadata_path: &Path, workspace_root: &Path, layout: &MetadataConfig) -> String {
   // Try to get relative path from workspace root to metadata file
   if let Ok(relative) = metadata_path.strip_prefix(workspace_root) {
     let components: Vec<&std::ffi::OsStr> = relative
@@ -160,14 +170,17 @@ pub fn generate_namespace(metadata_path: &Path, workspace_root: &Path) -> String
       })
       .collect();
 
-    // Find "metadata" directory and extract path before it
-    if let Some(metadata_idx) = components.iter().position(|c| c.to_string_lossy() == "metadata") {
+    // Find the metadata directory and extract path before it
+    if let Some(metadata_idx) = components
+      .iter()
+      .position(|c| layout.is_metadata_dir(&c.to_string_lossy()))
+    {
       if metadata_idx == 0 {
         // metadata at root → "global"
         return "global".to_string();
       }
 
-      // Join components before "metadata" with "/"
+      // Join components before the metadata directory with "/"
       let ns_parts: Vec<String> = components[..metadata_idx]
         .iter()
         .map(|c| c.to_string_lossy().to_string())
@@ -181,6 +194,44 @@ pub fn generate_namespace(metadata_path: &Path, workspace_root: &Path) -> String
   "global".to_string()
 }
 
+/// Resolves the stable id of a metadata entity.
+///
+/// # Rules
+/// - An explicit, non-empty frontmatter `id` wins
+/// - Otherwise `{namespace}/{type}/{file stem}`, e.g. `book-01/character/hero`
+///
+/// The key depends only on the location relative to the workspace root, so ids
+/// survive cloning the workspace elsewhere; an explicit `id` also survives moves.
+///
+/// # Arguments
+/// * `path` - Path to the metadata file
+/// * `frontmatter` - Frontmatter JSON to check for an `id` field
+/// * `namespace` - Namespace from [`generate_namespace`]
+/// * `type_` - Type from [`resolve_type`]
+///
+/// # Returns
+/// The id, or None if the path has no usable file stem and no explicit id is set
+///
+/// # Examples
+/// ```ignore
+/// let path = Path::new("/project/book-01/metadata/characters/hero.md");
+/// assert_eq!(
+///   resolve_entity_id(path, &json!({}), "book-01", "character"),
+///   Some("book-01/character/hero".to_string())
+/// );
+/// ```
+#[must_use]
+pub fn resolve_entity_id(path: &Path, frontmatter: &Value, namespace: &str, type_: &str) -> Option<String> {
+  if let Some(id) = frontmatter.get("id").and_then(Value::as_str).map(str::trim)
+    && !id.is_empty()
+  {
+    return Some(id.to_string());
+  }
+
+  let stem = path.file_stem().and_then(|stem| stem.to_str())?;
+  Some(format!("{namespace}/{type_}/{stem}"))
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -188,31 +239,74 @@ mod tests {
   #[test]
   fn test_infer_type_from_path_metadata_root() {
     let path = Path::new("metadata/config.md");
-    assert_eq!(infer_type_from_path(path), "metadata");
+    assert_eq!(infer_type_from_path(path, &MetadataConfig::default()), "metadata");
   }
 
   #[test]
   fn test_infer_type_from_path_characters() {
     let path = Path::new("metadata/characters/hero.md");
-    assert_eq!(infer_type_from_path(path), "character");
+    assert_eq!(infer_type_from_path(path, &MetadataConfig::default()), "character");
   }
 
   #[test]
   fn test_infer_type_from_path_scenes() {
     let path = Path::new("metadata/scenes/opening.md");
-    assert_eq!(infer_type_from_path(path), "scene");
+    assert_eq!(infer_type_from_path(path, &MetadataConfig::default()), "scene");
   }
 
   #[test]
   fn test_infer_type_from_path_singularize_notes() {
     let path = Path::new("metadata/notes/chapter1.md");
-    assert_eq!(infer_type_from_path(path), "note");
+    assert_eq!(infer_type_from_path(path, &MetadataConfig::default()), "note");
   }
 
   #[test]
   fn test_infer_type_from_path_singularize_entries() {
     let path = Path::new("metadata/entries/plot.md");
-    assert_eq!(infer_type_from_path(path), "entry");
+    assert_eq!(infer_type_from_path(path, &MetadataConfig::default()), "entry");
+  }
+
+  fn chinese_layout() -> MetadataConfig {
+    MetadataConfig {
+      dirs: vec!["设定".to_string()],
+      folder_types: [("人物".to_string(), "character".to_string())].into(),
+      types: vec!["character".to_string(), "location".to_string()],
+      ..MetadataConfig::default()
+    }
+  }
+
+  #[test]
+  fn test_infer_type_from_path_configured_layout() {
+    let layout = chinese_layout();
+    assert_eq!(
+      infer_type_from_path(Path::new("设定/人物/李白.md"), &layout),
+      "character"
+    );
+    assert_eq!(infer_type_from_path(Path::new("设定/世界观.md"), &layout), "metadata");
+    assert_eq!(
+      infer_type_from_path(Path::new("设定/locations/a.md"), &layout),
+      "location"
+    );
+    // Not in the type list
+    assert_eq!(infer_type_from_path(Path::new("设定/杂项/a.md"), &layout), "metadata");
+  }
+
+  #[test]
+  fn test_generate_namespace_configured_layout() {
+    let layout = chinese_layout();
+    let workspace_root = Path::new("/project");
+    assert_eq!(
+      generate_namespace(Path::new("/project/卷一/设定/人物/李白.md"), workspace_root, &layout),
+      "卷一"
+    );
+    assert_eq!(
+      generate_namespace(
+        Path::new("/project/metadata/characters/hero.md"),
+        workspace_root,
+        &layout
+      ),
+      "global"
+    );
   }
 
   #[test]
@@ -251,7 +345,10 @@ mod tests {
     let frontmatter = serde_json::json!({ "type": "protagonist" });
     let path = Path::new("metadata/characters/hero.md");
 
-    assert_eq!(resolve_type(path, &frontmatter), "protagonist");
+    assert_eq!(
+      resolve_type(path, &frontmatter, &MetadataConfig::default()),
+      "protagonist"
+    );
   }
 
   #[test]
@@ -259,7 +356,10 @@ mod tests {
     let frontmatter = serde_json::json!({ "name": "Hero" });
     let path = Path::new("metadata/characters/hero.md");
 
-    assert_eq!(resolve_type(path, &frontmatter), "character");
+    assert_eq!(
+      resolve_type(path, &frontmatter, &MetadataConfig::default()),
+      "character"
+    );
   }
 
   #[test]
@@ -267,7 +367,7 @@ mod tests {
     let frontmatter = serde_json::json!({});
     let path = Path::new("metadata/scenes/opening.md");
 
-    assert_eq!(resolve_type(path, &frontmatter), "scene");
+    assert_eq!(resolve_type(path, &frontmatter, &MetadataConfig::default()), "scene");
   }
 
   #[test]
@@ -275,7 +375,10 @@ mod tests {
     let frontmatter = serde_json::json!({ "type": 123 });
     let path = Path::new("metadata/characters/hero.md");
 
-    assert_eq!(resolve_type(path, &frontmatter), "character");
+    assert_eq!(
+      resolve_type(path, &frontmatter, &MetadataConfig::default()),
+      "character"
+    );
   }
 
   #[test]
@@ -283,7 +386,10 @@ mod tests {
     let metadata_path = Path::new("/project/metadata/config.md");
     let workspace_root = Path::new("/project");
 
-    assert_eq!(generate_namespace(metadata_path, workspace_root), "global");
+    assert_eq!(
+      generate_namespace(metadata_path, workspace_root, &MetadataConfig::default()),
+      "global"
+    );
   }
 
   #[test]
@@ -291,7 +397,10 @@ mod tests {
     let metadata_path = Path::new("/project/book-01/metadata/characters.md");
     let workspace_root = Path::new("/project");
 
-    assert_eq!(generate_namespace(metadata_path, workspace_root), "book-01");
+    assert_eq!(
+      generate_namespace(metadata_path, workspace_root, &MetadataConfig::default()),
+      "book-01"
+    );
   }
 
   #[test]
@@ -299,7 +408,10 @@ mod tests {
     let metadata_path = Path::new("/project/book-01/part-01/metadata/scene.md");
     let workspace_root = Path::new("/project");
 
-    assert_eq!(generate_namespace(metadata_path, workspace_root), "book-01/part-01");
+    assert_eq!(
+      generate_namespace(metadata_path, workspace_root, &MetadataConfig::default()),
+      "book-01/part-01"
+    );
   }
 
   #[test]
@@ -307,7 +419,10 @@ mod tests {
     let metadata_path = Path::new("metadata/config.md");
     let workspace_root = Path::new(".");
 
-    assert_eq!(generate_namespace(metadata_path, workspace_root), "global");
+    assert_eq!(
+      generate_namespace(metadata_path, workspace_root, &MetadataConfig::default()),
+      "global"
+    );
   }
 
   #[test]
@@ -316,8 +431,31 @@ mod tests {
     let workspace_root = Path::new("/project");
 
     assert_eq!(
-      generate_namespace(metadata_path, workspace_root),
+      generate_namespace(metadata_path, workspace_root, &MetadataConfig::default()),
       "series-01/book-02/chapter-05"
     );
   }
+
+  #[test]
+  fn test_resolve_entity_id_prefers_frontmatter_id() {
+    let path = Path::new("/project/book-01/metadata/characters/hero.md");
+
+    assert_eq!(
+      resolve_entity_id(
+        path,
+        &serde_json::json!({ "id": " hero-of-time " }),
+        "book-01",
+        "character"
+      ),
+      Some("hero-of-time".to_string())
+    );
+    assert_eq!(
+      resolve_entity_id(path, &serde_json::json!({ "id": "" }), "book-01", "character"),
+      Some("book-01/character/hero".to_string())
+    );
+    assert_eq!(
+      resolve_entity_id(path, &serde_json::json!({}), "global", "character"),
+      Some("global/character/hero".to_string())
+    );
+  }
 }
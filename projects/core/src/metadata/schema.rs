@@ -0,0 +1,365 @@
+//! Per-type frontmatter schemas of metadata entities
+//!
+//! Each metadata type may declare a JSON Schema for its frontmatter, either in
+//! the root config (`metadata.schemas.<type>`) or as a file
+//! `<metadata dir>/_schema/<type>.yaml` (any base config format works). The
+//! config wins when both declare the same type.
+//!
+//! Schema files of the metadata directories at the workspace root apply to
+//! every document. A nested metadata directory (e.g. `book-01/metadata`) may
+//! add its own `_schema` files, which override the root ones for the
+//! documents inside it.
+
+use std::{
+  collections::BTreeMap,
+  path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use super::MetadataError;
+use crate::config::{
+  file_def::base_config_file_format,
+  metadata::MetadataConfig,
+  schema::{ConfigKeyDoc, SchemaViolation, collect_key_docs, collect_violations},
+};
+
+/// Directory under each metadata directory holding `<type>.yaml` schema files
+pub const SCHEMA_DIR: &str = "_schema";
+
+/// The compiled schema of one metadata type
+#[derive(Debug, Clone)]
+pub struct EntitySchema {
+  schema: Value,
+  validator: jsonschema::Validator,
+}
+
+impl EntitySchema {
+  /// Compile the schema of `type_`.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`MetadataError::InvalidSchema`] when `schema` is not a valid JSON Schema.
+  pub fn new(type_: &str, schema: Value) -> Result<Self, MetadataError> {
+    let validator = jsonschema::validator_for(&schema).map_err(|error| MetadataError::InvalidSchema {
+      type_: type_.to_string(),
+      message: error.to_string(),
+    })?;
+    Ok(Self { schema, validator })
+  }
+
+  /// Check `frontmatter` against the schema.
+  ///
+  /// `text` is the whole document; violations are located on its frontmatter
+  /// lines (1-based), a missing required key has no line.
+  #[must_use]
+  pub fn validate(&self, frontmatter: &Value, text: &str) -> Vec<SchemaViolation> {
+    let block = frontmatter_block(text);
+    let mut violations = Vec::new();
+    for error in self.validator.iter_errors(frontmatter) {
+      collect_violations(&error, block, &mut violations);
+    }
+    violations.sort_by(|a, b| a.line.cmp(&b.line).then_with(|| a.key.cmp(&b.key)));
+    violations
+  }
+
+  /// The frontmatter keys the schema declares, nested keys dotted, sorted by key
+  #[must_use]
+  pub fn key_docs(&self) -> Vec<ConfigKeyDoc> {
+    let mut docs = Vec::new();
+    collect_key_docs(&self.schema, &self.schema, "", &mut docs);
+    docs.sort_by(|a, b| a.key.cmp(&b.key));
+    docs
+  }
+}
+
+/// The frontmatter schemas of all metadata types of a workspace
+#[derive(Debug, Clone, Default)]
+pub struct EntitySchemas {
+  schemas: BTreeMap<String, EntitySchema>,
+}
+
+impl EntitySchemas {
+  /// Schemas from the `_schema` directories of `layout` under `workspace_root`,
+  /// overridden by the schemas declared in `layout` itself.
+  ///
+  /// # Errors
+  ///
+  /// Returns the first schema file that cannot be read or parsed, or the first
+  /// schema that does not compile.
+  pub fn load(workspace_root: &Path, layout: &MetadataConfig) -> Result<Self, MetadataError> {
+    Self::load_for(workspace_root, None, layout)
+  }
+
+  /// Schemas of the documents inside `metadata_dir` (see [`metadata_dir_of`]):
+  /// like [`EntitySchemas::load`], with the `_schema` files of a nested
+  /// `metadata_dir` overriding those at the workspace root.
+  ///
+  /// # Errors
+  ///
+  /// Same as [`EntitySchemas::load`].
+  pub fn load_for(
+    workspace_root: &Path,
+    metadata_dir: Option<&Path>,
+    layout: &MetadataConfig,
+  ) -> Result<Self, MetadataError> {
+    let mut dirs: Vec<PathBuf> = layout.dirs.iter().map(|dir| workspace_root.join(dir)).collect();
+    if let Some(metadata_dir) = metadata_dir
+      && !dirs.iter().any(|dir| dir == metadata_dir)
+    {
+      dirs.push(metadata_dir.to_path_buf());
+    }
+
+    let mut declared = BTreeMap::new();
+    for dir in dirs {
+      for path in schema_files(&dir.join(SCHEMA_DIR)) {
+        let Some(type_) = path.file_stem().and_then(|stem| stem.to_str()) else {
+          continue;
+        };
+        declared.insert(type_.to_string(), read_schema_file(&path)?);
+      }
+    }
+    declared.extend(layout.schemas.clone());
+    Self::new(declared)
+  }
+
+  /// Compile `schemas`, keyed by metadata type.
+  ///
+  /// # Errors
+  ///
+  /// Returns [`MetadataError::InvalidSchema`] for the first schema that does not compile.
+  pub fn new(schemas: impl IntoIterator<Item = (String, Value)>) -> Result<Self, MetadataError> {
+    let schemas = schemas
+      .into_iter()
+      .map(|(type_, schema)| EntitySchema::new(&type_, schema).map(|schema| (type_, schema)))
+      .collect::<Result<_, _>>()?;
+    Ok(Self { schemas })
+  }
+
+  /// The schema of `type_`, if one is declared
+  #[must_use]
+  pub fn get(&self, type_: &str) -> Option<&EntitySchema> {
+    self.schemas.get(type_)
+  }
+
+  #[must_use]
+  pub fn is_empty(&self) -> bool {
+    self.schemas.is_empty()
+  }
+}
+
+/// The innermost metadata directory of `layout` that contains `path`, stopping
+/// at `workspace_root`; `None` for documents outside any metadata directory.
+#[must_use]
+pub fn metadata_dir_of(path: &Path, workspace_root: &Path, layout: &MetadataConfig) -> Option<PathBuf> {
+  path
+    .ancestors()
+    .skip(1)
+    .take_while(|dir| dir.starts_with(workspace_root) && *dir != workspace_root)
+    .find(|dir| {
+      dir
+        .file_name()
+        .is_some_and(|name| layout.is_metadata_dir(&name.to_string_lossy()))
+    })
+    .map(Path::to_path_buf)
+}
+
+/// Whether `path` is a schema file, i.e. lies directly in the `_schema`
+/// directory of a metadata directory of `layout`.
+#[must_use]
+pub fn is_schema_file(path: &Path, layout: &MetadataConfig) -> bool {
+  let Some(schema_dir) = path.parent() else {
+    return false;
+  };
+  schema_dir.file_name().is_some_and(|name| name == SCHEMA_DIR)
+    && schema_dir
+      .parent()
+      .and_then(Path::file_name)
+      .is_some_and(|name| layout.is_metadata_dir(&name.to_string_lossy()))
+}
+
+/// Schema files directly inside `dir`, sorted by path; none when `dir` is missing
+fn schema_files(dir: &Path) -> Vec<PathBuf> {
+  let Ok(entries) = std::fs::read_dir(dir) else {
+    return Vec::new();
+  };
+  let mut files: Vec<PathBuf> = entries
+    .filter_map(Result::ok)
+    .map(|entry| entry.path())
+    .filter(|path| path.is_file())
+    .filter(|path| {
+      path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| base_config_file_format(ext).is_some())
+    })
+    .collect();
+  files.sort();
+  files
+}
+
+fn read_schema_file(path: &Path) -> Result<Value, MetadataError> {
+  let schema_file_error = |message: String| MetadataError::SchemaFile {
+    path: path.to_path_buf(),
+    message,
+  };
+  let ext = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+  let format =
+    base_config_file_format(ext).ok_or_else(|| schema_file_error(format!("unsupported extension `{ext}`")))?;
+  config::Config::builder()
+    .add_source(config::File::from(path).format(format))
+    .build()
+    .and_then(config::Config::try_deserialize::<Value>)
+    .map_err(|error| schema_file_error(error.to_string()))
+}
+
+/// The lines of `text` up to and including the closing frontmatter delimiter;
+/// empty when the document has no frontmatter.
+fn frontmatter_block(text: &str) -> &str {
+  if !text.trim_start().starts_with("---") {
+    return "";
+  }
+  let mut end = 0;
+  for (index, line) in text.split_inclusive('\n').enumerate() {
+    end += line.len();
+    if index > 0 && line.trim().starts_with("---") {
+      return &text[..end];
+    }
+  }
+  text
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+  use tempfile::TempDir;
+
+  use super::{EntitySchema, EntitySchemas, is_schema_file, metadata_dir_of};
+  use crate::{config::metadata::MetadataConfig, error::ErrorCode};
+
+  fn character_schema() -> serde_json::Value {
+    json!({
+      "type": "object",
+      "required": ["name"],
+      "properties": {
+        "name": { "type": "string", "description": "Display name" },
+        "aliases": { "type": "array", "items": { "type": "string" } },
+        "age": { "type": "integer" },
+        "gender": { "enum": ["female", "male", "other"] }
+      }
+    })
+  }
+
+  #[test]
+  fn violations_point_at_frontmatter_lines() {
+    let schema = EntitySchema::new("character", character_schema()).expect("valid schema");
+    let text = "---\ntype: character\nage: old\naliases:\n  - Al\n  - 3\n---\nage: is not checked here\n";
+    let frontmatter = json!({ "type": "character", "age": "old", "aliases": ["Al", 3] });
+
+    let violations = schema.validate(&frontmatter, text);
+    let located: Vec<_> = violations
+      .iter()
+      .map(|violation| (violation.key.as_str(), violation.line))
+      .collect();
+    assert_eq!(located, vec![("", None), ("age", Some(3)), ("aliases.1", Some(4))]);
+    assert!(violations[0].message.contains("\"name\" is a required property"));
+
+    let valid = json!({ "name": "Alice", "gender": "female", "aliases": ["Al"] });
+    assert!(schema.validate(&valid, "---\nname: Alice\n---\n").is_empty());
+  }
+
+  #[test]
+  fn key_docs_describe_declared_properties() {
+    let schema = EntitySchema::new("character", character_schema()).expect("valid schema");
+    let docs = schema.key_docs();
+
+    let keys: Vec<_> = docs.iter().map(|doc| doc.key.as_str()).collect();
+    assert_eq!(keys, vec!["age", "aliases", "gender", "name"]);
+    assert_eq!(docs[1].value_type, "array of string");
+    assert_eq!(
+      docs[2].allowed_values,
+      vec![json!("female"), json!("male"), json!("other")]
+    );
+    assert_eq!(docs[3].description.as_deref(), Some("Display name"));
+  }
+
+  #[test]
+  fn load_merges_schema_files_with_config() {
+    let workspace = TempDir::new().expect("tempdir");
+    let schema_dir = workspace.path().join("metadata/_schema");
+    std::fs::create_dir_all(&schema_dir).expect("create schema dir");
+    std::fs::write(
+      schema_dir.join("character.yaml"),
+      "required: [name]\nproperties:\n  name:\n    type: string\n",
+    )
+    .expect("write schema");
+    std::fs::write(schema_dir.join("scene.json"), r#"{ "required": ["title"] }"#).expect("write schema");
+    std::fs::write(schema_dir.join("notes.txt"), "not a schema").expect("write notes");
+
+    let mut layout = MetadataConfig::default();
+    layout
+      .schemas
+      .insert("scene".to_string(), json!({ "required": ["summary"] }));
+    let schemas = EntitySchemas::load(workspace.path(), &layout).expect("load schemas");
+
+    let character = schemas.get("character").expect("schema from file");
+    assert_eq!(character.validate(&json!({}), "").len(), 1);
+    let scene = schemas.get("scene").expect("schema from config");
+    assert!(scene.validate(&json!({ "summary": "Opening" }), "").is_empty());
+    assert!(schemas.get("notes").is_none());
+  }
+
+  #[test]
+  fn invalid_schemas_are_reported() {
+    let error = EntitySchemas::new([("character".to_string(), json!({ "type": 1 }))]).expect_err("invalid schema");
+    assert_eq!(error.code(), "metadata.invalid_schema");
+    assert!(
+      error
+        .to_string()
+        .starts_with("invalid frontmatter schema for `character`"),
+      "{error}"
+    );
+
+    let workspace = TempDir::new().expect("tempdir");
+    let schema_dir = workspace.path().join("metadata/_schema");
+    std::fs::create_dir_all(&schema_dir).expect("create schema dir");
+    std::fs::write(schema_dir.join("character.yaml"), "required: [name").expect("write schema");
+    let error = EntitySchemas::load(workspace.path(), &MetadataConfig::default()).expect_err("broken file");
+    assert_eq!(error.code(), "metadata.schema_file");
+  }
+
+  #[test]
+  fn nested_metadata_dirs_override_root_schema_files() {
+    let workspace = TempDir::new().expect("tempdir");
+    let root = workspace.path();
+    let root_schema_dir = root.join("metadata/_schema");
+    let nested_schema_dir = root.join("book-01/metadata/_schema");
+    std::fs::create_dir_all(&root_schema_dir).expect("create schema dir");
+    std::fs::create_dir_all(&nested_schema_dir).expect("create schema dir");
+    std::fs::write(root_schema_dir.join("character.yaml"), "required: [name]\n").expect("write schema");
+    std::fs::write(root_schema_dir.join("scene.yaml"), "required: [title]\n").expect("write schema");
+    std::fs::write(nested_schema_dir.join("character.yaml"), "required: [alias]\n").expect("write schema");
+
+    let layout = MetadataConfig::default();
+    let hero = root.join("book-01/metadata/characters/hero.md");
+    let nested = metadata_dir_of(&hero, root, &layout);
+    assert_eq!(nested, Some(root.join("book-01/metadata")));
+    assert_eq!(metadata_dir_of(&root.join("chapter-01.md"), root, &layout), None);
+    assert!(is_schema_file(&nested_schema_dir.join("character.yaml"), &layout));
+    assert!(!is_schema_file(
+      &root.join("book-01/metadata/characters/hero.md"),
+      &layout
+    ));
+
+    let schemas = EntitySchemas::load_for(root, nested.as_deref(), &layout).expect("load schemas");
+    let character = schemas.get("character").expect("nested schema");
+    let violations = character.validate(&json!({ "name": "Hero" }), "");
+    assert_eq!(violations.len(), 1);
+    assert!(violations[0].message.contains("\"alias\""), "{}", violations[0].message);
+    assert!(schemas.get("scene").is_some());
+
+    let root_schemas = EntitySchemas::load(root, &layout).expect("load schemas");
+    let character = root_schemas.get("character").expect("root schema");
+    assert!(character.validate(&json!({ "name": "Hero" }), "").is_empty());
+  }
+}
@@ -1,19 +1,19 @@
 use derive_builder::Builder;
 
-use super::{feat::Feature, manager::config::ConfigManager};
+use super::feat::Feature;
 
 // 使用 `static_assertions` 在编译期断言 `State: Send + Sync`
 static_assertions::assert_impl_all!(State: Send, Sync);
 
+/// 进程级全局状态
+///
+/// 配置与工作区相关，不在此处保存：由 LSP/CLI 针对实际的工作区根目录创建 `ConfigManager`。
 #[derive(Builder, Debug, getset::Getters)]
 #[builder(setter(into), build_fn(skip))]
 #[allow(dead_code)]
 pub struct State {
   #[getset(get = "pub")]
   feature: Feature,
-  #[builder(private)]
-  #[getset(get = "pub")]
-  config_manager: ConfigManager,
 }
 
 impl StateBuilder {
@@ -23,11 +23,6 @@ impl StateBuilder {
       .clone()
       .ok_or_else(|| StateBuilderError::ValidationError("wrong feature".to_string()))?;
 
-    let config_manager = ConfigManager::new(feature.clone());
-
-    Ok(State {
-      feature,
-      config_manager,
-    })
+    Ok(State { feature })
   }
 }
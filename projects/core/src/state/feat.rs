@@ -1,14 +1,24 @@
-use std::{collections::HashMap, error::Error, sync::Arc};
+use std::{cell::RefCell, collections::HashMap, sync::Arc};
 
 use derive_new::new;
 
-pub type LoaderFn =
-  Arc<dyn Fn(&str) -> Result<HashMap<String, serde_json::Value>, Box<dyn Error + Send + Sync>> + Send + Sync>;
+use crate::config::{error::ConfigError, overrides::ConfigOverrides};
+
+/// JS/TS 配置加载器：求值配置脚本并返回其导出的配置；失败时返回 [`ConfigError::Script`]
+pub type LoaderFn = Arc<dyn Fn(&str) -> Result<HashMap<String, serde_json::Value>, ConfigError> + Send + Sync>;
+
+thread_local! {
+  /// 当前线程上替代全局状态的 Feature（见 [`Feature::scope`]）
+  static SCOPED_FEATURE: RefCell<Option<Feature>> = const { RefCell::new(None) };
+}
 
 #[derive(Clone, new)]
 pub struct Feature {
   js_loader: Option<LoaderFn>,
   ts_loader: Option<LoaderFn>,
+  /// 环境变量与命令行的配置覆盖，叠加在所有配置文件之上
+  #[new(default)]
+  config_overrides: ConfigOverrides,
 }
 
 impl std::fmt::Debug for Feature {
@@ -16,11 +26,51 @@ impl std::fmt::Debug for Feature {
     f.debug_struct("Feature")
       .field("js_loader", &self.js_loader.as_ref().map(|_| "<function>"))
       .field("ts_loader", &self.ts_loader.as_ref().map(|_| "<function>"))
+      .field("config_overrides", &self.config_overrides)
       .finish()
   }
 }
 
 impl Feature {
+  #[must_use]
+  pub fn with_config_overrides(mut self, config_overrides: ConfigOverrides) -> Self {
+    self.config_overrides = config_overrides;
+    self
+  }
+
+  /// 替换 JS/TS 加载器，例如为其加上调用方自己的运行时选择
+  #[must_use]
+  pub fn map_loaders(mut self, map: impl Fn(LoaderFn) -> LoaderFn) -> Self {
+    self.js_loader = self.js_loader.map(&map);
+    self.ts_loader = self.ts_loader.map(&map);
+    self
+  }
+
+  /// 在当前线程执行 `f`，期间求值 JS/TS 配置使用本 Feature 的加载器而非全局状态中的
+  ///
+  /// 配置格式的解析接口无法携带参数，`ConfigManager` 借此让同一进程中的多个实例使用各自的加载器。
+  pub fn scope<T>(&self, f: impl FnOnce() -> T) -> T {
+    struct Restore(Option<Feature>);
+    impl Drop for Restore {
+      fn drop(&mut self) {
+        SCOPED_FEATURE.set(self.0.take());
+      }
+    }
+
+    let _restore = Restore(SCOPED_FEATURE.replace(Some(self.clone())));
+    f()
+  }
+
+  /// 当前线程上由 [`Self::scope`] 设置的 Feature
+  pub(crate) fn scoped() -> Option<Feature> {
+    SCOPED_FEATURE.with_borrow(Clone::clone)
+  }
+
+  #[must_use]
+  pub fn config_overrides(&self) -> &ConfigOverrides {
+    &self.config_overrides
+  }
+
   #[must_use]
   pub fn js_support(&self) -> bool {
     self.js_loader.is_some()
@@ -1,16 +1,30 @@
 use std::sync::OnceLock;
 
-use super::{_state::StateBuilder, State, feat::Feature};
+use super::{_state::StateBuilder, State, StateBuilderError, feat::Feature};
+use crate::error::ErrorCode;
 
 /// 全局状态初始化器错误类型
-#[derive(Debug)]
+#[derive(Debug, thiserror::Error)]
 pub enum InitError {
   /// 已经初始化过
+  #[error("global state is already initialized")]
   AlreadyInitialized,
   /// 尚未初始化就尝试访问
+  #[error("global state is not initialized")]
   Uninitialized,
   /// state 构建错误
-  StateBuildError,
+  #[error("failed to build global state: {0}")]
+  StateBuildError(#[source] StateBuilderError),
+}
+
+impl ErrorCode for InitError {
+  fn code(&self) -> &'static str {
+    match self {
+      InitError::AlreadyInitialized => "state.already_initialized",
+      InitError::Uninitialized => "state.uninitialized",
+      InitError::StateBuildError(_) => "state.build",
+    }
+  }
 }
 
 pub struct Initializer;
@@ -22,23 +36,16 @@ impl Initializer {
   ///
   /// # Errors
   ///
-  /// Returns `InitError::AlreadyInitialized` if the global state was already initialized.
-  ///
-  /// # Panics
-  ///
-  /// Panics if the `State` builder fails (should not happen with valid inputs).
+  /// Returns `InitError::AlreadyInitialized` if the global state was already initialized,
+  /// or `InitError::StateBuildError` if the `State` builder fails.
   pub fn init(feature: Feature) -> Result<&'static State, InitError> {
     // 使用链式 Builder 构造 State，然后放入全局单例
-    match StateBuilder::default().feature(feature).build() {
-      Ok(st) => {
-        GLOBAL_STATE.set(st).map_err(|_| InitError::AlreadyInitialized)?;
-        GLOBAL_STATE.get().ok_or(InitError::Uninitialized)
-      }
-      Err(e) => {
-        dbg!(e);
-        Err(InitError::StateBuildError)
-      }
-    }
+    let state = StateBuilder::default()
+      .feature(feature)
+      .build()
+      .map_err(InitError::StateBuildError)?;
+    GLOBAL_STATE.set(state).map_err(|_| InitError::AlreadyInitialized)?;
+    GLOBAL_STATE.get().ok_or(InitError::Uninitialized)
   }
 
   /// 如果尚未初始化，使用提供的闭包懒初始化并返回全局状态引用；如果已经初始化则直接返回引用。
@@ -10,74 +10,234 @@ use parking_lot::RwLock;
 use crate::{
   config::{
     NovelSagaConfig, OverridableConfig, RootConfig,
-    file_def::{CONFIG_FILE_NAMES, IGNORE_CONFIG_FILE_NAMES, get_base_config_file_extensions},
+    error::ConfigError,
+    extends::{
+      ConfigLayer, LayerOrigin, add_config_layers_source, config_layers, config_layers_with_content, extended_files,
+    },
+    file_def::{CONFIG_FILE_NAMES, IGNORE_CONFIG_FILE_NAMES, get_base_config_file_extensions, is_config_file},
+    file_override::FileOverrideMatcher,
     fileformat::NovelSagaFileFormat,
   },
-  state::feat::Feature,
+  discovery::IgnoreMatchers,
+  error::ErrorCode,
+  state::{
+    feat::Feature,
+    manager::provenance::{ExplainedConfig, ProvenanceBuilder},
+  },
 };
 
+/// `ConfigManager` 加载根配置时的错误
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigManagerError {
+  /// 根配置文件存在但无法读取或解析
+  #[error("failed to load root config {}: {source}", path.display())]
+  RootConfig { path: PathBuf, source: config::ConfigError },
+}
+
+impl ErrorCode for ConfigManagerError {
+  fn code(&self) -> &'static str {
+    match self {
+      ConfigManagerError::RootConfig { .. } => "state.root_config",
+    }
+  }
+}
+
+#[derive(Clone, Debug)]
+struct RootState {
+  config: RootConfig,
+  dir: PathBuf,
+  /// 根配置文件（未找到时为 `None`）
+  file: Option<PathBuf>,
+  /// 根配置中的 `overrides`，glob 相对于 `dir`
+  file_overrides: Vec<FileOverrideMatcher>,
+  /// 根配置经由 `extends` 继承的文件（已规范化）
+  extended_files: Vec<PathBuf>,
+}
+
+/// 缓存的覆盖配置及其经由 `extends` 继承的文件（已规范化），后者变更时缓存失效
+#[derive(Clone, Debug)]
+struct CachedOverrideConfig {
+  config: OverridableConfig,
+  extended_files: Vec<PathBuf>,
+}
+
 #[derive(Clone, Debug)]
 pub struct ConfigManager {
-  // 全局根配置（只加载一次，不可修改）
-  root_config: RootConfig,
-  root_dir: PathBuf,
-  cache: Arc<RwLock<HashMap<PathBuf, OverridableConfig>>>,
+  // 根配置（启动时加载，配置文件变更时通过 `reload_root_config` 重新加载）
+  root: Arc<RwLock<RootState>>,
+  start_dir: PathBuf,
+  cache: Arc<RwLock<HashMap<PathBuf, CachedOverrideConfig>>>,
+  // 各目录 ignore 文件编译后的匹配器（文件变更时自动重建）
+  ignore_matchers: Arc<IgnoreMatchers>,
   feature: Feature,
 }
 
 #[allow(dead_code)]
 impl ConfigManager {
-  /// 初始化配置管理器
-  /// 获取root配置
+  /// 以指定目录为起点向上查找并加载根配置（例如 LSP 的某个 workspace folder、CLI 的目标目录）
   ///
-  /// # Panics
+  /// 不依赖进程当前目录；未找到配置文件时使用默认配置，根目录为 `start_dir`。
   ///
-  /// 当无法读取配置文件时会 panic
-  #[must_use]
-  pub fn new(feature: Feature) -> Self {
-    let (root_dir, root_config) = if let Ok(root_config_file) = Self::find_root_config_file(
-      &std::env::current_dir().unwrap(),
-      feature.js_support(),
-      feature.ts_support(),
-    ) {
-      let root_config =
-        Self::load_root_config_file(&root_config_file, &feature).expect("Failed to load root config file");
-      (root_config_file.parent().unwrap().to_path_buf(), root_config)
-    } else {
-      // 未找到配置文件，使用默认配置
-      (std::env::current_dir().unwrap(), NovelSagaConfig::default())
-    };
+  /// # Errors
+  ///
+  /// 找到的根配置文件无法读取或解析时返回 [`ConfigManagerError::RootConfig`]
+  pub fn new(feature: Feature, start_dir: &Path) -> Result<Self, ConfigManagerError> {
+    let root = Self::load_root_state(start_dir, &feature)?;
 
-    Self {
-      root_config: root_config.root,
+    Ok(Self {
+      root: Arc::new(RwLock::new(root)),
+      start_dir: start_dir.to_path_buf(),
       cache: Arc::new(RwLock::new(HashMap::new())),
-      root_dir,
+      ignore_matchers: Arc::new(
+        IgnoreMatchers::new(IGNORE_CONFIG_FILE_NAMES.iter().copied()).case_insensitive(cfg!(target_os = "windows")),
+      ),
       feature,
+    })
+  }
+
+  #[must_use]
+  pub fn get_root_config(&self) -> RootConfig {
+    self.root.read().config.clone()
+  }
+
+  /// 根配置所在目录（未找到配置文件时为起始目录）
+  #[must_use]
+  pub fn root_dir(&self) -> PathBuf {
+    self.root.read().dir.clone()
+  }
+
+  /// 从起始目录重新查找并加载根配置
+  ///
+  /// 根配置目录变化时清空全部覆盖配置缓存（级联的终点随之改变）。
+  ///
+  /// # Errors
+  ///
+  /// 新的根配置无法解析时返回错误，此时保留原有根配置
+  pub fn reload_root_config(&self) -> Result<(), ConfigManagerError> {
+    let root = Self::load_root_state(&self.start_dir, &self.feature)?;
+    let dir_changed = {
+      let mut current = self.root.write();
+      let dir_changed = current.dir != root.dir;
+      *current = root;
+      dir_changed
+    };
+    if dir_changed {
+      self.cache.write().clear();
     }
+    Ok(())
+  }
+
+  /// 删除 `dir` 下所有路径的覆盖配置缓存（该目录中的配置文件变更后调用）
+  pub fn invalidate_override_config_cache_under(&self, dir: &Path) {
+    self.cache.write().retain(|path, _| !path.starts_with(dir));
   }
 
+  /// 已加载的配置（根配置与缓存中的覆盖配置）经由 `extends` 继承的文件，已规范化、排序
+  ///
+  /// 这些文件可能位于工作区之外、不符合配置文件命名，需要单独监听。
   #[must_use]
-  pub fn get_root_config(&self) -> &RootConfig {
-    &self.root_config
+  pub fn extended_config_files(&self) -> Vec<PathBuf> {
+    let mut files = self.root.read().extended_files.clone();
+    files.extend(
+      self
+        .cache
+        .read()
+        .values()
+        .flat_map(|cached| cached.extended_files.iter().cloned()),
+    );
+    files.sort();
+    files.dedup();
+    files
+  }
+
+  /// 被 `extends` 继承的文件变更后调用：删除依赖它的覆盖配置缓存，根配置依赖它时重新加载根配置
+  ///
+  /// # Errors
+  ///
+  /// 同 [`Self::reload_root_config`]
+  pub fn invalidate_extended_config_file(&self, path: &Path) -> Result<(), ConfigManagerError> {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    self
+      .cache
+      .write()
+      .retain(|_, cached| !cached.extended_files.contains(&path));
+    let root_extends = self.root.read().extended_files.contains(&path);
+    if root_extends {
+      self.reload_root_config()?;
+    }
+    Ok(())
+  }
+
+  /// 检查配置文件能否被解析
+  ///
+  /// # Errors
+  ///
+  /// 配置文件无法读取或反序列化时返回 [`ConfigError::Load`]
+  pub fn validate_config_file(&self, path: &Path) -> Result<(), ConfigError> {
+    self
+      .feature
+      .scope(|| Self::load_root_config_file(path, &self.feature))
+      .map(|_| ())
+      .map_err(Self::load_error(path))
+  }
+
+  fn load_root_state(start_dir: &Path, feature: &Feature) -> Result<RootState, ConfigManagerError> {
+    feature.scope(|| Self::load_root_state_scoped(start_dir, feature))
+  }
+
+  fn load_root_state_scoped(start_dir: &Path, feature: &Feature) -> Result<RootState, ConfigManagerError> {
+    if let Ok(root_config_file) = Self::find_root_config_file(start_dir, feature.js_support(), feature.ts_support()) {
+      let dir = root_config_file.parent().unwrap_or(start_dir).to_path_buf();
+      let (root_config, file_overrides, extended_files) = Self::load_root_config_layers(&root_config_file)
+        .and_then(|(config, extended_files)| {
+          let config = feature.config_overrides().apply(&config.root)?;
+          let file_overrides = FileOverrideMatcher::compile(&dir, &config.overrides)?;
+          Ok((config, file_overrides, extended_files))
+        })
+        .map_err(|source| ConfigManagerError::RootConfig {
+          path: root_config_file.clone(),
+          source,
+        })?;
+      Ok(RootState {
+        config: root_config,
+        dir,
+        file: Some(root_config_file),
+        file_overrides,
+        extended_files,
+      })
+    } else {
+      // 未找到配置文件，使用默认配置
+      let defaults = NovelSagaConfig::default().root;
+      Ok(RootState {
+        config: feature.config_overrides().apply(&defaults).unwrap_or(defaults),
+        dir: start_dir.to_path_buf(),
+        file: None,
+        file_overrides: Vec::new(),
+        extended_files: Vec::new(),
+      })
+    }
   }
 
   /// # Errors
   ///
-  /// 当无法读取配置文件时会返回错误
-  pub fn get_override_config(&self, path: &Path) -> Result<OverridableConfig, config::ConfigError> {
+  /// - `path` 被忽略时返回 [`ConfigError::IgnoredFile`]
+  /// - 参与合并的配置文件无法读取或解析时返回 [`ConfigError::Load`]
+  /// - 环境变量或命令行覆盖无法应用时返回 [`ConfigError::Override`]
+  pub fn get_override_config(&self, path: &Path) -> Result<OverridableConfig, ConfigError> {
     // 判断缓存中是否存在（只读锁）
     {
       let cache_read = self.cache.read();
-      if let Some(cfg) = cache_read.get(path) {
-        return Ok(cfg.clone());
+      if let Some(cached) = cache_read.get(path) {
+        return Ok(cached.config.clone());
       }
     }
 
     // 加载配置文件
-    let cfg = self.load_override_config_file(path)?;
+    let cached = self.feature.scope(|| self.load_override_config(path, None))?;
+    let cfg = cached.config.clone();
     // 写入缓存（写锁）
     let mut cache_write = self.cache.write();
-    cache_write.insert(path.to_path_buf(), cfg.clone());
+    cache_write.insert(path.to_path_buf(), cached);
     Ok(cfg)
   }
 
@@ -87,6 +247,7 @@ impl ConfigManager {
   }
 
   fn get_config_files_on_every_parent_dirs(&self, start_path: &Path) -> VecDeque<PathBuf> {
+    let root_dir = self.root_dir();
     let mut current_path = start_path;
     let mut config_files: VecDeque<PathBuf> = VecDeque::new();
 
@@ -99,7 +260,7 @@ impl ConfigManager {
       }
 
       // 向上继续查找，直到 self.root_dir
-      if current_path == self.root_dir {
+      if current_path == root_dir {
         break;
       }
       match current_path.parent() {
@@ -112,130 +273,184 @@ impl ConfigManager {
   }
 
   /// 判断一个配置文件是否被 ignore（支持自定义 ignore 文件名，例如 `.novelsagaignore`）
+  ///
+  /// 只检查根配置目录到该路径之间各级目录中的 ignore 文件（已编译的匹配器会被缓存），复杂度为 O(深度)；
+  /// 根配置目录之外的文件不属于当前工作区，视为被忽略。
   fn is_ignored_config_file(&self, path: &Path) -> bool {
-    // time
-    let start_time = std::time::Instant::now();
-    let mut binding = ignore::WalkBuilder::new(&self.root_dir);
-    let walker_builder = binding
-      .git_global(false)
-      .hidden(false)
-      .follow_links(true)
-      .git_global(false)
-      .require_git(false)
-      .git_exclude(false)
-      .git_ignore(false)
-      .require_git(false)
-      .ignore_case_insensitive(
-        // windows
-        #[cfg(target_os = "windows")]
-        true,
-        // other os
-        #[cfg(not(target_os = "windows"))]
-        false,
-      );
-    for ignore_file_name in IGNORE_CONFIG_FILE_NAMES {
-      walker_builder.add_custom_ignore_filename(ignore_file_name);
+    let root_dir = self.root_dir();
+    if !path.starts_with(&root_dir) {
+      return true;
     }
-    #[cfg(target_arch = "wasm32")]
-    {
-      let walker = walker_builder.build();
-      for result in walker {
-        if let Ok(entry) = result {
-          let entry_path = entry.path();
-          if entry_path == path {
-            return true;
-          }
-        }
+    self.ignore_matchers.is_ignored(&root_dir, path, path.is_dir())
+  }
+
+  fn load_root_config_file(path: &Path, _feature: &Feature) -> Result<NovelSagaConfig, config::ConfigError> {
+    Self::load_root_config_layers(path).map(|(config, _)| config)
+  }
+
+  /// 加载根配置文件，同时返回其经由 `extends` 继承的文件
+  fn load_root_config_layers(path: &Path) -> Result<(NovelSagaConfig, Vec<PathBuf>), config::ConfigError> {
+    // 与覆盖配置共用加载入口，JS/TS 配置通过全局单例获取 loader
+    let layers = config_layers(path)?;
+    let config = add_config_layers_source(config::Config::builder(), path, &layers)?.build()?;
+    Ok((config.try_deserialize()?, extended_files(&layers)))
+  }
+
+  /// 解释 `path` 的生效覆盖配置：合并结果以及每个配置项由哪个文件（哪一行）设置
+  ///
+  /// # Errors
+  ///
+  /// 与 [`Self::get_override_config`] 相同：文件被忽略、无法读取或解析时返回错误
+  pub fn explain_override_config(&self, path: &Path) -> Result<ExplainedConfig, ConfigError> {
+    self.feature.scope(|| self.explain_override_config_impl(path, None))
+  }
+
+  /// 同 [`Self::explain_override_config`]，但 `path` 本身的内容取 `content`（如编辑器中未保存的文本）
+  ///
+  /// # Errors
+  ///
+  /// 同 [`Self::explain_override_config`]
+  pub fn explain_document_config(&self, path: &Path, content: &str) -> Result<ExplainedConfig, ConfigError> {
+    self
+      .feature
+      .scope(|| self.explain_override_config_impl(path, Some(content)))
+  }
+
+  fn explain_override_config_impl(&self, path: &Path, content: Option<&str>) -> Result<ExplainedConfig, ConfigError> {
+    let config = self.load_override_config(path, content)?.config;
+    let mut files = Vec::new();
+    let mut provenance = ProvenanceBuilder::new();
+    let (cascade, own) = self.override_source_files(path);
+    for file in &cascade {
+      Self::explain_file(&mut provenance, &mut files, &Self::file_layers(file, path, content)?);
+    }
+    let root = self.root.read();
+    if let Some(root_file) = &root.file {
+      for file_override in Self::matching_file_overrides(&root.file_overrides, path) {
+        provenance.add_file_override(root_file, file_override);
       }
-      let elapsed = start_time.elapsed();
-      dbg!(elapsed);
-      false
     }
-    #[cfg(not(target_arch = "wasm32"))]
-    {
-      let (tx, rx) = std::sync::mpsc::channel();
-      let walker = walker_builder.build_parallel();
-      walker.run(|| {
-        let tx = tx.clone();
-        Box::new(move |result| {
-          if let Ok(entry) = result {
-            let entry_path = entry.path();
-            if entry_path == path {
-              tx.send(true).unwrap();
-              return ignore::WalkState::Quit;
-            }
-          }
-          ignore::WalkState::Continue
-        })
-      });
-      let elapsed = start_time.elapsed();
-      dbg!(elapsed);
-      // walker returns an entry when the path is visible; invert result so that
-      // true means "ignored" and false means "not ignored"
-      !rx.try_recv().unwrap_or(false)
+    drop(root);
+    if let Some(file) = &own {
+      Self::explain_file(&mut provenance, &mut files, &Self::file_layers(file, path, content)?);
+    }
+    let config_value = serde_json::to_value(&config).map_err(|source| ConfigError::Serialize { source })?;
+    for config_override in self.feature.config_overrides().applicable(&config_value) {
+      provenance.add_override(&config_override.key, &config_override.origin);
     }
+    Ok(provenance.finish(config, files))
   }
 
-  fn load_root_config_file(path: &Path, _feature: &Feature) -> Result<NovelSagaConfig, config::ConfigError> {
-    // 统一使用 config::File 加载，NovelSagaFileFormat 会自动通过全局单例获取 loader
-    let config = config::Config::builder().add_source(config::File::from(path)).build()?;
-    config.try_deserialize()
+  /// 记录一个文件（连同其 `extends`）各图层的来源
+  fn explain_file(provenance: &mut ProvenanceBuilder, files: &mut Vec<PathBuf>, layers: &[ConfigLayer]) {
+    for layer in layers {
+      provenance.add_layer(&layer.origin, &layer.content, &layer.value);
+      if let LayerOrigin::File(path) = &layer.origin {
+        files.push(path.clone());
+      }
+    }
+  }
+
+  /// `file` 展开 `extends` 后的图层；`file` 为 `path` 且给出 `content` 时以其代替磁盘上的内容
+  fn file_layers(file: &Path, path: &Path, content: Option<&str>) -> Result<Vec<ConfigLayer>, ConfigError> {
+    match content {
+      Some(content) if file == path => config_layers_with_content(file, content),
+      _ => config_layers(file),
+    }
+    .map_err(Self::load_error(file))
+  }
+
+  /// 把 `file` 的读取或解析错误包装为 [`ConfigError::Load`]
+  fn load_error(file: &Path) -> impl FnOnce(config::ConfigError) -> ConfigError + '_ {
+    move |source| ConfigError::Load {
+      path: file.to_path_buf(),
+      source: Box::new(source),
+    }
   }
 
-  fn load_override_config_file(&self, path: &Path) -> Result<OverridableConfig, config::ConfigError> {
+  fn load_override_config(&self, path: &Path, content: Option<&str>) -> Result<CachedOverrideConfig, ConfigError> {
     if self.is_ignored_config_file(path) {
-      return Err(config::ConfigError::Message(format!(
-        "Ignored config file: {}",
-        path.display()
-      )));
+      return Err(ConfigError::IgnoredFile {
+        path: path.to_path_buf(),
+      });
     }
-    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
-    // 加载所有父级目录的配置文件
-    let mut cfg_builder_parents = config::Config::builder();
-    for parent_cfg_path in self.get_config_files_on_every_parent_dirs(path.parent().unwrap()) {
-      let config_file_result = Self::find_config_file_in_directory(
-        parent_cfg_path.parent().unwrap(),
-        self.feature.js_support(),
-        self.feature.ts_support(),
-      );
-      if let Ok(cfg_path) = config_file_result {
-        dbg!(cfg_path.display());
-        // 统一使用 config::File，会自动根据扩展名选择合适的 Format
-        cfg_builder_parents = cfg_builder_parents.add_source(config::File::from(cfg_path));
-      }
+    let mut builder = config::Config::builder();
+    let mut extended = Vec::new();
+    let (cascade, own) = self.override_source_files(path);
+    for file in cascade {
+      let layers = Self::file_layers(&file, path, content)?;
+      builder =
+        Self::add_config_file_source(builder, &file, &layers, &mut extended).map_err(Self::load_error(&file))?;
     }
-    dbg!(path.display());
-    let cfg_builder_result = if get_base_config_file_extensions().contains(&ext) {
-      // JSON 等可被 `config` 直接加载的文件
-      Ok(cfg_builder_parents.add_source(config::File::from(path)))
-    } else if NovelSagaFileFormat::Markdown.file_extensions().contains(&ext) {
-      // md loader --- 自定义解析 frontmatter 返回 OverridableConfig
-      Ok(cfg_builder_parents.add_source(config::File::new(path.to_str().unwrap(), NovelSagaFileFormat::Markdown)))
-    } else if NovelSagaFileFormat::JavaScript.file_extensions().contains(&ext) {
-      // JS 配置文件
-      Ok(cfg_builder_parents.add_source(config::File::new(
-        path.to_str().unwrap(),
-        NovelSagaFileFormat::JavaScript,
-      )))
-    } else if NovelSagaFileFormat::TypeScript.file_extensions().contains(&ext) {
-      // TS 配置文件
-      Ok(cfg_builder_parents.add_source(config::File::new(
-        path.to_str().unwrap(),
-        NovelSagaFileFormat::TypeScript,
-      )))
+    let root = self.root.read();
+    for file_override in Self::matching_file_overrides(&root.file_overrides, path) {
+      let source = config::Config::try_from(&file_override.config);
+      builder = builder.add_source(source.map_err(Self::load_error(root.file.as_deref().unwrap_or(path)))?);
+    }
+    drop(root);
+    if let Some(file) = own {
+      let layers = Self::file_layers(&file, path, content)?;
+      builder =
+        Self::add_config_file_source(builder, &file, &layers, &mut extended).map_err(Self::load_error(&file))?;
+    }
+    let config = builder
+      .build()
+      .and_then(config::Config::try_deserialize::<OverridableConfig>)
+      .map_err(Self::load_error(path))?;
+    // 环境变量与命令行覆盖叠加在配置文件之上
+    let config = self
+      .feature
+      .config_overrides()
+      .apply(&config)
+      .map_err(|source| ConfigError::Override {
+        source: Box::new(source),
+      })?;
+    Ok(CachedOverrideConfig {
+      config,
+      extended_files: extended,
+    })
+  }
+
+  /// 将 `file` 展开 `extends` 后的图层加入构建器，并记录其继承的文件
+  fn add_config_file_source(
+    builder: config::ConfigBuilder<config::builder::DefaultState>,
+    file: &Path,
+    layers: &[ConfigLayer],
+    extended: &mut Vec<PathBuf>,
+  ) -> Result<config::ConfigBuilder<config::builder::DefaultState>, config::ConfigError> {
+    extended.extend(extended_files(layers));
+    add_config_layers_source(builder, file, layers)
+  }
+
+  /// 参与 `path` 覆盖配置合并的文件，优先级从低到高：各级父目录的配置文件，最后是 `path` 本身
+  ///
+  /// 分为目录级联与文件自身两部分，根配置的 `overrides` 夹在两者之间；
+  /// `path` 本身是配置文件时属于目录级联。
+  fn override_source_files(&self, path: &Path) -> (Vec<PathBuf>, Option<PathBuf>) {
+    let mut cascade: Vec<PathBuf> = path
+      .parent()
+      .map(|dir| self.get_config_files_on_every_parent_dirs(dir).into())
+      .unwrap_or_default();
+    if is_config_file(path) {
+      cascade.push(path.to_path_buf());
+      (cascade, None)
     } else {
-      Err(config::ConfigError::Message(format!(
-        "Unsupported config file extension: {ext}"
-      )))
-    };
+      (cascade, Some(path.to_path_buf()))
+    }
+  }
 
-    match cfg_builder_result {
-      Ok(builder) => {
-        let cfg = builder.build()?;
-        cfg.try_deserialize::<OverridableConfig>()
-      }
-      Err(e) => Err(e),
+  /// 根配置 `overrides` 中匹配 `path` 的段，按声明顺序；配置文件本身不受其影响
+  fn matching_file_overrides<'a>(
+    file_overrides: &'a [FileOverrideMatcher],
+    path: &Path,
+  ) -> Vec<&'a FileOverrideMatcher> {
+    if is_config_file(path) {
+      return Vec::new();
     }
+    file_overrides
+      .iter()
+      .filter(|file_override| file_override.matches(path))
+      .collect()
   }
 
   fn load_override_config_dir(path: &Path) -> Result<OverridableConfig, config::ConfigError> {
@@ -309,14 +524,12 @@ impl ConfigManager {
       }
     }
 
-    dbg!("No workspace config found, searching for top-level config file...");
     // 未找到包含 workspace 字段的配置文件，返回最顶层找到的配置文件（若有）
     let root_file = searched_paths
       .into_iter()
       .rev()
       .find(|path| path.try_exists().unwrap_or(false));
-    if let Some(root_file) = root_file {
-      let parent_dir = root_file.parent().unwrap();
+    if let Some(parent_dir) = root_file.as_deref().and_then(Path::parent) {
       // 在其同级目录中复用 `find_config_file_in_directory`，返回字母表最前的配置文件
       return Self::find_config_file_in_directory(parent_dir, js_supported, ts_supported)
         .map_err(|_| std::io::Error::new(std::io::ErrorKind::NotFound, "No top-level config file found"));
@@ -359,8 +572,12 @@ mod test {
       .join("sub");
     let assets_test_md = assets_test_md_dir.clone().join("test.md");
     dbg!(&assets_test_md);
-    let manager = super::ConfigManager::new(Feature::new(None, None));
-    let result = manager.load_override_config_file(&assets_test_md);
+    let manager = super::ConfigManager::new(
+      Feature::new(None, None),
+      std::path::Path::new(env!("CARGO_MANIFEST_DIR")),
+    )
+    .unwrap();
+    let result = manager.get_override_config(&assets_test_md);
     if let Err(e) = &result {
       eprintln!("Error loading config: {e}");
     }
@@ -369,7 +586,7 @@ mod test {
     dbg!(&config);
     assert!(config.fmt.indent_spaces == 3);
     let assets_test_md_no_matter = assets_test_md_dir.join("test-no-matter.markdown");
-    let result_no_matter = manager.load_override_config_file(&assets_test_md_no_matter);
+    let result_no_matter = manager.get_override_config(&assets_test_md_no_matter);
     if let Err(e) = &result_no_matter {
       eprintln!("Error loading config without matter: {e}");
     }
@@ -377,7 +594,7 @@ mod test {
     dbg!(&config_no_matter);
     assert!(config_no_matter.fmt.indent_spaces == 1);
     let sub_sub_dir = assets_test_md_dir.join("sub").join("novelsaga.config.json");
-    let result_sub_sub = manager.load_override_config_file(&sub_sub_dir);
+    let result_sub_sub = manager.get_override_config(&sub_sub_dir);
     if let Err(e) = &result_sub_sub {
       eprintln!("Error loading sub/sub config: {e}");
     }
@@ -387,9 +604,116 @@ mod test {
     assert!(config_sub_sub.fmt.indent_spaces == 5);
   }
 
+  #[test]
+  fn test_explain_override_config() {
+    use crate::state::{ConfigSource, feat::Feature};
+    let sub_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+      .join("assets")
+      .join("test")
+      .join("config")
+      .join("config_manager")
+      .join("sub");
+    let manager = super::ConfigManager::new(
+      Feature::new(None, None),
+      std::path::Path::new(env!("CARGO_MANIFEST_DIR")),
+    )
+    .unwrap();
+
+    let explained = manager.explain_override_config(&sub_dir.join("test.md")).unwrap();
+
+    assert_eq!(explained.config.fmt.indent_spaces, 3);
+    assert_eq!(explained.files.last(), Some(&sub_dir.join("test.md")));
+    let indent = explained.entry("fmt.indent_spaces").unwrap();
+    assert_eq!(indent.value, serde_json::json!(3));
+    assert_eq!(
+      indent.source,
+      ConfigSource::File {
+        path: sub_dir.join("test.md"),
+        line: Some(3),
+      }
+    );
+    assert_eq!(
+      explained.entry("fmt.blank_lines_between_paragraphs").unwrap().source,
+      ConfigSource::File {
+        path: sub_dir.join("novelsaga.config.yaml"),
+        line: Some(3),
+      }
+    );
+  }
+
+  #[test]
+  fn test_explain_document_config_uses_unsaved_content() {
+    use crate::state::{ConfigSource, feat::Feature};
+    let test_md = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+      .join("assets")
+      .join("test")
+      .join("config")
+      .join("config_manager")
+      .join("sub")
+      .join("test.md");
+    let manager = super::ConfigManager::new(
+      Feature::new(None, None),
+      std::path::Path::new(env!("CARGO_MANIFEST_DIR")),
+    )
+    .unwrap();
+
+    let explained = manager
+      .explain_document_config(&test_md, "---\nfmt:\n  indent_spaces: 7\n---\n")
+      .unwrap();
+    assert_eq!(explained.config.fmt.indent_spaces, 7);
+    assert_eq!(
+      explained.entry("fmt.indent_spaces").unwrap().source,
+      ConfigSource::File {
+        path: test_md.clone(),
+        line: Some(3),
+      }
+    );
+    assert_eq!(manager.get_override_config(&test_md).unwrap().fmt.indent_spaces, 3);
+  }
+
+  #[test]
+  fn test_overrides_layer_on_top_of_config_files() {
+    use crate::{
+      config::overrides::{ConfigOverrides, OverrideOrigin},
+      state::{ConfigSource, feat::Feature},
+    };
+    let sub_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+      .join("assets")
+      .join("test")
+      .join("config")
+      .join("config_manager")
+      .join("sub");
+    let overrides = ConfigOverrides::from_sources(
+      [("NOVELSAGA__FMT__INDENT_SPACES".to_string(), "0".to_string())],
+      &["workspace.cache_dir=build/cache".to_string()],
+    )
+    .unwrap();
+    let manager = super::ConfigManager::new(
+      Feature::new(None, None).with_config_overrides(overrides),
+      std::path::Path::new(env!("CARGO_MANIFEST_DIR")),
+    )
+    .unwrap();
+
+    assert_eq!(manager.get_root_config().workspace.unwrap().cache_dir, "build/cache");
+    let explained = manager.explain_override_config(&sub_dir.join("test.md")).unwrap();
+    assert_eq!(explained.config.fmt.indent_spaces, 0);
+    assert_eq!(
+      explained.entry("fmt.indent_spaces").unwrap().source,
+      ConfigSource::Override(OverrideOrigin::Env("NOVELSAGA__FMT__INDENT_SPACES".to_string()))
+    );
+    assert_eq!(
+      manager
+        .get_override_config(&sub_dir.join("test.md"))
+        .unwrap()
+        .fmt
+        .indent_spaces,
+      0
+    );
+  }
+
   #[test]
   fn test_is_ignored_config_file() {
-    use crate::state::feat::Feature;
+    use crate::{error::ErrorCode, state::feat::Feature};
     let current_dir = env!("CARGO_MANIFEST_DIR");
     let assets_test_ignore_dir = std::path::PathBuf::from(current_dir)
       .join("assets")
@@ -400,10 +724,17 @@ mod test {
       .join("sub")
       .join("test-ignore.md");
     dbg!(&assets_test_ignore_dir);
-    let manager = super::ConfigManager::new(Feature::new(None, None));
+    let manager = super::ConfigManager::new(
+      Feature::new(None, None),
+      std::path::Path::new(env!("CARGO_MANIFEST_DIR")),
+    )
+    .unwrap();
     let is_ignored = manager.is_ignored_config_file(&assets_test_ignore_dir);
     dbg!(is_ignored);
     assert!(is_ignored);
+    let error = manager.get_override_config(&assets_test_ignore_dir).unwrap_err();
+    assert_eq!(error.code(), "config.ignored_file");
+    assert!(matches!(&error, super::ConfigError::IgnoredFile { path } if *path == assets_test_ignore_dir));
   }
 
   #[test]
@@ -421,7 +752,11 @@ mod test {
       .join("watcher_test");
     let test_config_path = assets_test_watcher_dir.join("novelsaga.config.json");
     dbg!(&test_config_path);
-    let manager = super::ConfigManager::new(Feature::new(None, None));
+    let manager = super::ConfigManager::new(
+      Feature::new(None, None),
+      std::path::Path::new(env!("CARGO_MANIFEST_DIR")),
+    )
+    .unwrap();
     // 首次加载
     let result1 = manager.get_override_config(&test_config_path);
     assert!(result1.is_ok());
@@ -429,7 +764,11 @@ mod test {
     dbg!(&config1);
     assert!(config1.fmt.indent_spaces == 2);
     // 直接读取cache
-    let result_cached = manager.cache.read().get(&test_config_path).cloned();
+    let result_cached = manager
+      .cache
+      .read()
+      .get(&test_config_path)
+      .map(|cached| cached.config.clone());
     assert!(result_cached.is_some());
     let cached_config = result_cached.unwrap();
     dbg!(&cached_config);
@@ -446,11 +785,227 @@ mod test {
     let config2 = result2.unwrap();
     dbg!(&config2);
     assert!(config2.fmt.indent_spaces == 4);
-    let result_cached2 = manager.cache.read().get(&test_config_path).cloned();
+    let result_cached2 = manager
+      .cache
+      .read()
+      .get(&test_config_path)
+      .map(|cached| cached.config.clone());
     assert!(result_cached2.is_some());
     let cached_config2 = result_cached2.unwrap();
     assert!(cached_config2.fmt.indent_spaces == 4);
     // 恢复原始配置文件
     fs::write(&test_config_path, r#"{ "fmt": { "indent_spaces": 2 } }"#).expect("Unable to write file");
   }
+
+  #[test]
+  fn test_reload_root_config_and_invalidate_cache_under_dir() {
+    use std::fs;
+
+    use crate::state::feat::Feature;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let sub = root.join("sub");
+    fs::create_dir_all(&sub).unwrap();
+    fs::write(
+      root.join("novelsaga.config.json"),
+      r#"{ "workspace": { "cache_dir": "a" }, "fmt": { "indent_spaces": 2 } }"#,
+    )
+    .unwrap();
+    fs::write(
+      sub.join("novelsaga.config.json"),
+      r#"{ "fmt": { "indent_spaces": 3 } }"#,
+    )
+    .unwrap();
+
+    let manager = super::ConfigManager::new(Feature::new(None, None), &sub).unwrap();
+    assert_eq!(manager.get_root_config().workspace.unwrap().cache_dir, "a");
+    let root_cfg = root.join("novelsaga.config.json");
+    let sub_cfg = sub.join("novelsaga.config.json");
+    assert_eq!(manager.get_override_config(&root_cfg).unwrap().fmt.indent_spaces, 2);
+    assert_eq!(manager.get_override_config(&sub_cfg).unwrap().fmt.indent_spaces, 3);
+
+    fs::write(
+      sub.join("novelsaga.config.json"),
+      r#"{ "fmt": { "indent_spaces": 6 } }"#,
+    )
+    .unwrap();
+    manager.invalidate_override_config_cache_under(&sub);
+    assert_eq!(manager.get_override_config(&sub_cfg).unwrap().fmt.indent_spaces, 6);
+    assert!(manager.cache.read().contains_key(&root_cfg));
+
+    fs::write(
+      root.join("novelsaga.config.json"),
+      r#"{ "workspace": { "cache_dir": "b" } }"#,
+    )
+    .unwrap();
+    manager.reload_root_config().unwrap();
+    assert_eq!(manager.get_root_config().workspace.unwrap().cache_dir, "b");
+
+    fs::write(root.join("novelsaga.config.json"), "{ not json").unwrap();
+    let error = manager.validate_config_file(&root_cfg).unwrap_err();
+    assert!(matches!(&error, super::ConfigError::Load { path, .. } if *path == root_cfg));
+    assert!(manager.reload_root_config().is_err());
+    assert_eq!(manager.get_root_config().workspace.unwrap().cache_dir, "b");
+
+    // 构建时根配置损坏返回错误而不是 panic
+    let error = super::ConfigManager::new(Feature::new(None, None), &sub).unwrap_err();
+    assert!(matches!(
+      &error,
+      super::ConfigManagerError::RootConfig { path, .. } if *path == root_cfg
+    ));
+  }
+
+  #[test]
+  fn test_extends_merges_presets_and_files_with_provenance() {
+    use std::fs;
+
+    use crate::state::{ConfigSource, feat::Feature};
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let sub = root.join("sub");
+    fs::create_dir_all(&sub).unwrap();
+    fs::write(root.join("house.yaml"), "fmt:\n  blank_lines_between_paragraphs: 2\n").unwrap();
+    fs::write(
+      root.join("novelsaga.config.yaml"),
+      "extends: [en-manuscript, ./house.yaml]\nworkspace:\n  cache_dir: a\n",
+    )
+    .unwrap();
+    fs::write(sub.join("chapter.md"), "# Chapter\n").unwrap();
+
+    let manager = super::ConfigManager::new(Feature::new(None, None), &sub).unwrap();
+    assert_eq!(manager.get_root_config().workspace.unwrap().cache_dir, "a");
+
+    let explained = manager.explain_override_config(&sub.join("chapter.md")).unwrap();
+    assert_eq!(explained.config.fmt.indent_spaces, 0);
+    assert_eq!(explained.config.fmt.blank_lines_between_paragraphs, 2);
+    assert_eq!(
+      explained.entry("fmt.indent_spaces").unwrap().source.to_string(),
+      "preset en-manuscript:3"
+    );
+    assert_eq!(
+      explained.entry("fmt.blank_lines_between_paragraphs").unwrap().source,
+      ConfigSource::File {
+        path: root.join("./house.yaml"),
+        line: Some(2),
+      }
+    );
+    assert_eq!(
+      explained.files,
+      vec![
+        root.join("./house.yaml"),
+        root.join("novelsaga.config.yaml"),
+        sub.join("chapter.md")
+      ]
+    );
+  }
+
+  #[test]
+  fn test_extended_file_changes_invalidate_dependents() {
+    use std::fs;
+
+    use crate::state::feat::Feature;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let sub = root.join("sub");
+    fs::create_dir_all(&sub).unwrap();
+    fs::write(root.join("house.yaml"), "fmt:\n  indent_spaces: 2\n").unwrap();
+    fs::write(
+      root.join("sub-style.yaml"),
+      "fmt:\n  blank_lines_between_paragraphs: 1\n",
+    )
+    .unwrap();
+    fs::write(root.join("novelsaga.config.yaml"), "extends: [./house.yaml]\n").unwrap();
+    fs::write(sub.join(".novelsaga.yaml"), "extends: [../sub-style.yaml]\n").unwrap();
+    fs::write(sub.join("chapter.md"), "# Chapter\n").unwrap();
+
+    let manager = super::ConfigManager::new(Feature::new(None, None), &sub).unwrap();
+    let chapter = sub.join("chapter.md");
+    assert_eq!(manager.get_override_config(&chapter).unwrap().fmt.indent_spaces, 2);
+    let house = root.join("house.yaml").canonicalize().unwrap();
+    let sub_style = root.join("sub-style.yaml").canonicalize().unwrap();
+    assert_eq!(manager.extended_config_files(), vec![house, sub_style.clone()]);
+
+    fs::write(
+      root.join("house.yaml"),
+      "workspace:\n  cache_dir: b\nfmt:\n  indent_spaces: 6\n",
+    )
+    .unwrap();
+    manager
+      .invalidate_extended_config_file(&root.join("house.yaml"))
+      .unwrap();
+    assert_eq!(manager.get_override_config(&chapter).unwrap().fmt.indent_spaces, 6);
+    assert_eq!(manager.get_root_config().workspace.unwrap().cache_dir, "b");
+
+    fs::write(
+      root.join("sub-style.yaml"),
+      "fmt:\n  blank_lines_between_paragraphs: 3\n",
+    )
+    .unwrap();
+    manager.invalidate_extended_config_file(&sub_style).unwrap();
+    let config = manager.get_override_config(&chapter).unwrap();
+    assert_eq!(config.fmt.blank_lines_between_paragraphs, 3);
+  }
+
+  #[test]
+  fn test_file_overrides_apply_between_directory_cascade_and_frontmatter() {
+    use std::fs;
+
+    use crate::state::feat::Feature;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let root = temp_dir.path();
+    let drafts = root.join("drafts");
+    fs::create_dir_all(&drafts).unwrap();
+    fs::write(
+      root.join("novelsaga.config.yaml"),
+      "workspace: {}\nfmt:\n  indent_spaces: 2\noverrides:\n  - files: drafts/**\n    fmt:\n      indent_spaces: 0\n      \
+       blank_lines_between_paragraphs: 3\n  - files: drafts/late.md\n    fmt:\n      blank_lines_between_paragraphs: 5\n",
+    )
+    .unwrap();
+    fs::write(drafts.join("novelsaga.config.yaml"), "fmt:\n  indent_spaces: 6\n").unwrap();
+    fs::write(
+      drafts.join("ch1.md"),
+      "---\nfmt:\n  blank_lines_between_paragraphs: 2\n---\n",
+    )
+    .unwrap();
+    fs::write(drafts.join("late.md"), "# Late\n").unwrap();
+    fs::write(root.join("ch.md"), "# Chapter\n").unwrap();
+
+    let manager = super::ConfigManager::new(Feature::new(None, None), root).unwrap();
+    assert_eq!(manager.get_root_config().overrides.len(), 2);
+
+    let ch1 = manager.get_override_config(&drafts.join("ch1.md")).unwrap();
+    assert_eq!((ch1.fmt.indent_spaces, ch1.fmt.blank_lines_between_paragraphs), (0, 2));
+    let late = manager.get_override_config(&drafts.join("late.md")).unwrap();
+    assert_eq!(
+      (late.fmt.indent_spaces, late.fmt.blank_lines_between_paragraphs),
+      (0, 5)
+    );
+    let outside = manager.get_override_config(&root.join("ch.md")).unwrap();
+    assert_eq!(outside.fmt.indent_spaces, 2);
+    // 配置文件本身只参与目录级联
+    let dir_config = manager
+      .get_override_config(&drafts.join("novelsaga.config.yaml"))
+      .unwrap();
+    assert_eq!(dir_config.fmt.indent_spaces, 6);
+
+    let explained = manager.explain_override_config(&drafts.join("late.md")).unwrap();
+    assert_eq!(
+      explained.entry("fmt.indent_spaces").unwrap().source.to_string(),
+      format!(
+        "{} overrides[0] (drafts/**)",
+        root.join("novelsaga.config.yaml").display()
+      )
+    );
+
+    fs::write(
+      root.join("novelsaga.config.yaml"),
+      "workspace: {}\noverrides:\n  - files: \"[\"\n",
+    )
+    .unwrap();
+    assert!(manager.reload_root_config().is_err());
+  }
 }
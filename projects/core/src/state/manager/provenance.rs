@@ -0,0 +1,243 @@
+//! 配置来源追踪：记录每个生效配置项由哪个文件（及行号）设置
+
+use std::{
+  collections::BTreeMap,
+  fmt,
+  path::{Path, PathBuf},
+};
+
+use serde_json::Value;
+
+use crate::config::{
+  OverridableConfig, extends::LayerOrigin, file_override::FileOverrideMatcher, overrides::OverrideOrigin,
+};
+
+/// 一个生效配置项的来源
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+  /// 没有任何配置文件设置该项，使用默认值
+  Default,
+  /// 由配置文件（或 markdown frontmatter）设置；`line` 从 1 开始，无法定位时为 `None`
+  File { path: PathBuf, line: Option<usize> },
+  /// 由配置文件 `extends` 的内置预设设置
+  Preset { name: &'static str, line: Option<usize> },
+  /// 由根配置 `overrides` 中的第 `index` 段设置，`files` 为该段的 glob
+  FileOverride {
+    path: PathBuf,
+    index: usize,
+    files: Vec<String>,
+  },
+  /// 由环境变量或 `--config` 参数覆盖
+  Override(OverrideOrigin),
+}
+
+impl fmt::Display for ConfigSource {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ConfigSource::Default => f.write_str("default"),
+      ConfigSource::File { path, line: Some(line) } => write!(f, "{}:{line}", path.display()),
+      ConfigSource::File { path, line: None } => write!(f, "{}", path.display()),
+      ConfigSource::Preset { name, line: Some(line) } => write!(f, "preset {name}:{line}"),
+      ConfigSource::Preset { name, line: None } => write!(f, "preset {name}"),
+      ConfigSource::FileOverride { path, index, files } => {
+        write!(f, "{} overrides[{index}] ({})", path.display(), files.join(", "))
+      }
+      ConfigSource::Override(origin) => write!(f, "{origin}"),
+    }
+  }
+}
+
+/// 一个生效配置项：点分键名（如 `fmt.indent_spaces`）、合并后的值与来源
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigEntry {
+  pub key: String,
+  pub value: Value,
+  pub source: ConfigSource,
+}
+
+/// 合并后的覆盖配置及其来源
+#[derive(Debug, Clone)]
+pub struct ExplainedConfig {
+  pub config: OverridableConfig,
+  /// 每个配置项，按键名排序
+  pub entries: Vec<ConfigEntry>,
+  /// 参与合并的文件，优先级从低到高
+  pub files: Vec<PathBuf>,
+}
+
+impl ExplainedConfig {
+  #[must_use]
+  pub fn entry(&self, key: &str) -> Option<&ConfigEntry> {
+    self.entries.iter().find(|entry| entry.key == key)
+  }
+}
+
+/// 逐层记录来源：后加入的文件覆盖先前的来源，只追踪 `OverridableConfig` 中存在的键
+pub(crate) struct ProvenanceBuilder {
+  sources: BTreeMap<String, ConfigSource>,
+}
+
+impl ProvenanceBuilder {
+  pub(crate) fn new() -> Self {
+    let defaults = serde_json::to_value(OverridableConfig::default()).unwrap_or_default();
+    Self {
+      sources: leaf_values(&defaults)
+        .into_keys()
+        .map(|key| (key, ConfigSource::Default))
+        .collect(),
+    }
+  }
+
+  /// 记录 `layer`（单个文件或预设解析出的配置）设置的键；`content` 用于定位行号
+  pub(crate) fn add_layer(&mut self, origin: &LayerOrigin, content: &str, layer: &Value) {
+    for key in leaf_values(layer).into_keys() {
+      if let Some(source) = self.sources.get_mut(&key) {
+        let line = find_key_line(content, &key);
+        *source = match origin {
+          LayerOrigin::File(path) => ConfigSource::File {
+            path: path.clone(),
+            line,
+          },
+          LayerOrigin::Preset(name) => ConfigSource::Preset { name, line },
+        };
+      }
+    }
+  }
+
+  /// 记录根配置 `overrides` 中一段设置的键
+  pub(crate) fn add_file_override(&mut self, path: &Path, file_override: &FileOverrideMatcher) {
+    for key in leaf_values(&file_override.config).into_keys() {
+      if let Some(source) = self.sources.get_mut(&key) {
+        *source = ConfigSource::FileOverride {
+          path: path.to_path_buf(),
+          index: file_override.index,
+          files: file_override.files.as_slice().to_vec(),
+        };
+      }
+    }
+  }
+
+  /// 记录环境变量或命令行覆盖的键
+  pub(crate) fn add_override(&mut self, key: &str, origin: &OverrideOrigin) {
+    if let Some(source) = self.sources.get_mut(key) {
+      *source = ConfigSource::Override(origin.clone());
+    }
+  }
+
+  pub(crate) fn finish(self, config: OverridableConfig, files: Vec<PathBuf>) -> ExplainedConfig {
+    let values = serde_json::to_value(&config)
+      .map(|value| leaf_values(&value))
+      .unwrap_or_default();
+    let entries = self
+      .sources
+      .into_iter()
+      .map(|(key, source)| ConfigEntry {
+        value: values.get(&key).cloned().unwrap_or(Value::Null),
+        key,
+        source,
+      })
+      .collect();
+    ExplainedConfig { config, entries, files }
+  }
+}
+
+/// 把嵌套对象展开为 `a.b.c → 值`
+fn leaf_values(value: &Value) -> BTreeMap<String, Value> {
+  fn walk(prefix: &str, value: &Value, out: &mut BTreeMap<String, Value>) {
+    match value {
+      Value::Object(map) => {
+        for (key, child) in map {
+          let key = if prefix.is_empty() {
+            key.clone()
+          } else {
+            format!("{prefix}.{key}")
+          };
+          walk(&key, child, out);
+        }
+      }
+      leaf => {
+        out.insert(prefix.to_string(), leaf.clone());
+      }
+    }
+  }
+
+  let mut out = BTreeMap::new();
+  walk("", value, &mut out);
+  out
+}
+
+/// 在配置文本中定位点分键名所在的行（从 1 开始）
+///
+/// 逐段向下查找：每一段须出现在某行的键部分（`:`/`=` 之前，或 TOML 的 `[section]`），
+/// 且不早于上一段所在行；适用于 JSON/YAML/TOML/JS 及 frontmatter 的常见写法。
+pub(crate) fn find_key_line(content: &str, key: &str) -> Option<usize> {
+  let lines: Vec<&str> = content.lines().collect();
+  let mut from = 0;
+  for segment in key.split('.') {
+    let offset = lines[from..].iter().position(|line| line_declares_key(line, segment))?;
+    from += offset;
+  }
+  Some(from + 1)
+}
+
+fn line_declares_key(line: &str, segment: &str) -> bool {
+  let line = line.trim_start();
+  if line.starts_with("//") || line.starts_with('#') {
+    return false;
+  }
+  let key_part = line.split([':', '=']).next().unwrap_or(line);
+  key_part
+    .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-'))
+    .any(|word| word == segment)
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::{ConfigSource, ProvenanceBuilder, find_key_line};
+  use crate::config::{OverridableConfig, extends::LayerOrigin};
+
+  #[test]
+  fn find_key_line_handles_common_syntaxes() {
+    let yaml = "fmt:\n  indent_spaces: 1\n  \"blank_lines_between_paragraphs\": 2";
+    assert_eq!(find_key_line(yaml, "fmt.indent_spaces"), Some(2));
+    assert_eq!(find_key_line(yaml, "fmt.blank_lines_between_paragraphs"), Some(3));
+
+    let json5 = "{\n  // indent_spaces in a comment\n  fmt: {\n    indent_spaces: 2,\n  },\n}";
+    assert_eq!(find_key_line(json5, "fmt.indent_spaces"), Some(4));
+
+    let toml = "[fmt]\nindent_spaces = 2\n";
+    assert_eq!(find_key_line(toml, "fmt.indent_spaces"), Some(2));
+    assert_eq!(find_key_line("fmt.indent_spaces = 2", "fmt.indent_spaces"), Some(1));
+    assert_eq!(find_key_line(toml, "fmt.missing"), None);
+  }
+
+  #[test]
+  fn later_layers_override_earlier_sources() {
+    let mut builder = ProvenanceBuilder::new();
+    builder.add_layer(
+      &LayerOrigin::File("/a/novelsaga.config.json".into()),
+      "{ \"fmt\": { \"indent_spaces\": 2 } }",
+      &json!({ "fmt": { "indent_spaces": 2 } }),
+    );
+    builder.add_layer(
+      &LayerOrigin::File("/a/b/chapter.md".into()),
+      "---\nfmt:\n  indent_spaces: 3\n  unknown: 1\n---\n",
+      &json!({ "fmt": { "indent_spaces": 3, "unknown": 1 } }),
+    );
+    let explained = builder.finish(OverridableConfig::default(), Vec::new());
+
+    assert_eq!(
+      explained.entry("fmt.indent_spaces").map(|entry| &entry.source),
+      Some(&ConfigSource::File {
+        path: "/a/b/chapter.md".into(),
+        line: Some(3),
+      })
+    );
+    let blank_lines = explained.entry("fmt.blank_lines_between_paragraphs").expect("entry");
+    assert_eq!(blank_lines.source, ConfigSource::Default);
+    assert_eq!(blank_lines.value, json!(1));
+    assert!(explained.entry("fmt.unknown").is_none());
+  }
+}
@@ -3,3 +3,8 @@ pub mod feat;
 pub mod init;
 pub use _state::{State, StateBuilderError};
 mod manager;
+pub(crate) use manager::provenance::find_key_line;
+pub use manager::{
+  config::{ConfigManager, ConfigManagerError},
+  provenance::{ConfigEntry, ConfigSource, ExplainedConfig},
+};
@@ -20,8 +20,8 @@ use tower_lsp::{
   jsonrpc::{self, ErrorCode},
   lsp_types::{
     ClientCapabilities, CompletionContext, CompletionParams, CompletionResponse, CompletionTriggerKind,
-    DidChangeTextDocumentParams, DidChangeWatchedFilesClientCapabilities, DidChangeWatchedFilesParams,
-    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentFormattingParams,
+    DiagnosticServerCapabilities, DidChangeTextDocumentParams, DidChangeWatchedFilesClientCapabilities,
+    DidChangeWatchedFilesParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentFormattingParams,
     DynamicRegistrationClientCapabilities, FileChangeType, FileEvent, FormattingOptions, Hover, HoverParams,
     HoverProviderCapability, InitializeParams, InitializeResult, OneOf, Position, PublishDiagnosticsParams, ServerInfo,
     TextDocumentClientCapabilities, TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
@@ -1087,8 +1087,8 @@ fn client_capabilities() -> ClientCapabilities {
 
 fn assert_core_capabilities(result: &InitializeResult) -> Result<()> {
   match result.capabilities.text_document_sync {
-    Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)) => {}
-    ref other => bail!("expected FULL textDocumentSync, got {other:?}"),
+    Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::INCREMENTAL)) => {}
+    ref other => bail!("expected INCREMENTAL textDocumentSync, got {other:?}"),
   }
 
   match result.capabilities.document_formatting_provider {
@@ -1112,6 +1112,11 @@ fn assert_core_capabilities(result: &InitializeResult) -> Result<()> {
     ref other => bail!("expected completion provider with no trigger characters, got {other:?}"),
   }
 
+  match &result.capabilities.diagnostic_provider {
+    Some(DiagnosticServerCapabilities::Options(options)) if options.workspace_diagnostics => {}
+    ref other => bail!("expected pull diagnostics with workspace diagnostics, got {other:?}"),
+  }
+
   Ok(())
 }
 